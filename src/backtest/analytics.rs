@@ -0,0 +1,460 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+
+use csv::Writer;
+use serde::Serialize;
+
+use crate::backtest::report::{BacktestReport, mean_std};
+use crate::data::bar::Bar;
+use crate::data::feed::Symbol;
+use crate::portfolio::optimization::{ReturnMatrix, covariance};
+
+/// Value-at-Risk and Expected Shortfall at one confidence level, as positive losses.
+/// From equity curves they are fractions of equity per period; from positions, money.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct VarEstimate {
+    pub confidence: f64,
+    pub historical_var: f64,
+    pub historical_cvar: f64,
+    /// Normal approximation from the sample mean and standard deviation.
+    pub parametric_var: f64,
+    pub parametric_cvar: f64,
+}
+
+impl VarEstimate {
+    /// Estimates from a sample of per-period returns (or PnLs); zeros when it is empty.
+    pub fn from_returns(returns: &[f64], confidence: f64) -> Self {
+        if returns.is_empty() {
+            return Self { confidence, ..Default::default() };
+        }
+        let (historical_var, historical_cvar) = historical_var(returns, confidence);
+        let (parametric_var, parametric_cvar) = parametric_var(returns, confidence);
+        Self { confidence, historical_var, historical_cvar, parametric_var, parametric_cvar }
+    }
+}
+
+/// Empirical VaR and CVaR: the loss at the `1 - confidence` quantile and the mean loss
+/// of the returns at or beyond it.
+pub fn historical_var(returns: &[f64], confidence: f64) -> (f64, f64) {
+    let mut sorted: Vec<f64> = returns.iter().copied().filter(|r| r.is_finite()).collect();
+    if sorted.is_empty() {
+        return (0.0, 0.0);
+    }
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    // Tolerance so that e.g. 5% of 100 is 5 despite 1.0 - 0.95 being slightly above 0.05.
+    let tail = (((1.0 - confidence) * sorted.len() as f64 - 1e-9).ceil() as usize).clamp(1, sorted.len());
+    let cvar = -sorted[..tail].iter().sum::<f64>() / tail as f64;
+    (-sorted[tail - 1], cvar)
+}
+
+/// Gaussian VaR and CVaR from the sample mean and standard deviation.
+pub fn parametric_var(returns: &[f64], confidence: f64) -> (f64, f64) {
+    let (mean, std) = mean_std(returns);
+    let z = normal_quantile(confidence);
+    (std * z - mean, std * normal_pdf(z) / (1.0 - confidence) - mean)
+}
+
+fn normal_pdf(x: f64) -> f64 {
+    (-0.5 * x * x).exp() / (2.0 * std::f64::consts::PI).sqrt()
+}
+
+/// Inverse standard normal CDF (Acklam's rational approximation, ~1e-9 relative error).
+pub fn normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969683028665376e1, 2.209460984245205e2, -2.759285104469687e2,
+        1.38357751867269e2, -3.066479806614716e1, 2.506628277459239,
+    ];
+    const B: [f64; 5] = [
+        -5.447609879822406e1, 1.615858368580409e2, -1.556989798598866e2,
+        6.680131188771972e1, -1.328068155288572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784894002430293e-3, -3.223964580411365e-1, -2.400758277161838,
+        -2.549732539343734, 4.374664141464968, 2.938163982698783,
+    ];
+    const D: [f64; 4] = [7.784695709041462e-3, 3.224671290700398e-1, 2.445134137142996, 3.754408661907416];
+    if p <= 0.0 {
+        return f64::NEG_INFINITY;
+    }
+    if p >= 1.0 {
+        return f64::INFINITY;
+    }
+    let tail = |q: f64| {
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    };
+    if p < 0.02425 {
+        tail((-2.0 * p.ln()).sqrt())
+    } else if p > 1.0 - 0.02425 {
+        -tail((-2.0 * (1.0 - p).ln()).sqrt())
+    } else {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    }
+}
+
+/// Sample skewness (third standardized moment); 0 for fewer than 3 values or no dispersion.
+pub fn skewness(values: &[f64]) -> f64 {
+    let (m2, m3) = (central_moment(values, 2), central_moment(values, 3));
+    if values.len() < 3 || m2 <= 0.0 {
+        return 0.0;
+    }
+    m3 / m2.powf(1.5)
+}
+
+/// Excess kurtosis (fourth standardized moment minus 3, so a normal sample is near 0).
+pub fn excess_kurtosis(values: &[f64]) -> f64 {
+    let (m2, m4) = (central_moment(values, 2), central_moment(values, 4));
+    if values.len() < 4 || m2 <= 0.0 {
+        return 0.0;
+    }
+    m4 / (m2 * m2) - 3.0
+}
+
+fn central_moment(values: &[f64], k: i32) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    values.iter().map(|v| (v - mean).powi(k)).sum::<f64>() / n
+}
+
+/// `f` over each trailing window of `window` points, aligned with the input: the first
+/// `window - 1` entries are `None`.
+fn rolling<F: Fn(usize, usize) -> Option<f64>>(len: usize, window: usize, f: F) -> Vec<Option<f64>> {
+    (0..len)
+        .map(|i| if window >= 2 && i + 1 >= window { f(i + 1 - window, i + 1) } else { None })
+        .collect()
+}
+
+/// Annualized standard deviation of returns over each trailing window.
+pub fn rolling_volatility(returns: &[f64], window: usize, periods_per_year: f64) -> Vec<Option<f64>> {
+    rolling(returns.len(), window, |a, b| Some(mean_std(&returns[a..b]).1 * periods_per_year.sqrt()))
+}
+
+/// Sample covariance of two equally long series.
+fn cov(a: &[f64], b: &[f64]) -> f64 {
+    covariance(&[a.to_vec(), b.to_vec()])[0][1]
+}
+
+/// cov(returns, benchmark) / var(benchmark) over each trailing window; `None` where the
+/// benchmark doesn't move.
+pub fn rolling_beta(returns: &[f64], benchmark: &[f64], window: usize) -> Vec<Option<f64>> {
+    let len = returns.len().min(benchmark.len());
+    rolling(len, window, |a, b| {
+        let var = mean_std(&benchmark[a..b]).1.powi(2);
+        (var > 0.0).then(|| cov(&returns[a..b], &benchmark[a..b]) / var)
+    })
+}
+
+/// Pearson correlation over each trailing window; `None` where either series is flat.
+pub fn rolling_correlation(a: &[f64], b: &[f64], window: usize) -> Vec<Option<f64>> {
+    let len = a.len().min(b.len());
+    rolling(len, window, |s, e| {
+        let (sa, sb) = (mean_std(&a[s..e]).1, mean_std(&b[s..e]).1);
+        (sa > 0.0 && sb > 0.0).then(|| cov(&a[s..e], &b[s..e]) / (sa * sb))
+    })
+}
+
+/// Per-bar returns of a report's equity curve, starting from its starting cash, so they
+/// line up with `equity_curve` and `timestamps`.
+pub fn equity_returns(report: &BacktestReport) -> Vec<f64> {
+    let mut prev = report.starting_cash;
+    report
+        .equity_curve
+        .iter()
+        .map(|&e| {
+            let r = if prev != 0.0 { e / prev - 1.0 } else { 0.0 };
+            prev = e;
+            r
+        })
+        .collect()
+}
+
+/// Benchmark close-to-close returns at `timestamps`, 0 where either bar is missing.
+pub fn benchmark_returns(timestamps: &[String], benchmark: &[Bar]) -> Vec<f64> {
+    let closes: HashMap<&str, f64> = benchmark.iter().map(|b| (b.timestamp.as_str(), b.close)).collect();
+    let mut prev: Option<f64> = None;
+    timestamps
+        .iter()
+        .map(|ts| {
+            let close = closes.get(ts.as_str()).copied();
+            let r = match (prev, close) {
+                (Some(p), Some(c)) if p != 0.0 => c / p - 1.0,
+                _ => 0.0,
+            };
+            prev = close.or(prev);
+            r
+        })
+        .collect()
+}
+
+/// Money VaR of holding `positions` for one period, from the joint history of their
+/// symbols' returns. Historical estimates revalue today's exposures under each past
+/// period; parametric ones use the exposures' covariance. Symbols without a price or
+/// history are ignored.
+pub fn position_var(
+    positions: &HashMap<Symbol, f64>,
+    prices: &HashMap<Symbol, f64>,
+    history: &ReturnMatrix,
+    confidence: f64,
+) -> VarEstimate {
+    let exposure: Vec<f64> = history
+        .symbols
+        .iter()
+        .map(|s| positions.get(s).copied().unwrap_or(0.0) * prices.get(s).copied().unwrap_or(0.0))
+        .collect();
+    let pnls: Vec<f64> = (0..history.len())
+        .map(|t| exposure.iter().zip(&history.returns).map(|(x, r)| x * r[t]).sum())
+        .collect();
+    if pnls.is_empty() {
+        return VarEstimate { confidence, ..Default::default() };
+    }
+    let (historical_var, historical_cvar) = historical_var(&pnls, confidence);
+    let cov = covariance(&history.returns);
+    let mean: f64 = exposure.iter().zip(history.means()).map(|(x, m)| x * m).sum();
+    let variance: f64 = (0..exposure.len())
+        .flat_map(|i| (0..exposure.len()).map(move |j| (i, j)))
+        .map(|(i, j)| exposure[i] * exposure[j] * cov[i][j])
+        .sum();
+    let (std, z) = (variance.max(0.0).sqrt(), normal_quantile(confidence));
+    VarEstimate {
+        confidence,
+        historical_var,
+        historical_cvar,
+        parametric_var: std * z - mean,
+        parametric_cvar: std * normal_pdf(z) / (1.0 - confidence) - mean,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RiskConfig {
+    pub confidence_levels: Vec<f64>,
+    /// Bars in each rolling window.
+    pub window: usize,
+    /// See `TradingCalendar::periods_per_year`.
+    pub periods_per_year: f64,
+}
+
+impl Default for RiskConfig {
+    fn default() -> Self {
+        Self { confidence_levels: vec![0.95, 0.99], window: 24, periods_per_year: 252.0 }
+    }
+}
+
+/// Rolling correlation between two strategies' returns.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct StrategyCorrelation {
+    pub a: String,
+    pub b: String,
+    pub values: Vec<Option<f64>>,
+}
+
+/// Risk profile of one backtest's per-bar returns. The rolling series line up with
+/// `timestamps`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RiskReport {
+    pub var: Vec<VarEstimate>,
+    pub skewness: f64,
+    pub excess_kurtosis: f64,
+    /// Full-sample beta against the benchmark, if one was given.
+    pub beta: Option<f64>,
+    pub timestamps: Vec<String>,
+    pub returns: Vec<f64>,
+    pub rolling_volatility: Vec<Option<f64>>,
+    pub rolling_beta: Vec<Option<f64>>,
+    /// Money VaR of the positions open at the end, per confidence level; empty when the
+    /// run finished flat.
+    pub position_var: Vec<VarEstimate>,
+    /// Between the strategies of a multi-strategy run.
+    pub rolling_correlations: Vec<StrategyCorrelation>,
+}
+
+impl RiskReport {
+    /// Analyses `report`'s equity curve, with beta measured against `benchmark` bars
+    /// (matched by timestamp) when given.
+    pub fn from_report(report: &BacktestReport, benchmark: Option<&[Bar]>, config: &RiskConfig) -> Self {
+        let returns = equity_returns(report);
+        let bench = benchmark.map(|bars| benchmark_returns(&report.timestamps, bars));
+        Self {
+            var: config.confidence_levels.iter().map(|&c| VarEstimate::from_returns(&returns, c)).collect(),
+            skewness: skewness(&returns),
+            excess_kurtosis: excess_kurtosis(&returns),
+            beta: bench.as_ref().and_then(|b| rolling_beta(&returns, b, returns.len()).pop().flatten()),
+            rolling_volatility: rolling_volatility(&returns, config.window, config.periods_per_year),
+            rolling_beta: bench.map(|b| rolling_beta(&returns, &b, config.window)).unwrap_or_default(),
+            timestamps: report.timestamps.clone(),
+            returns,
+            ..Default::default()
+        }
+    }
+
+    /// Adds the VaR of `positions` marked at `prices`, over `history`'s returns.
+    pub fn with_position_var(
+        mut self,
+        positions: &HashMap<Symbol, f64>,
+        prices: &HashMap<Symbol, f64>,
+        history: &ReturnMatrix,
+        config: &RiskConfig,
+    ) -> Self {
+        self.position_var = if positions.values().all(|q| *q == 0.0) {
+            Vec::new()
+        } else {
+            config.confidence_levels.iter().map(|&c| position_var(positions, prices, history, c)).collect()
+        };
+        self
+    }
+
+    /// Adds strategy pairs' rolling correlations, as from
+    /// `MultiStrategyReport::rolling_correlations`.
+    pub fn with_correlations(mut self, pairs: Vec<(String, String, Vec<Option<f64>>)>) -> Self {
+        self.rolling_correlations =
+            pairs.into_iter().map(|(a, b, values)| StrategyCorrelation { a, b, values }).collect();
+        self
+    }
+
+    pub fn print_summary(&self) {
+        println!("\n=== Risk ===");
+        for v in &self.var {
+            println!(
+                "{:.1}% VaR: historical {:.2}% (ES {:.2}%) | parametric {:.2}% (ES {:.2}%)",
+                v.confidence * 100.0,
+                v.historical_var * 100.0,
+                v.historical_cvar * 100.0,
+                v.parametric_var * 100.0,
+                v.parametric_cvar * 100.0
+            );
+        }
+        println!("Skew {:.3} | Excess kurtosis {:.3}", self.skewness, self.excess_kurtosis);
+        if let Some(beta) = self.beta {
+            println!("Beta {:.3}", beta);
+        }
+        for v in &self.position_var {
+            println!(
+                "{:.1}% position VaR: historical {:.2} (ES {:.2}) | parametric {:.2} (ES {:.2})",
+                v.confidence * 100.0,
+                v.historical_var,
+                v.historical_cvar,
+                v.parametric_var,
+                v.parametric_cvar
+            );
+        }
+    }
+
+    /// Writes the full report, summary and series, as JSON.
+    pub fn write_json(&self, path: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        serde_json::to_writer_pretty(File::create(path)?, self)?;
+        Ok(())
+    }
+
+    /// Writes the per-bar series, with a `corr_<a>_<b>` column per strategy pair; rolling
+    /// cells are empty until a window fills.
+    pub fn write_csv(&self, path: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let cell = |v: Option<&Option<f64>>| v.copied().flatten().map_or(String::new(), |x| x.to_string());
+        let mut wtr = Writer::from_path(path)?;
+        let mut header: Vec<String> =
+            ["timestamp", "return", "rolling_volatility", "rolling_beta"].map(String::from).to_vec();
+        header.extend(self.rolling_correlations.iter().map(|c| format!("corr_{}_{}", c.a, c.b)));
+        wtr.write_record(&header)?;
+        for (i, (ts, r)) in self.timestamps.iter().zip(&self.returns).enumerate() {
+            let mut row = vec![
+                ts.clone(),
+                r.to_string(),
+                cell(self.rolling_volatility.get(i)),
+                cell(self.rolling_beta.get(i)),
+            ];
+            row.extend(self.rolling_correlations.iter().map(|c| cell(c.values.get(i))));
+            wtr.write_record(&row)?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_var_and_moments() {
+        let returns: Vec<f64> = (1..=100).map(|i| i as f64 / 1000.0 - 0.05).collect();
+        let (var, cvar) = historical_var(&returns, 0.95);
+        // Worst five: -0.049 .. -0.045.
+        assert!((var - 0.045).abs() < 1e-12);
+        assert!((cvar - 0.047).abs() < 1e-12);
+        assert!((normal_quantile(0.975) - 1.959964).abs() < 1e-6);
+        let v = VarEstimate::from_returns(&returns, 0.99);
+        assert!(v.parametric_cvar > v.parametric_var && v.parametric_var > 0.0);
+        assert!(skewness(&returns).abs() < 1e-12);
+        // Uniform distribution: excess kurtosis near -1.2.
+        assert!((excess_kurtosis(&returns) + 1.2).abs() < 0.01);
+        assert!(skewness(&[0.0, 0.0, 0.0, 0.0, 1.0]) > 0.0);
+    }
+
+    #[test]
+    fn test_rolling_beta_and_correlation() {
+        let bench = [0.01, -0.02, 0.03, -0.01, 0.02];
+        let levered: Vec<f64> = bench.iter().map(|r| 2.0 * r).collect();
+        let beta = rolling_beta(&levered, &bench, 3);
+        assert_eq!(beta.len(), 5);
+        assert!(beta[..2].iter().all(Option::is_none));
+        assert!(beta[2..].iter().all(|b| (b.unwrap() - 2.0).abs() < 1e-12));
+        let inverse: Vec<f64> = bench.iter().map(|r| -r).collect();
+        assert!((rolling_correlation(&bench, &inverse, 5)[4].unwrap() + 1.0).abs() < 1e-12);
+        assert_eq!(rolling_correlation(&bench, &[0.0; 5], 5)[4], None);
+    }
+
+    #[test]
+    fn test_report_and_position_var() {
+        let closes = [100.0, 101.0, 99.0, 102.0, 100.0];
+        let bars: Vec<Bar> = closes
+            .iter()
+            .enumerate()
+            .map(|(h, &close)| Bar {
+                timestamp: format!("2024-01-01T{:02}:00:00Z", h),
+                open: close,
+                high: close,
+                low: close,
+                close,
+                volume: 1.0,
+            })
+            .collect();
+        // Fully invested in the benchmark.
+        let report = BacktestReport {
+            starting_cash: 100.0,
+            final_equity: 100.0,
+            equity_curve: closes.to_vec(),
+            timestamps: bars.iter().map(|b| b.timestamp.clone()).collect(),
+            ..Default::default()
+        };
+        let risk = RiskReport::from_report(&report, Some(&bars), &RiskConfig { window: 3, ..Default::default() });
+        assert_eq!((risk.returns.len(), risk.var.len()), (5, 2));
+        assert!((risk.beta.unwrap() - 1.0).abs() < 1e-12);
+        assert!(risk.rolling_volatility[2].is_some() && risk.rolling_volatility[1].is_none());
+
+        let history = ReturnMatrix::from_bars(HashMap::from([("X".to_string(), bars)])).unwrap();
+        let positions = HashMap::from([("X".to_string(), 2.0)]);
+        let prices = HashMap::from([("X".to_string(), 100.0)]);
+        let v = position_var(&positions, &prices, &history, 0.95);
+        // The worst period (-1.98%) on 200 of exposure.
+        assert!((v.historical_var - 200.0 * (1.0 - 99.0 / 101.0)).abs() < 1e-9);
+
+        let risk = risk
+            .with_position_var(&positions, &prices, &history, &RiskConfig::default())
+            .with_correlations(vec![("a".into(), "b".into(), vec![None, None, Some(0.5), Some(1.0), Some(-1.0)])]);
+        assert_eq!(risk.position_var.len(), 2);
+        assert_eq!(risk.position_var[0], v);
+        let path = std::env::temp_dir().join("quantx_risk_series_test.csv");
+        risk.write_csv(path.to_str().unwrap()).unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert!(csv.starts_with("timestamp,return,rolling_volatility,rolling_beta,corr_a_b\n"));
+        assert!(csv.lines().nth(3).unwrap().ends_with(",0.5"));
+        let flat = risk.with_position_var(&HashMap::new(), &prices, &history, &RiskConfig::default());
+        assert!(flat.position_var.is_empty());
+    }
+}
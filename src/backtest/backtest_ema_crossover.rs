@@ -1,129 +1,475 @@
-use crate::data::bar::Bar;
-use crate::data::order::OrderSide;
-use crate::strategy::ema_switch::EmaSwitchStrategy;
+use std::collections::HashMap;
+use std::sync::Arc;
 
-pub fn continuous_backtest(bars: &[Bar]) {
-    let mut strategy = EmaSwitchStrategy::new(9 * 24, 20 * 24);
+use crate::backtest::engine::{EngineConfig, run_backtest};
+use crate::backtest::report::{BacktestReport, PerformanceMetrics};
+use crate::broker::bracket::{BracketManager, BracketSpec, ExitReason, IntrabarSequence};
+use crate::broker::OrderRequest;
+use crate::data::bar::{Bar, infer_interval};
+use crate::data::calendar::TradingCalendar;
+use crate::data::order::{OrderSide, Signal};
+use crate::optimize::ParamSet;
+use crate::portfolio::margin::MarginConfig;
+use crate::risk::sizing::{PositionSizer, SizingContext, StrategyQuantity};
+use crate::risk::{RiskLimits, RiskManager};
+use crate::strategy::ema_switch::{EmaSwitch, EmaSwitchStrategy};
 
-    let starting_cash = 150_000.0;
+/// Strategy and execution settings for the EMA switch backtest.
+#[derive(Debug, Clone)]
+pub struct EmaBacktestConfig {
+    pub ema_short: usize,
+    pub ema_long: usize,
+    pub starting_cash: f64,
+    pub commission_rate: f64,
+    pub slippage_rate: f64,
+    /// Don't open new positions below this much cash.
+    pub min_cash_threshold: f64,
+    /// Stop-loss / take-profit / trailing stop attached to every long entry. Without it
+    /// positions only exit when the trend flips.
+    pub protection: Option<BracketSpec>,
+    /// Resolves bars that cross both the stop and the target.
+    pub intrabar: IntrabarSequence,
+    /// Entry sizing, capped by the cash available. `None` goes all in.
+    pub sizer: Option<Arc<dyn PositionSizer>>,
+    /// Pre-trade limits on entries, plus the daily-loss halt and drawdown kill switch.
+    pub risk: RiskLimits,
+    /// Trade the trend long and short on margin, through the backtest engine, instead
+    /// of the long-only cash loop. `sizer` and `protection` don't apply there.
+    pub margin: Option<MarginConfig>,
+}
+
+impl Default for EmaBacktestConfig {
+    fn default() -> Self {
+        Self {
+            ema_short: 9 * 24,
+            ema_long: 20 * 24,
+            starting_cash: 150_000.0,
+            commission_rate: 0.001, // 0.1%
+            slippage_rate: 0.0005,  // 0.05%
+            min_cash_threshold: 5000.0,
+            protection: None,
+            intrabar: IntrabarSequence::default(),
+            sizer: None,
+            risk: RiskLimits::default(),
+            margin: None,
+        }
+    }
+}
+
+impl EmaBacktestConfig {
+    /// Copy with `ema_short` / `ema_long` taken from an optimizer parameter set, where present.
+    pub fn with_params(&self, params: &ParamSet) -> Self {
+        let mut config = self.clone();
+        if let Some(v) = params.get("ema_short") {
+            config.ema_short = v.round() as usize;
+        }
+        if let Some(v) = params.get("ema_long") {
+            config.ema_long = v.round() as usize;
+        }
+        config
+    }
+}
+
+/// Runs the EMA switch backtest on `symbol` with `config`, printing every fill and a summary.
+pub fn continuous_backtest(
+    symbol: &str,
+    bars: &[Bar],
+    config: &EmaBacktestConfig,
+    calendar: &dyn TradingCalendar,
+) -> BacktestReport {
+    let report = run_ema_backtest(symbol, bars, config, calendar, true);
+
+    let starting_cash = report.starting_cash;
+    let equity = report.final_equity;
+    let final_pnl = equity - starting_cash;
+    let return_pct = report.metrics.total_return * 100.0;
+
+    println!("\n----------------------------");
+    println!("✅ Final Summary (Dynamic Qty, Realistic, fees + slippage)");
+    println!("Starting Cash: {:.2}", starting_cash);
+    println!("Final Equity:  {:.2}", equity);
+    println!("Net PnL:       {:.2}", final_pnl);
+    println!("Return:        {:.2}%", return_pct);
+    println!(
+        "Annualized:    {:.2}% ({} calendar)",
+        report.metrics.annualized_return * 100.0,
+        calendar.name()
+    );
+    println!("Sharpe:        {:.2}", report.metrics.sharpe);
+    println!("Max Drawdown:  {:.2}%", report.metrics.max_drawdown * 100.0);
+    println!("Borrow Cost:   {:.2}", report.borrow_cost);
+    println!("Total Trades:  {}", report.trades);
+    println!("Winning Trades: {}", report.wins);
+    println!("Losing Trades:  {}", report.losses);
+    if report.losses > 0 {
+        println!("Win/Loss Ratio: {:.2}", report.wins as f64 / report.losses as f64);
+    }
+    println!("----------------------------");
+    report
+}
+
+/// Runs the EMA switch strategy over `symbol`'s `bars` with fees and slippage, trading
+/// towards its target long-only: up-crosses buy the target weight of cash (or what
+/// `config.sizer` says), down-crosses close the whole position. `verbose` prints every fill.
+pub fn run_ema_backtest(
+    symbol: &str,
+    bars: &[Bar],
+    config: &EmaBacktestConfig,
+    calendar: &dyn TradingCalendar,
+    verbose: bool,
+) -> BacktestReport {
+    if let Some(margin) = config.margin {
+        return run_ema_margin_backtest(symbol, bars, config, margin, calendar);
+    }
+    let mut strategy = EmaSwitchStrategy::new(config.ema_short, config.ema_long);
+
+    let starting_cash = config.starting_cash;
     let mut cash = starting_cash;
     let mut position_qty: f64 = 0.0;
     let mut entry_price: f64 = 0.0;
+    let mut entry_fee: f64 = 0.0;
 
     let mut trades = 0usize;
     let mut wins = 0usize;
     let mut losses = 0usize;
+    let mut trade_pnls = Vec::new();
+    let mut equity_curve = Vec::with_capacity(bars.len());
+    let mut timestamps = Vec::with_capacity(bars.len());
 
-    let commission_rate = 0.001; // 0.1%
-    let slippage_rate = 0.0005;  // 0.05%
-    let min_cash_threshold = 5000.0; // don't trade below this
+    let commission_rate = config.commission_rate;
+    let slippage_rate = config.slippage_rate;
+    let min_cash_threshold = config.min_cash_threshold;
+    let mut brackets = BracketManager::new(config.intrabar, 14);
+    let mut risk = RiskManager::new(config.risk.clone());
 
     for bar in bars {
-        let orders = strategy.generate_signal(bar);
-
-        for order in orders {
+        // Protective exits trigger inside the bar, before the close-based signal.
+        for exit in brackets.on_bar(symbol, bar) {
+            if position_qty <= 0.0 {
+                continue;
+            }
             trades += 1;
+            let close_price = match exit.reason {
+                ExitReason::TakeProfit => exit.price,
+                ExitReason::StopLoss | ExitReason::TrailingStop => exit.price * (1.0 - slippage_rate),
+            };
+            let revenue = close_price * position_qty;
+            let fee = revenue * commission_rate;
+            let pnl = (close_price - entry_price) * position_qty;
+            cash += revenue - fee;
+            if pnl > 0.0 { wins += 1; } else { losses += 1; }
+            trade_pnls.push(pnl - fee - entry_fee);
+            if verbose {
+                println!(
+                    "🛡️ {:?} {:.4} {} @ {:.2} | PnL = {:.2}",
+                    exit.reason, position_qty, symbol, close_price, pnl
+                );
+            }
+            position_qty = 0.0;
+        }
 
-            match order.side {
-                OrderSide::Buy => {
-                    // Skip if already long
-                    if position_qty > 0.0 {
-                        continue;
-                    }
-
-                    // Close short first (if any)
-                    if position_qty < 0.0 {
-                        let close_price = order.price * (1.0 + slippage_rate);
-                        let pnl = (entry_price - close_price) * position_qty.abs();
-                        cash += pnl;
-                        let fee = close_price * position_qty.abs() * commission_rate;
-                        cash -= fee;
-                        if pnl > 0.0 { wins += 1; } else { losses += 1; }
-                        println!("📈 CLOSE SHORT @ {:.2} | PnL = {:.2}", close_price, pnl);
-                        position_qty = 0.0;
-                    }
-
-                    // Open new long only if enough cash
-                    if cash < min_cash_threshold {
-                        continue;
-                    }
+        let marked = cash + position_qty * bar.close;
+        let date = bar.datetime().map(|t| calendar.local_date(t)).unwrap_or_default();
+        if risk.on_equity(marked, date, &bar.timestamp) && position_qty > 0.0 {
+            // Kill switch: flatten at the close and stop trading.
+            let close_price = bar.close * (1.0 - slippage_rate);
+            let revenue = close_price * position_qty;
+            let fee = revenue * commission_rate;
+            let pnl = (close_price - entry_price) * position_qty;
+            cash += revenue - fee;
+            trades += 1;
+            if pnl > 0.0 { wins += 1; } else { losses += 1; }
+            trade_pnls.push(pnl - fee - entry_fee);
+            if verbose {
+                println!("🛑 KILL SWITCH: flattened {:.4} {} @ {:.2} | PnL = {:.2}", position_qty, symbol, close_price, pnl);
+            }
+            position_qty = 0.0;
+            brackets.cancel_symbol(symbol);
+        }
 
-                    let fill_price = order.price * (1.0 + slippage_rate);
-                    let investable_cash = cash * 0.999; // keep tiny buffer
-                    let quantity = investable_cash / fill_price;
-                    if quantity <= 0.0 {
-                        continue;
-                    }
+        // Long-only, so entries happen when flat and equity is the cash; a short target
+        // just closes the long until the next up-cross.
+        match strategy.target(bar) {
+            Some(target) if target.quantity(bar.close, cash) <= 0.0 && position_qty > 0.0 => {
+                let close_price = bar.close * (1.0 - slippage_rate);
+                let revenue = close_price * position_qty;
+                let fee = revenue * commission_rate;
+                let pnl = (close_price - entry_price) * position_qty;
+                cash += revenue - fee;
+                trades += 1;
 
-                    let cost = fill_price * quantity;
-                    let fee = cost * commission_rate;
-                    cash -= cost + fee;
-                    entry_price = fill_price;
-                    position_qty = quantity;
+                if pnl > 0.0 { wins += 1; } else { losses += 1; }
+                trade_pnls.push(pnl - fee - entry_fee);
 
+                if verbose {
                     println!(
-                        "BUY  {:.4} BTC @ {:.2} (cost {:.2}, fee {:.2})",
-                        position_qty, fill_price, cost, fee
+                        "📉 CLOSE LONG {:.4} {} @ {:.2} | PnL = {:.2}",
+                        position_qty, symbol, close_price, pnl
                     );
                 }
 
-                OrderSide::Sell => {
-                    // Skip if already short
-                    if position_qty < 0.0 {
-                        continue;
+                position_qty = 0.0;
+                brackets.cancel_symbol(symbol);
+            }
+            // Open new long only if flat and there is enough cash
+            Some(target) if target.quantity(bar.close, cash) > 0.0 && position_qty <= 0.0 && cash >= min_cash_threshold => {
+                let fill_price = bar.close * (1.0 + slippage_rate);
+                let investable_cash = cash * 0.999; // keep tiny buffer
+                let all_in = investable_cash / fill_price;
+                let quantity = match &config.sizer {
+                    Some(sizer) => {
+                        let ctx = SizingContext {
+                            equity: cash,
+                            cash,
+                            price: fill_price,
+                            position: position_qty,
+                            atr: brackets.atr(symbol),
+                        };
+                        let signal = Signal {
+                            side: OrderSide::Buy,
+                            conviction: 1.0,
+                            price: bar.close,
+                            timestamp: bar.timestamp.clone(),
+                            stop: None,
+                            quantity: None,
+                        };
+                        sizer.size(&signal, &ctx)
                     }
+                    None => target.quantity(fill_price, investable_cash),
+                }
+                .min(all_in);
+                if quantity > 0.0 {
+                    let request = OrderRequest::market(symbol, OrderSide::Buy, quantity);
+                    match risk.check(&request, fill_price, position_qty, 0, &bar.timestamp) {
+                        Err(reason) => {
+                            if verbose {
+                                println!("⛔ BUY {:.4} {} rejected: {}", quantity, symbol, reason);
+                            }
+                        }
+                        Ok(()) => {
+                            let cost = fill_price * quantity;
+                            let fee = cost * commission_rate;
+                            cash -= cost + fee;
+                            entry_price = fill_price;
+                            entry_fee = fee;
+                            position_qty = quantity;
+                            trades += 1;
+                            if let Some(spec) = &config.protection {
+                                brackets.open(None, symbol, OrderSide::Buy, quantity, fill_price, spec);
+                            }
 
-                    // Close long position first
-                    if position_qty > 0.0 {
-                        let close_price = order.price * (1.0 - slippage_rate);
-                        let revenue = close_price * position_qty;
-                        let fee = revenue * commission_rate;
-                        let pnl = (close_price - entry_price) * position_qty;
-                        cash += revenue - fee;
-
-                        if pnl > 0.0 { wins += 1; } else { losses += 1; }
-
-                        println!(
-                            "📉 CLOSE LONG {:.4} BTC @ {:.2} | PnL = {:.2}",
-                            position_qty, close_price, pnl
-                        );
-
-                        position_qty = 0.0;
+                            if verbose {
+                                println!(
+                                    "BUY  {:.4} {} @ {:.2} (cost {:.2}, fee {:.2})",
+                                    position_qty, symbol, fill_price, cost, fee
+                                );
+                            }
+                        }
                     }
                 }
             }
+            _ => {}
         }
+
+        equity_curve.push(cash + position_qty * bar.close);
+        timestamps.push(bar.timestamp.clone());
     }
 
     // Final square-off if needed
-    if position_qty > 0.0 {
-        if let Some(last) = bars.last() {
-            let close_price = last.close * (1.0 - slippage_rate);
-            let pnl = (close_price - entry_price) * position_qty;
-            let fee = close_price * position_qty * commission_rate;
-            cash += close_price * position_qty - fee;
+    if position_qty > 0.0
+        && let Some(last) = bars.last()
+    {
+        let close_price = last.close * (1.0 - slippage_rate);
+        let pnl = (close_price - entry_price) * position_qty;
+        let fee = close_price * position_qty * commission_rate;
+        cash += close_price * position_qty - fee;
+        trade_pnls.push(pnl - fee - entry_fee);
+        if verbose {
             println!(
-                "🔚 FINAL SQUAREOFF {:.4} BTC @ {:.2} | PnL = {:.2}",
-                position_qty, close_price, pnl
+                "🔚 FINAL SQUAREOFF {:.4} {} @ {:.2} | PnL = {:.2}",
+                position_qty, symbol, close_price, pnl
             );
-            position_qty = 0.0;
+        }
+        if let Some(last_equity) = equity_curve.last_mut() {
+            *last_equity = cash;
         }
     }
 
-    let final_pnl = cash - starting_cash;
-    let return_pct = (cash / starting_cash - 1.0) * 100.0;
+    let periods_per_year = infer_interval(bars)
+        .map(|interval| calendar.periods_per_year(interval))
+        .unwrap_or(252.0);
+    let mut curve = Vec::with_capacity(equity_curve.len() + 1);
+    curve.push(starting_cash);
+    curve.extend_from_slice(&equity_curve);
 
-    println!("\n----------------------------");
-    println!("✅ Final Summary (Dynamic Qty, Realistic, fees + slippage)");
-    println!("Starting Cash: {:.2}", starting_cash);
-    println!("Final Cash:    {:.2}", cash);
-    println!("Net PnL:       {:.2}", final_pnl);
-    println!("Return:        {:.2}%", return_pct);
-    println!("Total Trades:  {}", trades);
-    println!("Winning Trades: {}", wins);
-    println!("Losing Trades:  {}", losses);
-    if losses > 0 {
-        println!("Win/Loss Ratio: {:.2}", wins as f64 / losses as f64);
+    BacktestReport {
+        starting_cash,
+        final_equity: cash,
+        metrics: PerformanceMetrics::from_equity(&curve, periods_per_year),
+        equity_curve,
+        timestamps,
+        trade_pnls,
+        trades,
+        wins,
+        losses,
+        positions: HashMap::new(),
+        borrow_cost: 0.0,
+    }
+}
+
+/// Fully long after up-crosses and fully short after down-crosses, on margin. Losses on
+/// either side are bounded by liquidation rather than running on.
+fn run_ema_margin_backtest(
+    symbol: &str,
+    bars: &[Bar],
+    config: &EmaBacktestConfig,
+    margin: MarginConfig,
+    calendar: &dyn TradingCalendar,
+) -> BacktestReport {
+    let strategy = EmaSwitch::new(config.ema_short, config.ema_long);
+    let engine_config = EngineConfig {
+        starting_cash: config.starting_cash,
+        commission_rate: config.commission_rate,
+        slippage_rate: config.slippage_rate,
+        margin: Some(margin),
+        ..Default::default()
+    };
+    let mut risk = RiskManager::new(config.risk.clone());
+    run_backtest(&strategy, &StrategyQuantity, symbol, bars, &engine_config, calendar, &mut risk)
+        .expect("the paper broker only refuses malformed orders")
+        .report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broker::bracket::ExitLevel;
+    use crate::data::calendar::Crypto247;
+
+    #[test]
+    fn test_sizer_limits_entry_quantity() {
+        use crate::risk::sizing::PercentEquity;
+        let bars: Vec<Bar> = [100.0, 101.0, 102.0, 104.0, 106.0]
+            .iter()
+            .enumerate()
+            .map(|(i, &c)| Bar {
+                timestamp: format!("2024-01-01T{:02}:00:00Z", i),
+                open: c,
+                high: c,
+                low: c,
+                close: c,
+                volume: 1.0,
+            })
+            .collect();
+        let config = EmaBacktestConfig {
+            ema_short: 2,
+            ema_long: 3,
+            commission_rate: 0.0,
+            slippage_rate: 0.0,
+            sizer: Some(Arc::new(PercentEquity(0.25))),
+            ..Default::default()
+        };
+        let report = run_ema_backtest("BTCUSDT", &bars, &config, &Crypto247, false);
+        // A quarter of the cash went in at 102 and was squared off at 106.
+        let expected = 150_000.0 + 150_000.0 * 0.25 / 102.0 * 4.0;
+        assert!((report.final_equity - expected).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_stop_loss_caps_the_loss_before_the_trend_flips() {
+        let closes = [100.0, 101.0, 102.0, 104.0, 106.0, 108.0, 109.0, 108.0, 90.0, 80.0];
+        let bars: Vec<Bar> = closes
+            .iter()
+            .enumerate()
+            .map(|(i, &c)| Bar {
+                timestamp: format!("2024-01-01T{:02}:00:00Z", i),
+                open: c,
+                high: c + 0.5,
+                // A wick on bar 7 reaches the stop while the close keeps the trend intact.
+                low: if i == 7 { 95.0 } else { c - 0.5 },
+                close: c,
+                volume: 1.0,
+            })
+            .collect();
+        let base = EmaBacktestConfig {
+            ema_short: 2,
+            ema_long: 3,
+            commission_rate: 0.0,
+            slippage_rate: 0.0,
+            ..Default::default()
+        };
+        let protected = EmaBacktestConfig {
+            protection: Some(BracketSpec::new().stop_loss(ExitLevel::Percent(0.03))),
+            ..base.clone()
+        };
+        let plain = run_ema_backtest("BTCUSDT", &bars, &base, &Crypto247, false);
+        let stopped = run_ema_backtest("BTCUSDT", &bars, &protected, &Crypto247, false);
+        assert!(stopped.final_equity > plain.final_equity);
+        assert_eq!(stopped.trade_pnls.len(), 1);
+        assert!(stopped.trade_pnls[0] < 0.0);
+    }
+
+    #[test]
+    fn test_kill_switch_flattens_and_blocks_reentry() {
+        let closes = [100.0, 101.0, 102.0, 104.0, 106.0, 108.0, 109.0, 90.0, 95.0, 100.0, 105.0, 110.0];
+        let bars: Vec<Bar> = closes
+            .iter()
+            .enumerate()
+            .map(|(i, &c)| Bar {
+                timestamp: format!("2024-01-01T{:02}:00:00Z", i),
+                open: c,
+                high: c,
+                low: c,
+                close: c,
+                volume: 1.0,
+            })
+            .collect();
+        let base = EmaBacktestConfig {
+            ema_short: 2,
+            ema_long: 3,
+            commission_rate: 0.0,
+            slippage_rate: 0.0,
+            ..Default::default()
+        };
+        let guarded = EmaBacktestConfig {
+            risk: RiskLimits::default().with_max_drawdown(0.1),
+            ..base.clone()
+        };
+        let plain = run_ema_backtest("BTCUSDT", &bars, &base, &Crypto247, false);
+        let killed = run_ema_backtest("BTCUSDT", &bars, &guarded, &Crypto247, false);
+        assert!(plain.trade_pnls.len() > 1);
+        // Flattened at 90 when the drawdown hit 17%; the recovery is never re-entered.
+        assert_eq!(killed.trade_pnls.len(), 1);
+        let flat = killed.equity_curve[7];
+        assert!(killed.equity_curve[7..].iter().all(|&e| (e - flat).abs() < 1e-9));
+    }
+
+    #[test]
+    fn test_margin_mode_shorts_the_downtrend() {
+        let closes = [100.0, 102.0, 104.0, 106.0, 108.0, 110.0, 100.0, 90.0, 80.0, 70.0, 60.0, 60.0];
+        let bars: Vec<Bar> = closes
+            .iter()
+            .enumerate()
+            .map(|(i, &c)| Bar {
+                timestamp: format!("2024-01-01T{:02}:00:00Z", i),
+                open: c,
+                high: c,
+                low: c,
+                close: c,
+                volume: 1.0,
+            })
+            .collect();
+        let cash = EmaBacktestConfig {
+            ema_short: 2,
+            ema_long: 3,
+            commission_rate: 0.0,
+            slippage_rate: 0.0,
+            ..Default::default()
+        };
+        let margin = EmaBacktestConfig { margin: Some(MarginConfig { leverage: 2.0, ..Default::default() }), ..cash.clone() };
+        let long_only = run_ema_backtest("BTCUSDT", &bars, &cash, &Crypto247, false);
+        let long_short = run_ema_backtest("BTCUSDT", &bars, &margin, &Crypto247, false);
+        assert!(long_short.final_equity > long_only.final_equity);
+        assert!(long_short.final_equity > long_short.starting_cash);
     }
-    println!("----------------------------");
 }
@@ -1,68 +1,200 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
+use chrono::NaiveDate;
+
 use crate::data::bar::Bar;
-use crate::data::downloader::download_and_extract_for_date;
-use crate::data::loader::CsvLoader;
+use crate::data::calendar::TradingCalendar;
+use crate::broker::OrderRequest;
 use crate::data::order::OrderSide;
+use crate::portfolio::rebalance::{RebalanceConfig, rebalance_orders};
+use crate::risk::{RejectReason, RiskManager};
 use crate::strategy::Strategy;
-use chrono::{Duration, Utc};
-use tokio::task::JoinHandle;
 
 #[derive(Debug)]
 pub struct DailyResult {
     pub date: String,
     pub pnl: f64,
+    /// Signals that were executed.
     pub trades: usize,
+    /// Signals refused by the risk manager or the cash check.
+    pub rejected: usize,
 }
 
-/// Single-day backtest (EOD square-off)
+pub const STARTING_CASH: f64 = 10_00000.0;
+
+/// Single-day backtest: only bars inside the calendar's session for `date` are traded,
+/// and the position is squared off at the last of them (EOD square-off). Every signal
+/// goes through `risk` and a cash check first; rejections are reported to the strategies.
+/// Strategies with a `target` are rebalanced towards it at the bar's close instead.
+/// To run consecutive days, pass the previous day's closing cash as `starting_cash` and
+/// the same `risk` so the drawdown kill switch sees the whole run.
 pub fn backtest_single_day(
     strategies: &[Arc<dyn Strategy>],
+    symbol: &str,
     bars: &[Bar],
     date: &str,
+    starting_cash: f64,
+    calendar: &dyn TradingCalendar,
+    risk: &mut RiskManager,
 ) -> DailyResult {
-    let mut cash = 10_00000.0;
-    let mut position: i64 = 0;
+    let mut cash = starting_cash;
+    let mut position = 0.0;
     let mut trades = 0usize;
+    let mut rejected = 0usize;
+
+    let day = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok();
+    let session = day.and_then(|d| calendar.session(d));
+    let session_bars: Vec<&Bar> = match session {
+        Some(session) => bars
+            .iter()
+            .filter(|b| b.datetime().is_ok_and(|t| session.contains(t)))
+            .collect(),
+        None => Vec::new(),
+    };
+
+    for bar in session_bars.iter().copied() {
+        let equity = cash + position * bar.close;
+        if let Some(day) = day
+            && risk.on_equity(equity, day, &bar.timestamp)
+            && position != 0.0
+        {
+            // Kill switch: flatten at this bar's close.
+            cash += position * bar.close;
+            position = 0.0;
+            trades += 1;
+        }
 
-    for bar in bars {
         for strat in strategies {
-            if let Some(order) = strat.generate_signal(bar) {
+            // Targets are traded towards at the close; signals at their own price.
+            let requests: Vec<(OrderRequest, f64)> = if let Some(target) = strat.target(bar) {
+                rebalance_orders(
+                    &HashMap::from([(symbol.to_string(), target)]),
+                    &HashMap::from([(symbol.to_string(), position)]),
+                    &HashMap::from([(symbol.to_string(), bar.close)]),
+                    cash + position * bar.close,
+                    &RebalanceConfig::default(),
+                )
+                .into_iter()
+                .map(|request| (request, bar.close))
+                .collect()
+            } else if let Some(order) = strat.generate_signal(bar) {
+                vec![(OrderRequest::market(symbol, order.side, order.quantity as f64), order.price)]
+            } else {
+                Vec::new()
+            };
+            for (request, price) in requests {
+                let cost = price * request.quantity;
+                if risk.check(&request, price, position, 0, &bar.timestamp).is_err() {
+                    rejected += 1;
+                    continue;
+                }
+                if request.side == OrderSide::Buy && cost > cash {
+                    let reason = RejectReason::InsufficientCash { required: cost, available: cash };
+                    risk.record_rejection(&request, reason, &bar.timestamp);
+                    rejected += 1;
+                    continue;
+                }
                 trades += 1;
-                match order.side {
+                match request.side {
                     OrderSide::Buy => {
-                        if cash > order.price {
-                            cash -= order.price * order.quantity as f64;
-                            position += order.quantity;
-                        }
+                        cash -= cost;
+                        position += request.quantity;
                     }
                     OrderSide::Sell => {
-                        cash += order.price * order.quantity as f64;
-                        position -= order.quantity;
+                        cash += cost;
+                        position -= request.quantity;
                     }
                 }
             }
         }
-    }
 
-    // EOD square-off using last bar
-    if position != 0 {
-        if let Some(last) = bars.last() {
-            // if position>0 we sell, else buy to cover
-            if position > 0 {
-                cash += last.close * (position.abs() as f64);
-            } else {
-                cash -= last.close * (position.abs() as f64);
+        for event in risk.take_events() {
+            for strat in strategies {
+                strat.on_risk_event(&event);
             }
-            position = 0;
         }
     }
 
-    let pnl = cash - 10_00000.0;
-    print!("Date: {}, PnL: {:.2}, Trades: {}\n", date, pnl, trades);
+    // EOD square-off using the last in-session bar
+    if position != 0.0
+        && let Some(last) = session_bars.last()
+    {
+        // Selling a long or buying back a short both settle `position * close`.
+        cash += last.close * position;
+    }
+
+    let pnl = cash - starting_cash;
+    println!("Date: {}, PnL: {:.2}, Trades: {}, Rejected: {}", date, pnl, trades, rejected);
     DailyResult {
         date: date.to_string(),
         pnl,
         trades,
+        rejected,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::calendar::Crypto247;
+    use crate::risk::RiskLimits;
+    use crate::strategy::always_buy::AlwaysBuy;
+
+    fn bullish(ts: &str, price: f64) -> Bar {
+        Bar { timestamp: ts.into(), open: price - 1.0, high: price, low: price - 1.0, close: price, volume: 50_000.0 }
+    }
+
+    #[test]
+    fn test_unaffordable_signals_are_not_trades() {
+        let strategies: Vec<Arc<dyn Strategy>> = vec![Arc::new(AlwaysBuy)];
+        let bars = vec![bullish("2024-01-01T00:00:00Z", 600_000.0), bullish("2024-01-01T01:00:00Z", 600_000.0)];
+        let result = backtest_single_day(&strategies, "BTCUSDT", &bars, "2024-01-01", STARTING_CASH, &Crypto247, &mut RiskManager::default());
+        assert_eq!((result.trades, result.rejected), (1, 1));
+        assert_eq!(result.pnl, 0.0);
+    }
+
+    #[test]
+    fn test_risk_limits_apply() {
+        let strategies: Vec<Arc<dyn Strategy>> = vec![Arc::new(AlwaysBuy)];
+        let bars: Vec<Bar> = (0..5).map(|h| bullish(&format!("2024-01-01T0{}:00:00Z", h), 100.0)).collect();
+        let mut risk = RiskManager::new(RiskLimits::default().with_max_position(2.0));
+        let result = backtest_single_day(&strategies, "BTCUSDT", &bars, "2024-01-01", STARTING_CASH, &Crypto247, &mut risk);
+        assert_eq!((result.trades, result.rejected), (2, 3));
+    }
+
+    #[test]
+    fn test_kill_switch_carries_across_days() {
+        let strategies: Vec<Arc<dyn Strategy>> = vec![Arc::new(AlwaysBuy)];
+        let mut risk = RiskManager::new(RiskLimits::default().with_max_drawdown(0.1));
+        let day1 = vec![bullish("2024-01-01T00:00:00Z", 100.0), bullish("2024-01-01T01:00:00Z", 50.0)];
+        let first = backtest_single_day(&strategies, "BTCUSDT", &day1, "2024-01-01", 200.0, &Crypto247, &mut risk);
+        // One unit bought at 100 and flattened at 50 by the kill switch; the second buy is refused.
+        assert_eq!((first.pnl, first.trades, first.rejected), (-50.0, 2, 1));
+        let day2 = vec![bullish("2024-01-02T00:00:00Z", 50.0)];
+        let second =
+            backtest_single_day(&strategies, "BTCUSDT", &day2, "2024-01-02", 200.0 + first.pnl, &Crypto247, &mut risk);
+        assert_eq!((second.trades, second.rejected), (0, 1));
+    }
+
+    #[test]
+    fn test_target_weight_is_rebalanced() {
+        use crate::data::order::Order;
+        use crate::portfolio::rebalance::Target;
+        struct HalfLong;
+        impl Strategy for HalfLong {
+            fn generate_signal(&self, _bar: &Bar) -> Option<Order> {
+                None
+            }
+            fn target(&self, _bar: &Bar) -> Option<Target> {
+                Some(Target::Weight(0.5))
+            }
+        }
+        let strategies: Vec<Arc<dyn Strategy>> = vec![Arc::new(HalfLong)];
+        let bars = vec![bullish("2024-01-01T00:00:00Z", 100.0), bullish("2024-01-01T01:00:00Z", 200.0)];
+        let result = backtest_single_day(&strategies, "BTCUSDT", &bars, "2024-01-01", 1_000.0, &Crypto247, &mut RiskManager::default());
+        // 5 units at 100; at 200 equity is 1500, so 1.25 are sold to hold 3.75 (750).
+        assert_eq!((result.trades, result.rejected), (2, 0));
+        assert_eq!(result.pnl, 500.0);
     }
 }
@@ -0,0 +1,391 @@
+use std::collections::HashMap;
+
+use chrono::{Days, NaiveDate};
+use futures::executor::block_on;
+
+use crate::backtest::report::{BacktestReport, PerformanceMetrics};
+use crate::broker::paper::PaperBroker;
+use crate::broker::{BarFill, BarStep, BookFill, Broker, BrokerResult, LiveSummary, PercentageFee};
+use crate::data::bar::{Bar, infer_interval};
+use crate::data::calendar::TradingCalendar;
+use crate::data::continuous::ContinuousSeries;
+use crate::data::corporate_actions::{AdjustmentMode, CorporateActions};
+use crate::data::depth::{BarBookFeed, BookLevels, BookSource};
+use crate::data::feed::Symbol;
+use crate::portfolio::Portfolio;
+use crate::portfolio::borrow::{AccrualPeriod, BorrowAccrual, BorrowRates};
+use crate::portfolio::margin::MarginConfig;
+use crate::portfolio::rebalance::RebalanceConfig;
+use crate::risk::RiskManager;
+use crate::risk::sizing::PositionSizer;
+use crate::strategy::Strategy;
+use crate::strategy::indicators::Atr;
+
+/// Account and market settings for `run_backtest`.
+#[derive(Debug, Clone)]
+pub struct EngineConfig {
+    pub starting_cash: f64,
+    pub commission_rate: f64,
+    pub slippage_rate: f64,
+    pub allow_short: bool,
+    pub rebalance: RebalanceConfig,
+    pub corporate_actions: Option<CorporateActions>,
+    /// How the bars were adjusted for `corporate_actions`; `None` for raw prices.
+    pub adjustment: Option<AdjustmentMode>,
+    /// Trade as a margin account (shorts allowed, liquidations), rather than cash.
+    pub margin: Option<MarginConfig>,
+    /// Interest on shorts, and on negative cash outside margin accounts, charged hourly.
+    pub borrow: Option<BorrowRates>,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            starting_cash: 100_000.0,
+            commission_rate: 0.001,
+            slippage_rate: 0.0005,
+            allow_short: false,
+            rebalance: RebalanceConfig::default(),
+            corporate_actions: None,
+            adjustment: None,
+            margin: None,
+            borrow: None,
+        }
+    }
+}
+
+impl EngineConfig {
+    fn broker(&self) -> PaperBroker {
+        let broker = PaperBroker::new(self.starting_cash)
+            .with_fill_model(BarFill { slippage_rate: self.slippage_rate })
+            .with_fee_model(PercentageFee { maker_rate: self.commission_rate, taker_rate: self.commission_rate })
+            .allow_short(self.allow_short);
+        let broker = match self.margin {
+            Some(margin) => broker.with_margin(margin),
+            None => broker,
+        };
+        match &self.borrow {
+            Some(rates) => broker.with_borrow(BorrowAccrual::new(rates.clone(), AccrualPeriod::Hourly)),
+            None => broker,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EngineResult {
+    pub report: BacktestReport,
+    /// Final account, with the blotter, dividends and borrow cost.
+    pub portfolio: Portfolio,
+    pub summary: LiveSummary,
+}
+
+/// Backtests one strategy on `symbol`'s bars through a `PaperBroker`, with the same
+/// per-bar loop as `run_live`: orders fill on the next bar, and targets, sizing and risk
+/// checks behave as they do live. Corporate actions are applied at the first bar of each
+/// local date on or after their ex-date.
+pub fn run_backtest(
+    strategy: &dyn Strategy,
+    sizer: &dyn PositionSizer,
+    symbol: &str,
+    bars: &[Bar],
+    config: &EngineConfig,
+    calendar: &dyn TradingCalendar,
+    risk: &mut RiskManager,
+) -> BrokerResult<EngineResult> {
+    let mut engine = Engine::new(strategy, sizer, config, calendar, risk, config.broker(), bars.len());
+    for bar in bars {
+        engine.step(symbol, bar, None)?;
+    }
+    engine.finish(bars)
+}
+
+/// Like `run_backtest`, with the bars paired with a replayed order book. Strategies see
+/// the book through `generate_signal_with_book`, and orders fill against the book as of
+/// the close before them per `BookFill`.
+pub fn run_book_backtest<S: BookSource>(
+    strategy: &dyn Strategy,
+    sizer: &dyn PositionSizer,
+    symbol: &str,
+    feed: BarBookFeed<S>,
+    config: &EngineConfig,
+    calendar: &dyn TradingCalendar,
+    risk: &mut RiskManager,
+) -> BrokerResult<EngineResult> {
+    let fills = BookFill { fallback: BarFill { slippage_rate: config.slippage_rate }, ..Default::default() };
+    let mut engine = Engine::new(strategy, sizer, config, calendar, risk, config.broker().with_fill_model(fills), 0);
+    let mut bars = Vec::new();
+    for (bar, book) in feed {
+        engine.step(symbol, &bar, book.as_ref())?;
+        engine.broker.set_book(symbol, book);
+        bars.push(bar);
+    }
+    engine.finish(&bars)
+}
+
+/// Like `run_backtest`, on a continuous futures series: each bar trades the contract it
+/// was taken from. At every roll, orders still working on the old contract move to the
+/// next one and fill on the roll bar; after them the position is moved across at the
+/// roll bar's close with two `FillKind::Roll` fills. Use an unadjusted series
+/// (`BackAdjust::None`) so fills and roll legs happen at traded prices.
+pub fn run_futures_backtest(
+    strategy: &dyn Strategy,
+    sizer: &dyn PositionSizer,
+    series: &ContinuousSeries,
+    config: &EngineConfig,
+    calendar: &dyn TradingCalendar,
+    risk: &mut RiskManager,
+) -> BrokerResult<EngineResult> {
+    let mut engine = Engine::new(strategy, sizer, config, calendar, risk, config.broker(), series.bars.len());
+    for (i, (bar, contract)) in series.bars.iter().zip(&series.active).enumerate() {
+        if let Some(roll) = series.roll_at(i) {
+            engine.broker.schedule_roll(roll);
+        }
+        engine.step(contract, bar, None)?;
+    }
+    engine.finish(&series.bars)
+}
+
+/// State shared by the engine entry points while they walk their bars.
+struct Engine<'a> {
+    strategy: &'a dyn Strategy,
+    sizer: &'a dyn PositionSizer,
+    config: &'a EngineConfig,
+    calendar: &'a dyn TradingCalendar,
+    risk: &'a mut RiskManager,
+    broker: PaperBroker,
+    summary: LiveSummary,
+    atr: HashMap<Symbol, Atr>,
+    curve: Vec<f64>,
+    timestamps: Vec<String>,
+    last_date: Option<NaiveDate>,
+}
+
+impl<'a> Engine<'a> {
+    fn new(
+        strategy: &'a dyn Strategy,
+        sizer: &'a dyn PositionSizer,
+        config: &'a EngineConfig,
+        calendar: &'a dyn TradingCalendar,
+        risk: &'a mut RiskManager,
+        broker: PaperBroker,
+        len: usize,
+    ) -> Self {
+        Self {
+            strategy,
+            sizer,
+            config,
+            calendar,
+            risk,
+            broker,
+            summary: LiveSummary::default(),
+            atr: HashMap::new(),
+            curve: Vec::with_capacity(len),
+            timestamps: Vec::with_capacity(len),
+            last_date: None,
+        }
+    }
+
+    fn step(&mut self, symbol: &str, bar: &Bar, book: Option<&BookLevels>) -> BrokerResult<()> {
+        if let Some(actions) = &self.config.corporate_actions
+            && let Ok(now) = bar.datetime()
+        {
+            let today = self.calendar.local_date(now);
+            // Days without bars (weekends, holidays) still carry their actions over.
+            let mut day = self.last_date.map_or(today, |d| d + Days::new(1));
+            while day <= today {
+                self.broker.apply_corporate_actions(actions, day, self.config.adjustment);
+                day = day + Days::new(1);
+            }
+            self.last_date = Some(today);
+        }
+
+        let mut step = BarStep {
+            strategy: self.strategy,
+            sizer: self.sizer,
+            rebalance: &self.config.rebalance,
+            risk: self.risk,
+            atr: &mut self.atr,
+            summary: &mut self.summary,
+        };
+        block_on(step.run(&mut self.broker, symbol, bar, book))?;
+        self.curve.push(block_on(self.broker.account())?.equity);
+        self.timestamps.push(bar.timestamp.clone());
+        Ok(())
+    }
+
+    fn finish(mut self, bars: &[Bar]) -> BrokerResult<EngineResult> {
+        self.summary.account = block_on(self.broker.account())?;
+        let starting_cash = self.config.starting_cash;
+        let periods_per_year = infer_interval(bars)
+            .map(|interval| self.calendar.periods_per_year(interval))
+            .unwrap_or(252.0);
+        let mut full = Vec::with_capacity(self.curve.len() + 1);
+        full.push(starting_cash);
+        full.extend_from_slice(&self.curve);
+        let trade_pnls = self.broker.trade_pnls().to_vec();
+        let report = BacktestReport {
+            starting_cash,
+            final_equity: self.curve.last().copied().unwrap_or(starting_cash),
+            metrics: PerformanceMetrics::from_equity(&full, periods_per_year),
+            equity_curve: self.curve,
+            timestamps: self.timestamps,
+            wins: trade_pnls.iter().filter(|p| **p > 0.0).count(),
+            losses: trade_pnls.iter().filter(|p| **p <= 0.0).count(),
+            trade_pnls,
+            trades: self.summary.fills.len(),
+            positions: self.broker.portfolio().quantities(),
+            borrow_cost: self.broker.portfolio().borrow_cost,
+        };
+        Ok(EngineResult { report, portfolio: self.broker.portfolio().clone(), summary: self.summary })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::calendar::ExchangeCalendar;
+    use crate::data::corporate_actions::{ActionKind, CorporateAction};
+    use crate::risk::sizing::FixedQuantity;
+    use crate::strategy::always_buy::AlwaysBuy;
+
+    fn bar(ts: &str, close: f64) -> Bar {
+        Bar { timestamp: ts.into(), open: close - 2.0, high: close, low: close - 2.0, close, volume: 20_000.0 }
+    }
+
+    #[test]
+    fn test_dividend_paid_on_split_adjusted_series() {
+        let date = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+        let actions = CorporateActions::new(vec![
+            CorporateAction { symbol: "INFY".into(), ex_date: date("2024-10-03"), kind: ActionKind::Dividend(10.0) },
+            CorporateAction { symbol: "INFY".into(), ex_date: date("2024-10-07"), kind: ActionKind::Split(5.0) },
+        ]);
+        // Back-adjusted for the later split, at 09:30 IST.
+        let bars = vec![
+            bar("2024-10-01T04:00:00+00:00", 200.0),
+            bar("2024-10-02T04:00:00+00:00", 198.0),
+            bar("2024-10-03T04:00:00+00:00", 196.0),
+        ];
+        let config = EngineConfig {
+            starting_cash: 10_000.0,
+            commission_rate: 0.0,
+            slippage_rate: 0.0,
+            corporate_actions: Some(actions),
+            adjustment: Some(AdjustmentMode::PriceOnly),
+            ..Default::default()
+        };
+        let result = run_backtest(
+            &AlwaysBuy,
+            &FixedQuantity(5.0),
+            "INFY",
+            &bars,
+            &config,
+            &ExchangeCalendar::nse(),
+            &mut RiskManager::default(),
+        )
+        .unwrap();
+        // The five adjusted shares held into the ex-date were one real share; the second
+        // buy fills on the ex-date itself and doesn't qualify.
+        assert_eq!(result.portfolio.position("INFY"), 10.0);
+        assert_eq!(result.portfolio.dividends, 10.0);
+        assert_eq!(result.report.final_equity, 10_000.0 - 5.0 * 196.0 - 5.0 * 194.0 + 10.0 + 10.0 * 196.0);
+    }
+
+    #[test]
+    fn test_short_pays_borrow_in_report() {
+        use crate::data::calendar::Crypto247;
+        use crate::strategy::always_sell::AlwaysSell;
+        let bearish = |h: u32| Bar {
+            timestamp: format!("2024-01-01T{:02}:00:00Z", h),
+            open: 102.0,
+            high: 102.0,
+            low: 100.0,
+            close: 100.0,
+            volume: 20_000.0,
+        };
+        let bars: Vec<Bar> = (0..4).map(bearish).collect();
+        let config = EngineConfig {
+            starting_cash: 10_000.0,
+            commission_rate: 0.0,
+            slippage_rate: 0.0,
+            allow_short: true,
+            borrow: Some(BorrowRates::new(0.876)),
+            ..Default::default()
+        };
+        let result = run_backtest(&AlwaysSell, &FixedQuantity(10.0), "X", &bars, &config, &Crypto247, &mut RiskManager::default())
+            .unwrap();
+        // 10 short at the 01:00 open, then 20 at 02:00: charged 0.01% of 1000 and of 2000.
+        assert!((result.report.borrow_cost - 0.3).abs() < 1e-9);
+        assert_eq!(result.report.borrow_cost, result.portfolio.borrow_cost);
+    }
+
+    #[test]
+    fn test_futures_backtest_books_roll_legs() {
+        use crate::data::continuous::{BackAdjust, FuturesContract, RollRule, build_continuous};
+        use crate::data::calendar::Crypto247;
+        use crate::portfolio::FillKind;
+
+        let contract = |symbol: &str, expiry: &str, closes: &[(u32, f64)]| FuturesContract {
+            symbol: symbol.into(),
+            expiry: chrono::DateTime::parse_from_rfc3339(expiry).unwrap().to_utc(),
+            bars: closes.iter().map(|&(h, c)| bar(&format!("2025-03-01T{:02}:00:00+00:00", h), c)).collect(),
+            open_interest: vec![],
+        };
+        let contracts = vec![
+            contract("H25", "2025-03-01T03:00:00Z", &[(0, 100.0), (1, 101.0), (2, 102.0)]),
+            contract("M25", "2025-06-01T00:00:00Z", &[(0, 110.0), (1, 111.0), (2, 112.0), (3, 113.0), (4, 114.0)]),
+        ];
+        let series = build_continuous(contracts, RollRule::DaysBeforeExpiry(0), BackAdjust::None).unwrap();
+        let config = EngineConfig { starting_cash: 10_000.0, commission_rate: 0.0, slippage_rate: 0.0, ..Default::default() };
+        let result = run_futures_backtest(
+            &AlwaysBuy,
+            &FixedQuantity(1.0),
+            &series,
+            &config,
+            &Crypto247,
+            &mut RiskManager::default(),
+        )
+        .unwrap();
+
+        let blotter = &result.portfolio.blotter;
+        let rolls: Vec<_> = blotter.iter().filter(|f| f.kind == FillKind::Roll).collect();
+        assert_eq!(rolls.len(), 2);
+        assert_eq!((rolls[0].symbol.as_str(), rolls[0].quantity, rolls[0].price), ("H25", 2.0, 102.0));
+        assert_eq!((rolls[1].symbol.as_str(), rolls[1].quantity, rolls[1].price), ("M25", 2.0, 113.0));
+        // The buy still working on H25 at the roll fills on M25 at the roll bar's open,
+        // ahead of the legs booked at its close.
+        let moved = blotter.iter().position(|f| f.kind == FillKind::Trade && f.symbol == "M25").unwrap();
+        assert_eq!(blotter[moved].price, 111.0);
+        assert!(moved < blotter.iter().position(|f| f.kind == FillKind::Roll).unwrap());
+        assert_eq!(result.portfolio.position("H25"), 0.0);
+        assert_eq!(result.portfolio.position("M25"), 4.0);
+    }
+
+    #[test]
+    fn test_book_backtest_crosses_the_spread() {
+        use crate::data::calendar::Crypto247;
+        use crate::data::depth::{TickerReplay, TopOfBook};
+
+        let hour = 3_600_000;
+        let start = chrono::DateTime::parse_from_rfc3339("2025-01-01T00:00:00Z").unwrap().timestamp_millis();
+        let bars: Vec<Bar> = (0..3).map(|h| bar(&format!("2025-01-01T{:02}:00:00Z", h), 100.0)).collect();
+        // A wide book quoted just before each bar closes.
+        let quotes = (0..3)
+            .map(|h| TopOfBook { timestamp_ms: start + (h + 1) * hour - 1, bid: 97.0, bid_qty: 5.0, ask: 103.0, ask_qty: 5.0 })
+            .collect();
+        let feed = BarBookFeed::new(bars, TickerReplay::new(quotes), chrono::Duration::hours(1));
+        let config = EngineConfig { starting_cash: 10_000.0, commission_rate: 0.0, slippage_rate: 0.0, ..Default::default() };
+        let result = run_book_backtest(
+            &AlwaysBuy,
+            &FixedQuantity(1.0),
+            "BTCUSDT",
+            feed,
+            &config,
+            &Crypto247,
+            &mut RiskManager::default(),
+        )
+        .unwrap();
+        // Without the book these would fill at the 98.0 open.
+        let prices: Vec<f64> = result.summary.fills.iter().map(|f| f.price).collect();
+        assert_eq!(prices, vec![103.0, 103.0]);
+    }
+}
@@ -1,2 +1,9 @@
 pub mod backtest_single_day;
-pub mod backtest_ema_crossover;
\ No newline at end of file
+pub mod backtest_ema_crossover;
+pub mod report;
+pub mod engine;
+pub mod analytics;
+pub mod monte_carlo;
+pub mod multi_strategy;
+pub mod multi_currency;
+pub mod optimized_portfolio;
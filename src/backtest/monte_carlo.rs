@@ -0,0 +1,250 @@
+use std::error::Error;
+
+use csv::Writer;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::backtest::report::{BacktestReport, max_drawdown, mean_std, returns};
+
+#[derive(Debug, Clone)]
+pub struct MonteCarloConfig {
+    pub runs: usize,
+    pub seed: u64,
+    /// A path is ruined once equity falls to this fraction of starting cash (0.5 = lost half).
+    pub ruin_level: f64,
+}
+
+impl Default for MonteCarloConfig {
+    fn default() -> Self {
+        Self {
+            runs: 1000,
+            seed: 42,
+            ruin_level: 0.5,
+        }
+    }
+}
+
+/// Empirical distribution of one statistic across simulated paths.
+#[derive(Debug, Clone, Default)]
+pub struct Distribution {
+    /// Sorted ascending.
+    pub samples: Vec<f64>,
+    pub mean: f64,
+    pub std: f64,
+}
+
+impl Distribution {
+    pub fn new(mut samples: Vec<f64>) -> Self {
+        samples.sort_by(f64::total_cmp);
+        let (mean, std) = mean_std(&samples);
+        Self { samples, mean, std }
+    }
+
+    /// Linear-interpolated percentile, `q` in `0.0..=1.0`.
+    pub fn percentile(&self, q: f64) -> f64 {
+        if self.samples.is_empty() {
+            return f64::NAN;
+        }
+        let pos = q.clamp(0.0, 1.0) * (self.samples.len() - 1) as f64;
+        let (lo, hi) = (pos.floor() as usize, pos.ceil() as usize);
+        self.samples[lo] + (self.samples[hi] - self.samples[lo]) * (pos - lo as f64)
+    }
+
+    /// Central interval holding `level` of the samples (e.g. 0.95).
+    pub fn confidence_interval(&self, level: f64) -> (f64, f64) {
+        let tail = (1.0 - level) / 2.0;
+        (self.percentile(tail), self.percentile(1.0 - tail))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MonteCarloResult {
+    pub final_return: Distribution,
+    pub max_drawdown: Distribution,
+    /// Share of paths that touched the ruin level.
+    pub risk_of_ruin: f64,
+    /// Per path, in run order: `(final_return, max_drawdown, ruined)`.
+    pub paths: Vec<(f64, f64, bool)>,
+}
+
+impl MonteCarloResult {
+    fn from_paths(paths: Vec<(f64, f64, bool)>) -> Self {
+        let ruined = paths.iter().filter(|p| p.2).count();
+        Self {
+            final_return: Distribution::new(paths.iter().map(|p| p.0).collect()),
+            max_drawdown: Distribution::new(paths.iter().map(|p| p.1).collect()),
+            risk_of_ruin: if paths.is_empty() { 0.0 } else { ruined as f64 / paths.len() as f64 },
+            paths,
+        }
+    }
+
+    pub fn print_summary(&self, title: &str, level: f64) {
+        let (r_lo, r_hi) = self.final_return.confidence_interval(level);
+        let (d_lo, d_hi) = self.max_drawdown.confidence_interval(level);
+        println!("\n=== Monte Carlo: {} ({} runs) ===", title, self.paths.len());
+        println!(
+            "Final return: mean {:.2}% | {:.0}% CI [{:.2}%, {:.2}%]",
+            self.final_return.mean * 100.0,
+            level * 100.0,
+            r_lo * 100.0,
+            r_hi * 100.0
+        );
+        println!(
+            "Max drawdown: mean {:.2}% | {:.0}% CI [{:.2}%, {:.2}%]",
+            self.max_drawdown.mean * 100.0,
+            level * 100.0,
+            d_lo * 100.0,
+            d_hi * 100.0
+        );
+        println!("Risk of ruin: {:.2}%", self.risk_of_ruin * 100.0);
+    }
+
+    pub fn write_csv(&self, path: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut wtr = Writer::from_path(path)?;
+        wtr.write_record(["run", "final_return", "max_drawdown", "ruined"])?;
+        for (i, (ret, dd, ruined)) in self.paths.iter().enumerate() {
+            wtr.write_record([i.to_string(), ret.to_string(), dd.to_string(), ruined.to_string()])?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+/// Same trades, random order: the final return is unchanged but drawdowns and ruin vary.
+pub fn reshuffle_trades(report: &BacktestReport, config: &MonteCarloConfig) -> MonteCarloResult {
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let mut pnls = report.trade_pnls.clone();
+    let paths = (0..config.runs)
+        .map(|_| {
+            pnls.shuffle(&mut rng);
+            trade_path(report.starting_cash, &pnls, config.ruin_level)
+        })
+        .collect();
+    MonteCarloResult::from_paths(paths)
+}
+
+/// Drops each trade with probability `skip_pct` (0.0..=1.0), as if signals were missed.
+pub fn skip_trades(report: &BacktestReport, skip_pct: f64, config: &MonteCarloConfig) -> MonteCarloResult {
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let p = skip_pct.clamp(0.0, 1.0);
+    let paths = (0..config.runs)
+        .map(|_| {
+            let kept: Vec<f64> = report.trade_pnls.iter().copied().filter(|_| !rng.gen_bool(p)).collect();
+            trade_path(report.starting_cash, &kept, config.ruin_level)
+        })
+        .collect();
+    MonteCarloResult::from_paths(paths)
+}
+
+/// Rebuilds equity paths from per-bar returns drawn with replacement, in blocks of
+/// `block_size` consecutive bars to keep some autocorrelation (1 = plain bootstrap).
+pub fn bootstrap_returns(report: &BacktestReport, block_size: usize, config: &MonteCarloConfig) -> MonteCarloResult {
+    let mut curve = vec![report.starting_cash];
+    curve.extend(&report.equity_curve);
+    let rets = returns(&curve);
+    if rets.is_empty() {
+        return MonteCarloResult::default();
+    }
+    let block = block_size.clamp(1, rets.len());
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let paths = (0..config.runs)
+        .map(|_| {
+            let mut equity = vec![report.starting_cash];
+            while equity.len() <= rets.len() {
+                let start = rng.gen_range(0..=rets.len() - block);
+                for r in &rets[start..start + block] {
+                    if equity.len() > rets.len() {
+                        break;
+                    }
+                    let last = *equity.last().unwrap();
+                    equity.push(last * (1.0 + r));
+                }
+            }
+            equity_path(&equity, config.ruin_level)
+        })
+        .collect();
+    MonteCarloResult::from_paths(paths)
+}
+
+/// Re-runs the backtest with fees and slippage each scaled by a random factor in
+/// `1 ± jitter`. `backtest` receives `(fee_multiplier, slippage_multiplier)`.
+pub fn perturb_costs(
+    jitter: f64,
+    config: &MonteCarloConfig,
+    backtest: impl Fn(f64, f64) -> BacktestReport,
+) -> MonteCarloResult {
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let j = jitter.abs();
+    let paths = (0..config.runs)
+        .map(|_| {
+            let fee_mult = 1.0 + rng.gen_range(-j..=j);
+            let slip_mult = 1.0 + rng.gen_range(-j..=j);
+            let report = backtest(fee_mult, slip_mult);
+            let mut curve = vec![report.starting_cash];
+            curve.extend(&report.equity_curve);
+            equity_path(&curve, config.ruin_level)
+        })
+        .collect();
+    MonteCarloResult::from_paths(paths)
+}
+
+fn trade_path(starting_cash: f64, pnls: &[f64], ruin_level: f64) -> (f64, f64, bool) {
+    let mut equity = Vec::with_capacity(pnls.len() + 1);
+    equity.push(starting_cash);
+    for pnl in pnls {
+        equity.push(equity.last().unwrap() + pnl);
+    }
+    equity_path(&equity, ruin_level)
+}
+
+fn equity_path(equity: &[f64], ruin_level: f64) -> (f64, f64, bool) {
+    let start = equity[0];
+    let last = *equity.last().unwrap();
+    let ruined = equity.iter().any(|&e| e <= start * ruin_level);
+    (last / start - 1.0, max_drawdown(equity), ruined)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> BacktestReport {
+        BacktestReport {
+            starting_cash: 1_000.0,
+            final_equity: 1_100.0,
+            equity_curve: vec![1_050.0, 900.0, 1_000.0, 1_100.0],
+            trade_pnls: vec![300.0, -400.0, 200.0, -100.0, 100.0],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_reshuffle_keeps_final_return() {
+        let config = MonteCarloConfig { runs: 200, seed: 1, ruin_level: 0.4 };
+        let mc = reshuffle_trades(&report(), &config);
+        assert!(mc.final_return.samples.iter().all(|r| (r - 0.1).abs() < 1e-12));
+        assert!(mc.max_drawdown.samples.first() < mc.max_drawdown.samples.last());
+        assert_eq!(mc.risk_of_ruin, 0.0);
+    }
+
+    #[test]
+    fn test_skip_and_bootstrap_are_seeded() {
+        let config = MonteCarloConfig { runs: 100, seed: 9, ruin_level: 0.95 };
+        let a = skip_trades(&report(), 0.3, &config);
+        let b = skip_trades(&report(), 0.3, &config);
+        assert_eq!(a.paths, b.paths);
+        let boot = bootstrap_returns(&report(), 2, &config);
+        assert_eq!(boot.paths.len(), 100);
+        assert!(boot.risk_of_ruin > 0.0);
+        let (lo, hi) = boot.final_return.confidence_interval(0.9);
+        assert!(lo <= hi);
+    }
+
+    #[test]
+    fn test_percentile_interpolates() {
+        let d = Distribution::new(vec![3.0, 1.0, 2.0, 4.0]);
+        assert_eq!(d.percentile(0.5), 2.5);
+        assert_eq!(d.confidence_interval(1.0), (1.0, 4.0));
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::Duration;
+
+use crate::backtest::report::{BacktestReport, PerformanceMetrics};
+use crate::data::calendar::TradingCalendar;
+use crate::data::feed::{MultiSymbolFeed, Symbol};
+use crate::data::order::OrderSide;
+use crate::portfolio::currency::MultiCurrencyPortfolio;
+use crate::portfolio::{Fill, FillKind};
+use crate::strategy::Strategy;
+
+/// Runs each symbol's strategy over `feed`, settling fills in `portfolio`'s currency
+/// books and marking the account in its base currency after every slice. Signals fill
+/// at their price with commission in the quote currency. Buys need the quote cash;
+/// sells of spot pairs need the base cash, other sells may go short. The report is in
+/// the base currency and starts from the account's value at the first slice; slices
+/// where some currency has no rate repeat the last mark.
+pub fn run_multi_currency(
+    strategies: &[(Symbol, Arc<dyn Strategy>)],
+    feed: MultiSymbolFeed,
+    portfolio: &mut MultiCurrencyPortfolio,
+    commission_rate: f64,
+    calendar: &dyn TradingCalendar,
+) -> BacktestReport {
+    let mut prices: HashMap<Symbol, f64> = HashMap::new();
+    let mut starting_cash = None;
+    let mut curve = Vec::new();
+    let mut timestamps = Vec::new();
+    let mut slice_times = Vec::new();
+    let mut trade_pnls = Vec::new();
+    let mut trades = 0usize;
+
+    for slice in feed {
+        portfolio.on_slice(&slice);
+        if starting_cash.is_none() {
+            starting_cash = portfolio.equity(&prices);
+        }
+        for (symbol, strategy) in strategies {
+            let Some(bar) = slice.bars.get(symbol) else {
+                continue;
+            };
+            let Some(order) = strategy.generate_signal(bar) else {
+                continue;
+            };
+            let quantity = order.quantity as f64;
+            let fee = order.price * quantity * commission_rate;
+            let quote = portfolio.currency_of(symbol).to_string();
+            let cash = |currency: &str| portfolio.book(currency).map_or(0.0, |b| b.cash);
+            let affordable = match (order.side, portfolio.instrument(symbol).filter(|i| i.spot)) {
+                (OrderSide::Buy, _) => order.price * quantity + fee <= cash(&quote),
+                (OrderSide::Sell, Some(pair)) => quantity <= cash(&pair.base) + 1e-12,
+                (OrderSide::Sell, None) => true,
+            };
+            if quantity <= 0.0 || !affordable {
+                continue;
+            }
+            let realized = portfolio.apply_fill(Fill {
+                timestamp: bar.timestamp.clone(),
+                symbol: symbol.clone(),
+                side: order.side,
+                quantity,
+                price: order.price,
+                fee,
+                kind: FillKind::Trade,
+            });
+            trades += 1;
+            if realized != 0.0 {
+                // Converted at the current rate, like the equity curve.
+                let pnl = portfolio.fx.convert(realized - fee, &quote, &portfolio.base);
+                trade_pnls.push(pnl.unwrap_or(realized - fee));
+            }
+        }
+        for (symbol, bar) in &slice.bars {
+            prices.insert(symbol.clone(), bar.close);
+        }
+
+        let marked = portfolio.equity(&prices).or(curve.last().copied()).or(starting_cash).unwrap_or(0.0);
+        curve.push(marked);
+        timestamps.push(slice.timestamp.to_rfc3339());
+        slice_times.push(slice.timestamp);
+    }
+
+    let starting_cash = starting_cash.unwrap_or(0.0);
+    let interval = slice_times
+        .windows(2)
+        .take(16)
+        .map(|w| w[1] - w[0])
+        .filter(|d| *d > Duration::zero())
+        .min();
+    let periods_per_year = interval.map(|i| calendar.periods_per_year(i)).unwrap_or(252.0);
+    let mut full = Vec::with_capacity(curve.len() + 1);
+    full.push(starting_cash);
+    full.extend_from_slice(&curve);
+    BacktestReport {
+        starting_cash,
+        final_equity: curve.last().copied().unwrap_or(starting_cash),
+        metrics: PerformanceMetrics::from_equity(&full, periods_per_year),
+        equity_curve: curve,
+        timestamps,
+        wins: trade_pnls.iter().filter(|p| **p > 0.0).count(),
+        losses: trade_pnls.iter().filter(|p| **p <= 0.0).count(),
+        trade_pnls,
+        trades,
+        positions: portfolio.quantities(),
+        borrow_cost: 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::bar::Bar;
+    use crate::data::calendar::Crypto247;
+    use crate::data::feed::MissingBarPolicy;
+    use crate::strategy::{always_buy::AlwaysBuy, always_sell::AlwaysSell};
+
+    /// Bullish bars for `AlwaysBuy`, bearish ones for `AlwaysSell`.
+    fn bars(closes: &[f64], bullish: bool) -> Vec<Bar> {
+        let open = if bullish { 0.99 } else { 1.01 };
+        closes
+            .iter()
+            .enumerate()
+            .map(|(h, &close)| Bar {
+                timestamp: format!("2024-01-01T{:02}:00:00Z", h),
+                open: close * open,
+                high: close * 1.01,
+                low: close * 0.99,
+                close,
+                volume: 50_000.0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_eth_bought_for_btc_is_sold_for_usdt() {
+        let series = HashMap::from([
+            ("ETHBTC".to_string(), bars(&[0.05, 0.05], true)),
+            ("BTCUSDT".to_string(), bars(&[50_000.0, 50_000.0], true)),
+            ("ETHUSDT".to_string(), bars(&[2_500.0, 3_000.0], false)),
+        ]);
+        let feed = MultiSymbolFeed::from_series(series, MissingBarPolicy::Skip).unwrap();
+        let mut portfolio = MultiCurrencyPortfolio::new("USDT")
+            .with_pair("ETHBTC", "ETH", "BTC")
+            .with_pair("BTCUSDT", "BTC", "USDT")
+            .with_pair("ETHUSDT", "ETH", "USDT");
+        portfolio.deposit("BTC", 1.0);
+        // Buys one ETH per bar on ETHBTC and sells it on ETHUSDT.
+        let strategies: Vec<(Symbol, Arc<dyn Strategy>)> =
+            vec![("ETHBTC".into(), Arc::new(AlwaysBuy)), ("ETHUSDT".into(), Arc::new(AlwaysSell))];
+        let report = run_multi_currency(&strategies, feed, &mut portfolio, 0.0, &Crypto247);
+
+        assert_eq!(report.starting_cash, 50_000.0);
+        assert_eq!(report.trades, 4);
+        let cash = portfolio.cash_balances();
+        assert!((cash["BTC"] - 0.9).abs() < 1e-12);
+        assert_eq!(cash["ETH"], 0.0);
+        assert_eq!(cash["USDT"], 5_500.0);
+        // 0.9 BTC (45k) + 5.5k USDT.
+        assert!((report.final_equity - 50_500.0).abs() < 1e-6);
+    }
+}
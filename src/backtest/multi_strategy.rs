@@ -0,0 +1,461 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::backtest::analytics::{equity_returns, rolling_correlation};
+use crate::backtest::report::{BacktestReport, PerformanceMetrics};
+use crate::broker::OrderRequest;
+use crate::data::bar::{Bar, infer_interval};
+use crate::data::calendar::TradingCalendar;
+use crate::data::order::{OrderSide, Signal};
+use crate::portfolio::allocation::AllocationScheme;
+use crate::portfolio::rebalance::{RebalanceConfig, rebalance_orders};
+use crate::portfolio::{Fill, FillKind, Portfolio};
+use crate::risk::sizing::{PositionSizer, SizingContext};
+use crate::risk::{RiskLimits, RiskManager};
+use crate::strategy::Strategy;
+use crate::strategy::indicators::Atr;
+
+/// Capital budgeting across strategies that each trade their own sub-account.
+#[derive(Debug, Clone)]
+pub struct MultiStrategyConfig {
+    pub scheme: AllocationScheme,
+    pub starting_cash: f64,
+    /// Bars between rebalances; 0 keeps the initial split.
+    pub rebalance_every: usize,
+    /// Bars of sub-account returns the volatility-based schemes look at.
+    pub lookback: usize,
+    pub commission_rate: f64,
+    /// Sizes each sub-account's entries from its own equity; exits are capped at its
+    /// position. `None` trades the strategies' own quantities.
+    pub sizer: Option<Arc<dyn PositionSizer>>,
+    /// Filters for trading towards strategies' targets; unrelated to moving capital.
+    pub rebalance: RebalanceConfig,
+    /// Limits applied to each sub-account separately; a kill switch flattens and stops
+    /// only the strategy that tripped it.
+    pub risk: RiskLimits,
+}
+
+impl Default for MultiStrategyConfig {
+    fn default() -> Self {
+        Self {
+            scheme: AllocationScheme::Equal,
+            starting_cash: 1_000_000.0,
+            rebalance_every: 24,
+            lookback: 24 * 7,
+            commission_rate: 0.001,
+            sizer: None,
+            rebalance: RebalanceConfig::default(),
+            risk: RiskLimits::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StrategyResult {
+    pub name: String,
+    /// Performance of the sub-account itself, i.e. excluding capital moved in or out by
+    /// rebalancing, scaled to its initial budget.
+    pub report: BacktestReport,
+}
+
+#[derive(Debug, Clone)]
+pub struct MultiStrategyReport {
+    pub strategies: Vec<StrategyResult>,
+    pub combined: BacktestReport,
+    /// Weights set at the start and at each rebalance, with the bar timestamp.
+    pub allocations: Vec<(String, Vec<f64>)>,
+}
+
+impl MultiStrategyReport {
+    /// Rolling correlation of each pair of strategies' sub-account returns over `window`
+    /// bars, aligned with the reports' timestamps.
+    pub fn rolling_correlations(&self, window: usize) -> Vec<(String, String, Vec<Option<f64>>)> {
+        let returns: Vec<Vec<f64>> = self.strategies.iter().map(|s| equity_returns(&s.report)).collect();
+        let mut pairs = Vec::new();
+        for i in 0..self.strategies.len() {
+            for j in i + 1..self.strategies.len() {
+                let corr = rolling_correlation(&returns[i], &returns[j], window);
+                pairs.push((self.strategies[i].name.clone(), self.strategies[j].name.clone(), corr));
+            }
+        }
+        pairs
+    }
+
+    pub fn print_summary(&self) {
+        println!("\n=== Multi-Strategy Summary ===");
+        println!("{:<16} {:>14} {:>9} {:>8} {:>9} {:>7}", "Strategy", "Final Equity", "Return", "Sharpe", "Max DD", "Trades");
+        let row = |name: &str, r: &BacktestReport| {
+            println!(
+                "{:<16} {:>14.2} {:>8.2}% {:>8.2} {:>8.2}% {:>7}",
+                name,
+                r.final_equity,
+                r.metrics.total_return * 100.0,
+                r.metrics.sharpe,
+                r.metrics.max_drawdown * 100.0,
+                r.trades
+            );
+        };
+        for s in &self.strategies {
+            row(&s.name, &s.report);
+        }
+        row("Combined", &self.combined);
+        if let Some((ts, weights)) = self.allocations.last() {
+            let names: Vec<String> = self
+                .strategies
+                .iter()
+                .zip(weights)
+                .map(|(s, w)| format!("{} {:.1}%", s.name, w * 100.0))
+                .collect();
+            println!("Last allocation ({}): {}", ts, names.join(", "));
+        }
+    }
+}
+
+struct SubAccount {
+    portfolio: Portfolio,
+    budget: f64,
+    /// Equity right after the last capital transfer, and the NAV at that point.
+    base_equity: f64,
+    base_nav: f64,
+    nav: f64,
+    returns: Vec<f64>,
+    curve: Vec<f64>,
+    trade_pnls: Vec<f64>,
+    trades: usize,
+    risk: RiskManager,
+}
+
+impl SubAccount {
+    fn trade(&mut self, timestamp: &str, symbol: &str, side: OrderSide, quantity: f64, price: f64, commission_rate: f64) {
+        let fee = price * quantity * commission_rate;
+        let realized = self.portfolio.apply_fill(Fill {
+            timestamp: timestamp.to_string(),
+            symbol: symbol.to_string(),
+            side,
+            quantity,
+            price,
+            fee,
+            kind: FillKind::Trade,
+        });
+        self.trades += 1;
+        if realized != 0.0 {
+            self.trade_pnls.push(realized - fee);
+        }
+    }
+}
+
+/// Runs each strategy in its own sub-account on `symbol`'s bars. Budgets come from
+/// `config.scheme` and are reset every `rebalance_every` bars by moving cash between
+/// sub-accounts; positions are only trimmed when a sub-account's cash can't cover
+/// what it gives up. Signals fill at their price with commission
+/// after passing the sub-account's risk manager, and targets are traded towards at the
+/// close; buys that the sub-account can't afford are skipped, sells may go short.
+pub fn run_multi_strategy(
+    strategies: &[(String, Arc<dyn Strategy>)],
+    symbol: &str,
+    bars: &[Bar],
+    config: &MultiStrategyConfig,
+    calendar: &dyn TradingCalendar,
+) -> MultiStrategyReport {
+    let n = strategies.len();
+    let initial = config.scheme.weights(&vec![Vec::new(); n]);
+    let mut subs: Vec<SubAccount> = initial
+        .iter()
+        .map(|w| w * config.starting_cash)
+        .map(|cash| SubAccount {
+            portfolio: Portfolio::new(cash),
+            budget: cash,
+            base_equity: cash,
+            base_nav: 1.0,
+            nav: 1.0,
+            returns: Vec::new(),
+            curve: Vec::with_capacity(bars.len()),
+            trade_pnls: Vec::new(),
+            trades: 0,
+            risk: RiskManager::new(config.risk.clone()),
+        })
+        .collect();
+    let mut allocations = Vec::new();
+    if let Some(first) = bars.first() {
+        allocations.push((first.timestamp.clone(), initial));
+    }
+    let mut atr = Atr::new(14);
+    let mut combined = Vec::with_capacity(bars.len());
+    let mut timestamps = Vec::with_capacity(bars.len());
+
+    for (k, bar) in bars.iter().enumerate() {
+        let prices = HashMap::from([(symbol.to_string(), bar.close)]);
+        let date = bar.datetime().map(|t| calendar.local_date(t)).unwrap_or_default();
+        let symbol_atr = atr.update(bar);
+        for ((_, strategy), sub) in strategies.iter().zip(subs.iter_mut()) {
+            let position = sub.portfolio.position(symbol);
+            if sub.risk.on_equity(sub.portfolio.equity(&prices), date, &bar.timestamp) && position != 0.0 {
+                // Kill switch: flatten this sub-account at the close.
+                let side = if position > 0.0 { OrderSide::Sell } else { OrderSide::Buy };
+                sub.trade(&bar.timestamp, symbol, side, position.abs(), bar.close, config.commission_rate);
+            }
+            // Targets are traded towards at the close; signals at their own price.
+            let requests: Vec<(OrderRequest, f64)> = if let Some(target) = strategy.target(bar) {
+                let positions = HashMap::from([(symbol.to_string(), sub.portfolio.position(symbol))]);
+                let equity = sub.portfolio.equity(&prices);
+                rebalance_orders(&HashMap::from([(symbol.to_string(), target)]), &positions, &prices, equity, &config.rebalance)
+                    .into_iter()
+                    .map(|request| (request, bar.close))
+                    .collect()
+            } else if let Some(order) = strategy.generate_signal(bar) {
+                let position = sub.portfolio.position(symbol);
+                let quantity = match &config.sizer {
+                    Some(sizer) => {
+                        let ctx = SizingContext {
+                            equity: sub.portfolio.equity(&prices),
+                            cash: sub.portfolio.cash,
+                            price: order.price,
+                            position,
+                            atr: symbol_atr,
+                        };
+                        let reducing = match order.side {
+                            OrderSide::Buy => position < 0.0,
+                            OrderSide::Sell => position > 0.0,
+                        };
+                        let signal = Signal::from(order.clone());
+                        if reducing {
+                            sizer.exit_size(&signal, &ctx).min(position.abs())
+                        } else {
+                            sizer.size(&signal, &ctx)
+                        }
+                    }
+                    None => order.quantity as f64,
+                };
+                vec![(OrderRequest::market(symbol, order.side, quantity), order.price)]
+            } else {
+                Vec::new()
+            };
+            for (request, price) in requests {
+                let fee = price * request.quantity * config.commission_rate;
+                let position = sub.portfolio.position(symbol);
+                let affordable = request.side == OrderSide::Sell
+                    || price * request.quantity + fee <= sub.portfolio.cash;
+                if request.quantity > 0.0
+                    && affordable
+                    && sub.risk.check(&request, price, position, 0, &bar.timestamp).is_ok()
+                {
+                    sub.trade(&bar.timestamp, symbol, request.side, request.quantity, price, config.commission_rate);
+                }
+            }
+            for event in sub.risk.take_events() {
+                strategy.on_risk_event(&event);
+            }
+            let equity = sub.portfolio.equity(&prices);
+            let growth = if sub.base_equity > 0.0 { equity / sub.base_equity } else { 1.0 };
+            let nav = sub.base_nav * growth;
+            sub.returns.push(if sub.nav != 0.0 { nav / sub.nav - 1.0 } else { 0.0 });
+            sub.nav = nav;
+            sub.curve.push(nav * sub.budget);
+        }
+
+        let total: f64 = subs.iter().map(|s| s.portfolio.equity(&prices)).sum();
+        combined.push(total);
+        timestamps.push(bar.timestamp.clone());
+
+        if config.rebalance_every > 0 && (k + 1) % config.rebalance_every == 0 && k + 1 < bars.len() {
+            let window: Vec<Vec<f64>> = subs
+                .iter()
+                .map(|s| s.returns[s.returns.len().saturating_sub(config.lookback)..].to_vec())
+                .collect();
+            let weights = config.scheme.weights(&window);
+            for (sub, w) in subs.iter_mut().zip(&weights) {
+                let transfer = w * total - sub.portfolio.equity(&prices);
+                // Capital tied up in a long is freed by selling part of it, so the
+                // sub-account isn't left running on negative cash.
+                let shortfall = -(sub.portfolio.cash + transfer);
+                let held = sub.portfolio.position(symbol);
+                if shortfall > 0.0 && held > 0.0 && bar.close > 0.0 {
+                    let quantity = (shortfall / (bar.close * (1.0 - config.commission_rate))).min(held);
+                    sub.trade(&bar.timestamp, symbol, OrderSide::Sell, quantity, bar.close, config.commission_rate);
+                }
+                sub.portfolio.cash += transfer;
+                sub.base_equity = sub.portfolio.equity(&prices);
+                sub.base_nav = sub.nav;
+            }
+            allocations.push((bar.timestamp.clone(), weights));
+        }
+    }
+
+    let periods_per_year = infer_interval(bars)
+        .map(|interval| calendar.periods_per_year(interval))
+        .unwrap_or(252.0);
+    let report = |starting_cash: f64, curve: Vec<f64>, trade_pnls: Vec<f64>, trades: usize, positions| {
+        let mut full = Vec::with_capacity(curve.len() + 1);
+        full.push(starting_cash);
+        full.extend_from_slice(&curve);
+        BacktestReport {
+            starting_cash,
+            final_equity: curve.last().copied().unwrap_or(starting_cash),
+            metrics: PerformanceMetrics::from_equity(&full, periods_per_year),
+            equity_curve: curve,
+            timestamps: timestamps.clone(),
+            wins: trade_pnls.iter().filter(|p| **p > 0.0).count(),
+            losses: trade_pnls.iter().filter(|p| **p <= 0.0).count(),
+            trade_pnls,
+            trades,
+            positions,
+            borrow_cost: 0.0,
+        }
+    };
+
+    let all_pnls: Vec<f64> = subs.iter().flat_map(|s| s.trade_pnls.iter().copied()).collect();
+    let all_trades = subs.iter().map(|s| s.trades).sum();
+    let mut all_positions: HashMap<String, f64> = HashMap::new();
+    for (symbol, quantity) in subs.iter().flat_map(|s| s.portfolio.quantities()) {
+        *all_positions.entry(symbol).or_default() += quantity;
+    }
+    all_positions.retain(|_, q| *q != 0.0);
+    let combined = report(config.starting_cash, combined, all_pnls, all_trades, all_positions);
+    let strategies = strategies
+        .iter()
+        .zip(subs)
+        .map(|((name, _), sub)| StrategyResult {
+            name: name.clone(),
+            report: report(sub.budget, sub.curve, sub.trade_pnls, sub.trades, sub.portfolio.quantities()),
+        })
+        .collect();
+    MultiStrategyReport { strategies, combined, allocations }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::calendar::Crypto247;
+    use crate::strategy::{always_buy::AlwaysBuy, always_sell::AlwaysSell};
+
+    fn bars() -> Vec<Bar> {
+        (0..12)
+            .map(|h| {
+                let close = 100.0 + h as f64;
+                Bar {
+                    timestamp: format!("2024-01-01T{:02}:00:00Z", h),
+                    open: close - 1.0,
+                    high: close,
+                    low: close - 1.0,
+                    close,
+                    volume: 50_000.0,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sub_accounts_do_not_net() {
+        let strategies: Vec<(String, Arc<dyn Strategy>)> =
+            vec![("buy".into(), Arc::new(AlwaysBuy)), ("sell".into(), Arc::new(AlwaysSell))];
+        let config = MultiStrategyConfig {
+            scheme: AllocationScheme::Fixed(vec![3.0, 1.0]),
+            starting_cash: 10_000.0,
+            rebalance_every: 4,
+            commission_rate: 0.0,
+            ..Default::default()
+        };
+        let report = run_multi_strategy(&strategies, "X", &bars(), &config, &Crypto247);
+        let buy = &report.strategies[0].report;
+        assert_eq!((buy.starting_cash, report.strategies[1].report.starting_cash), (7_500.0, 2_500.0));
+        assert_eq!(buy.trades, 12);
+        assert!(buy.final_equity > buy.starting_cash);
+        assert_eq!(report.allocations.len(), 3);
+        assert!(report.allocations.iter().all(|(_, w)| w == &vec![0.75, 0.25]));
+        // The idle seller's capital grows with rebalancing, but its own performance doesn't.
+        let sell = &report.strategies[1].report;
+        assert_eq!((sell.trades, sell.final_equity), (0, 2_500.0));
+        assert!(report.combined.final_equity > config.starting_cash);
+    }
+
+    #[test]
+    fn test_inverse_vol_rebalance_moves_cash() {
+        let strategies: Vec<(String, Arc<dyn Strategy>)> =
+            vec![("a".into(), Arc::new(AlwaysBuy)), ("b".into(), Arc::new(AlwaysBuy))];
+        let config = MultiStrategyConfig {
+            scheme: AllocationScheme::InverseVolatility,
+            starting_cash: 10_000.0,
+            rebalance_every: 6,
+            commission_rate: 0.0,
+            ..Default::default()
+        };
+        let report = run_multi_strategy(&strategies, "X", &bars(), &config, &Crypto247);
+        // Identical strategies keep an even split and identical results.
+        let w = &report.allocations[1].1;
+        assert!((w[0] - 0.5).abs() < 1e-12);
+        let (a, b) = (&report.strategies[0].report, &report.strategies[1].report);
+        assert!((a.final_equity - b.final_equity).abs() < 1e-9);
+        assert!((report.combined.final_equity - a.final_equity - b.final_equity).abs() < 1e-6);
+        let corr = report.rolling_correlations(4);
+        assert_eq!((corr.len(), corr[0].2.len()), (1, 12));
+        assert!((corr[0].2[11].unwrap() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_risk_limits_apply_per_sub_account() {
+        let strategies: Vec<(String, Arc<dyn Strategy>)> =
+            vec![("a".into(), Arc::new(AlwaysBuy)), ("b".into(), Arc::new(AlwaysBuy))];
+        let config = MultiStrategyConfig {
+            starting_cash: 10_000.0,
+            rebalance_every: 0,
+            commission_rate: 0.0,
+            risk: RiskLimits::default().with_max_position(3.0),
+            ..Default::default()
+        };
+        let report = run_multi_strategy(&strategies, "X", &bars(), &config, &Crypto247);
+        // Each sub-account fills up to its own limit; they don't share it.
+        assert!(report.strategies.iter().all(|s| s.report.trades == 3));
+    }
+
+    #[test]
+    fn test_target_strategy_is_rebalanced() {
+        use crate::data::order::Order;
+        use crate::portfolio::rebalance::Target;
+        struct HalfLong;
+        impl Strategy for HalfLong {
+            fn generate_signal(&self, _bar: &Bar) -> Option<Order> {
+                None
+            }
+            fn target(&self, _bar: &Bar) -> Option<Target> {
+                Some(Target::Weight(0.5))
+            }
+        }
+        let strategies: Vec<(String, Arc<dyn Strategy>)> = vec![("half".into(), Arc::new(HalfLong))];
+        let config = MultiStrategyConfig {
+            starting_cash: 10_000.0,
+            rebalance_every: 0,
+            commission_rate: 0.0,
+            ..Default::default()
+        };
+        let report = run_multi_strategy(&strategies, "X", &bars(), &config, &Crypto247);
+        // Bought in on the first bar, then trimmed back to half on every rise.
+        let half = &report.strategies[0].report;
+        assert_eq!(half.trades, 12);
+        let all_in = 10_000.0 * 111.0 / 100.0;
+        assert!(half.final_equity > 10_000.0 && half.final_equity < (10_000.0 + all_in) / 2.0 + 1e-9);
+    }
+
+    #[test]
+    fn test_sized_sub_account_is_trimmed_to_fund_rebalance() {
+        use crate::risk::sizing::PercentEquity;
+        let strategies: Vec<(String, Arc<dyn Strategy>)> =
+            vec![("buy".into(), Arc::new(AlwaysBuy)), ("idle".into(), Arc::new(AlwaysSell))];
+        let config = MultiStrategyConfig {
+            starting_cash: 10_000.0,
+            rebalance_every: 4,
+            commission_rate: 0.0,
+            sizer: Some(Arc::new(PercentEquity(1.0))),
+            ..Default::default()
+        };
+        let report = run_multi_strategy(&strategies, "X", &bars(), &config, &Crypto247);
+        let buy = &report.strategies[0].report;
+        // All in for 50 units on the first bar; the two rebalances that take its gains
+        // away sell the difference instead of borrowing it, and no buy is affordable after.
+        assert_eq!(buy.trades, 3);
+        assert_eq!(buy.wins, 2);
+        let idle = &report.strategies[1].report;
+        assert_eq!((idle.trades, idle.final_equity), (0, 5_000.0));
+        assert!(idle.positions.is_empty());
+        assert_eq!(report.combined.positions, buy.positions);
+    }
+}
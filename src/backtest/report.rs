@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::data::feed::Symbol;
+
+/// Headline statistics of an equity curve.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PerformanceMetrics {
+    #[serde(with = "non_finite")]
+    pub total_return: f64,
+    #[serde(with = "non_finite")]
+    pub annualized_return: f64,
+    #[serde(with = "non_finite")]
+    pub annualized_volatility: f64,
+    #[serde(with = "non_finite")]
+    pub sharpe: f64,
+    /// Worst peak-to-trough loss, as a positive fraction of the peak.
+    #[serde(with = "non_finite")]
+    pub max_drawdown: f64,
+}
+
+/// Serde helpers for `f64` fields that may be NaN or infinite, which JSON numbers
+/// can't hold: those are written as `"NaN"`, `"inf"` or `"-inf"`. `null` reads as NaN.
+pub(crate) mod non_finite {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Repr {
+        Number(f64),
+        Text(String),
+    }
+
+    pub fn serialize<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        if value.is_finite() {
+            serializer.serialize_f64(*value)
+        } else {
+            serializer.collect_str(value)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        match Option::<Repr>::deserialize(deserializer)? {
+            Some(Repr::Number(value)) => Ok(value),
+            Some(Repr::Text(text)) => text.parse().map_err(serde::de::Error::custom),
+            None => Ok(f64::NAN),
+        }
+    }
+}
+
+impl PerformanceMetrics {
+    /// `periods_per_year` is the number of equity points per year (see `TradingCalendar::periods_per_year`).
+    pub fn from_equity(equity: &[f64], periods_per_year: f64) -> Self {
+        let (Some(&first), Some(&last)) = (equity.first(), equity.last()) else {
+            return Self::default();
+        };
+        if first <= 0.0 {
+            return Self::default();
+        }
+        let rets = returns(equity);
+        let total_return = last / first - 1.0;
+        let years = rets.len() as f64 / periods_per_year;
+        let annualized_return = if years > 0.0 && last > 0.0 {
+            (last / first).powf(1.0 / years) - 1.0
+        } else {
+            0.0
+        };
+        let (mean, std) = mean_std(&rets);
+        let annualized_volatility = std * periods_per_year.sqrt();
+        let sharpe = if std > 0.0 {
+            mean / std * periods_per_year.sqrt()
+        } else {
+            0.0
+        };
+        Self {
+            total_return,
+            annualized_return,
+            annualized_volatility,
+            sharpe,
+            max_drawdown: max_drawdown(equity),
+        }
+    }
+}
+
+/// Result of one backtest run, with enough detail for optimizers and robustness tests.
+#[derive(Debug, Clone, Default)]
+pub struct BacktestReport {
+    pub starting_cash: f64,
+    pub final_equity: f64,
+    /// Marked-to-market equity after each bar.
+    pub equity_curve: Vec<f64>,
+    /// Timestamps matching `equity_curve`.
+    pub timestamps: Vec<String>,
+    /// Realized PnL of each closed trade, net of fees, in close order.
+    pub trade_pnls: Vec<f64>,
+    pub trades: usize,
+    pub wins: usize,
+    pub losses: usize,
+    pub metrics: PerformanceMetrics,
+    /// Quantities still open when the run ended, by symbol; empty if it finished flat.
+    pub positions: HashMap<Symbol, f64>,
+    /// Interest paid on borrowed assets and negative cash, already in the equity curve.
+    pub borrow_cost: f64,
+}
+
+/// Simple period returns of an equity curve.
+pub fn returns(equity: &[f64]) -> Vec<f64> {
+    equity
+        .windows(2)
+        .map(|w| if w[0] != 0.0 { w[1] / w[0] - 1.0 } else { 0.0 })
+        .collect()
+}
+
+pub fn max_drawdown(equity: &[f64]) -> f64 {
+    let mut peak = f64::MIN;
+    let mut worst = 0.0f64;
+    for &e in equity {
+        peak = peak.max(e);
+        if peak > 0.0 {
+            worst = worst.max(1.0 - e / peak);
+        }
+    }
+    worst
+}
+
+/// Sample mean and standard deviation (n - 1).
+pub fn mean_std(values: &[f64]) -> (f64, f64) {
+    if values.is_empty() {
+        return (0.0, 0.0);
+    }
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    if values.len() < 2 {
+        return (mean, 0.0);
+    }
+    let var = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    (mean, var.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_max_drawdown() {
+        assert_eq!(max_drawdown(&[100.0, 120.0, 90.0, 130.0, 117.0]), 0.25);
+        assert_eq!(max_drawdown(&[100.0, 101.0, 102.0]), 0.0);
+    }
+
+    #[test]
+    fn test_metrics_from_equity() {
+        let m = PerformanceMetrics::from_equity(&[100.0, 110.0, 99.0, 121.0], 3.0);
+        assert!((m.total_return - 0.21).abs() < 1e-12);
+        assert!((m.annualized_return - 0.21).abs() < 1e-12);
+        assert!((m.max_drawdown - 0.1).abs() < 1e-12);
+        assert!(m.sharpe > 0.0);
+    }
+}
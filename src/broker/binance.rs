@@ -0,0 +1,693 @@
+use std::collections::HashMap;
+
+use chrono::DateTime;
+use futures::StreamExt;
+use hmac::{Hmac, Mac};
+use reqwest::{Client, Method};
+use serde_json::Value;
+use sha2::Sha256;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::broker::{AccountSnapshot, Broker, BrokerOrder, BrokerResult, OrderId, OrderRequest, OrderStatus, OrderType};
+use crate::data::bar::Bar;
+use crate::data::feed::Symbol;
+use crate::data::order::OrderSide;
+use crate::portfolio::{Fill, FillKind};
+
+#[derive(Debug, Clone)]
+pub struct BinanceConfig {
+    pub api_key: String,
+    pub secret_key: String,
+    /// REST root, e.g. `https://api.binance.com` or a local mock.
+    pub base_url: String,
+    /// Stream root the listen key is appended to.
+    pub ws_url: String,
+    pub recv_window: u64,
+    /// Balance reported as `AccountSnapshot::cash`.
+    pub quote_asset: String,
+}
+
+impl BinanceConfig {
+    pub fn new(api_key: &str, secret_key: &str) -> Self {
+        Self {
+            api_key: api_key.to_string(),
+            secret_key: secret_key.to_string(),
+            base_url: "https://api.binance.com".to_string(),
+            ws_url: "wss://stream.binance.com:9443/ws".to_string(),
+            recv_window: 5_000,
+            quote_asset: "USDT".to_string(),
+        }
+    }
+
+    pub fn testnet(api_key: &str, secret_key: &str) -> Self {
+        Self::new(api_key, secret_key)
+            .with_base_url("https://testnet.binance.vision")
+            .with_ws_url("wss://stream.testnet.binance.vision/ws")
+    }
+
+    /// Reads `BINANCE_API_KEY` and `BINANCE_SECRET_KEY`, plus optional `BINANCE_BASE_URL`
+    /// and `BINANCE_WS_URL` overrides.
+    pub fn from_env() -> BrokerResult<Self> {
+        let key = std::env::var("BINANCE_API_KEY").map_err(|_| "BINANCE_API_KEY is not set")?;
+        let secret = std::env::var("BINANCE_SECRET_KEY").map_err(|_| "BINANCE_SECRET_KEY is not set")?;
+        let mut config = Self::new(&key, &secret);
+        if let Ok(url) = std::env::var("BINANCE_BASE_URL") {
+            config.base_url = url;
+        }
+        if let Ok(url) = std::env::var("BINANCE_WS_URL") {
+            config.ws_url = url;
+        }
+        Ok(config)
+    }
+
+    pub fn with_base_url(mut self, url: &str) -> Self {
+        self.base_url = url.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn with_ws_url(mut self, url: &str) -> Self {
+        self.ws_url = url.trim_end_matches('/').to_string();
+        self
+    }
+}
+
+/// An error body (`{"code": ..., "msg": ...}`) returned by the API.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("Binance API error {code} (HTTP {status}): {msg}")]
+pub struct BinanceError {
+    pub status: u16,
+    pub code: i64,
+    pub msg: String,
+}
+
+/// Trading rules for one symbol from `exchangeInfo`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolInfo {
+    pub symbol: Symbol,
+    pub status: String,
+    pub base_asset: String,
+    pub quote_asset: String,
+    pub tick_size: f64,
+    pub step_size: f64,
+    pub min_qty: f64,
+    pub min_notional: f64,
+}
+
+#[derive(Clone, Copy)]
+enum Auth {
+    None,
+    ApiKey,
+    Signed,
+}
+
+/// A trade from the user data stream. `commission_asset` is set when the commission was
+/// paid in an asset other than the symbol's own (e.g. BNB); `fill.fee` is then in that asset.
+struct StreamFill {
+    fill: Fill,
+    commission_asset: Option<String>,
+}
+
+/// Live spot broker over the Binance REST API. Fills arrive through the user data
+/// stream once `start_user_stream` has been called, and are handed out by `on_bar`.
+pub struct BinanceBroker {
+    config: BinanceConfig,
+    client: Client,
+    symbols: HashMap<Symbol, SymbolInfo>,
+    order_symbols: HashMap<OrderId, Symbol>,
+    fills: Option<mpsc::UnboundedReceiver<StreamFill>>,
+    last_prices: HashMap<Symbol, f64>,
+    /// Orders refused before reaching the exchange, under ids counting down from
+    /// `u64::MAX` so they never clash with exchange ids.
+    local_rejects: HashMap<OrderId, BrokerOrder>,
+}
+
+impl BinanceBroker {
+    pub fn new(config: BinanceConfig) -> Self {
+        Self {
+            config,
+            client: Client::new(),
+            symbols: HashMap::new(),
+            order_symbols: HashMap::new(),
+            fills: None,
+            last_prices: HashMap::new(),
+            local_rejects: HashMap::new(),
+        }
+    }
+
+    /// Fetches and caches trading rules; an empty slice fetches every symbol.
+    pub async fn exchange_info(&mut self, symbols: &[&str]) -> BrokerResult<Vec<SymbolInfo>> {
+        let mut params = Vec::new();
+        if !symbols.is_empty() {
+            params.push(("symbols", serde_json::to_string(symbols)?));
+        }
+        let body = request(&self.client, &self.config, Method::GET, "/api/v3/exchangeInfo", params, Auth::None).await?;
+        let infos: Vec<SymbolInfo> = body["symbols"].as_array().into_iter().flatten().map(parse_symbol_info).collect();
+        for info in &infos {
+            self.symbols.insert(info.symbol.clone(), info.clone());
+        }
+        Ok(infos)
+    }
+
+    async fn symbol_info(&mut self, symbol: &str) -> BrokerResult<SymbolInfo> {
+        if !self.symbols.contains_key(symbol) {
+            self.exchange_info(&[symbol]).await?;
+        }
+        self.symbols.get(symbol).cloned().ok_or_else(|| format!("Unknown symbol {}", symbol).into())
+    }
+
+    /// Opens a listen key and streams `executionReport` trades into this broker,
+    /// renewing the key every 30 minutes. The task ends when the socket closes.
+    pub async fn start_user_stream(&mut self) -> BrokerResult<JoinHandle<()>> {
+        let body = request(&self.client, &self.config, Method::POST, "/api/v3/userDataStream", Vec::new(), Auth::ApiKey).await?;
+        let listen_key = body["listenKey"].as_str().ok_or("Missing listenKey in response")?.to_string();
+        let (mut ws, _) = connect_async(format!("{}/{}", self.config.ws_url, listen_key)).await?;
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.fills = Some(rx);
+        let (client, config) = (self.client.clone(), self.config.clone());
+        Ok(tokio::spawn(async move {
+            let period = std::time::Duration::from_secs(30 * 60);
+            let mut keepalive = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+            loop {
+                tokio::select! {
+                    msg = ws.next() => match msg {
+                        Some(Ok(Message::Text(text))) => {
+                            if let Some(fill) = parse_execution_report(&text)
+                                && tx.send(fill).is_err()
+                            {
+                                break;
+                            }
+                        }
+                        Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                        Some(Ok(_)) => {}
+                    },
+                    _ = keepalive.tick() => {
+                        let params = vec![("listenKey", listen_key.clone())];
+                        if let Err(e) = request(&client, &config, Method::PUT, "/api/v3/userDataStream", params, Auth::ApiKey).await {
+                            eprintln!("⚠️ Failed to renew listen key: {}", e);
+                        }
+                    }
+                }
+            }
+            eprintln!("⚠️ User data stream closed");
+        }))
+    }
+
+    fn parse_order(&mut self, body: &Value) -> BrokerResult<BrokerOrder> {
+        let id = body["orderId"].as_u64().ok_or("Missing orderId in response")?;
+        let symbol = body["symbol"].as_str().unwrap_or_default().to_string();
+        let side = match body["side"].as_str() {
+            Some("SELL") => OrderSide::Sell,
+            _ => OrderSide::Buy,
+        };
+        let order_type = match body["type"].as_str() {
+            Some("MARKET") => OrderType::Market,
+            _ => OrderType::Limit(num(&body["price"])),
+        };
+        let status = match body["status"].as_str() {
+            Some("NEW") | Some("PENDING_NEW") => OrderStatus::New,
+            Some("PARTIALLY_FILLED") => OrderStatus::PartiallyFilled,
+            Some("FILLED") => OrderStatus::Filled,
+            Some("REJECTED") => OrderStatus::Rejected,
+            _ => OrderStatus::Canceled,
+        };
+        let filled = num(&body["executedQty"]);
+        let quote = num(&body["cummulativeQuoteQty"]);
+        self.order_symbols.insert(id, symbol.clone());
+        Ok(BrokerOrder {
+            id,
+            request: OrderRequest { symbol, side, order_type, quantity: num(&body["origQty"]) },
+            status,
+            filled_quantity: filled,
+            avg_fill_price: if filled > 0.0 { quote / filled } else { 0.0 },
+        })
+    }
+
+    /// Records `request` as rejected without sending it and returns it under a fresh id.
+    fn reject_locally(&mut self, request: OrderRequest) -> BrokerOrder {
+        let id = u64::MAX - self.local_rejects.len() as u64;
+        let order = BrokerOrder { id, request, status: OrderStatus::Rejected, filled_quantity: 0.0, avg_fill_price: 0.0 };
+        self.local_rejects.insert(id, order.clone());
+        order
+    }
+
+    /// Price of one unit of `asset` in the quote asset: the last bar close of
+    /// `asset + quote` if one was seen, else the exchange's ticker.
+    async fn quote_price(&mut self, asset: &str) -> BrokerResult<f64> {
+        if asset == self.config.quote_asset {
+            return Ok(1.0);
+        }
+        let symbol = format!("{}{}", asset, self.config.quote_asset);
+        if let Some(price) = self.last_prices.get(&symbol) {
+            return Ok(*price);
+        }
+        let params = vec![("symbol", symbol)];
+        let body = request(&self.client, &self.config, Method::GET, "/api/v3/ticker/price", params, Auth::None).await?;
+        Ok(num(&body["price"]))
+    }
+
+    fn symbol_of(&self, id: OrderId) -> BrokerResult<Symbol> {
+        self.order_symbols.get(&id).cloned().ok_or_else(|| format!("Unknown order id {}", id).into())
+    }
+}
+
+impl Broker for BinanceBroker {
+    /// Quantity and price are rounded down to the symbol's step and tick size. Orders
+    /// below the symbol's minimum quantity or notional (market orders are valued at the
+    /// last close) are rejected without being sent. Those and orders the exchange refuses
+    /// (HTTP 400, e.g. insufficient balance) come back as `Rejected` under an id of their
+    /// own; other failures are errors.
+    async fn place_order(&mut self, request: OrderRequest) -> BrokerResult<BrokerOrder> {
+        let info = self.symbol_info(&request.symbol).await?;
+        let quantity = round_to_step(request.quantity, info.step_size);
+        let rounded: f64 = quantity.parse()?;
+        let reference = match request.order_type {
+            OrderType::Market => self.last_prices.get(&request.symbol).copied(),
+            OrderType::Limit(price) => Some(price),
+        };
+        if rounded <= 0.0 || rounded < info.min_qty || reference.is_some_and(|p| rounded * p < info.min_notional) {
+            eprintln!(
+                "⚠️ Order rejected: {} {} is below the minimum quantity {} or notional {}",
+                rounded, request.symbol, info.min_qty, info.min_notional
+            );
+            return Ok(self.reject_locally(request));
+        }
+        let mut params = vec![
+            ("symbol", request.symbol.clone()),
+            ("side", if request.side == OrderSide::Buy { "BUY" } else { "SELL" }.to_string()),
+        ];
+        match request.order_type {
+            OrderType::Market => params.push(("type", "MARKET".to_string())),
+            OrderType::Limit(price) => {
+                params.push(("type", "LIMIT".to_string()));
+                params.push(("timeInForce", "GTC".to_string()));
+                params.push(("price", round_to_step(price, info.tick_size)));
+            }
+        }
+        params.push(("quantity", quantity));
+        params.push(("newOrderRespType", "RESULT".to_string()));
+
+        match request_signed(self, Method::POST, "/api/v3/order", params).await {
+            Ok(body) => self.parse_order(&body),
+            Err(e) => match e.downcast_ref::<BinanceError>() {
+                Some(api) if api.status == 400 => {
+                    eprintln!("⚠️ Order rejected: {}", api);
+                    Ok(self.reject_locally(request))
+                }
+                _ => Err(e),
+            },
+        }
+    }
+
+    async fn cancel_order(&mut self, id: OrderId) -> BrokerResult<BrokerOrder> {
+        if self.local_rejects.contains_key(&id) {
+            return Err(format!("Order {} is already Rejected", id).into());
+        }
+        let params = vec![("symbol", self.symbol_of(id)?), ("orderId", id.to_string())];
+        let body = request_signed(self, Method::DELETE, "/api/v3/order", params).await?;
+        self.parse_order(&body)
+    }
+
+    async fn order_status(&mut self, id: OrderId) -> BrokerResult<BrokerOrder> {
+        if let Some(order) = self.local_rejects.get(&id) {
+            return Ok(order.clone());
+        }
+        let params = vec![("symbol", self.symbol_of(id)?), ("orderId", id.to_string())];
+        let body = request_signed(self, Method::GET, "/api/v3/order", params).await?;
+        self.parse_order(&body)
+    }
+
+    async fn open_orders(&mut self) -> BrokerResult<Vec<BrokerOrder>> {
+        let body = request_signed(self, Method::GET, "/api/v3/openOrders", Vec::new()).await?;
+        body.as_array().into_iter().flatten().map(|o| self.parse_order(o)).collect()
+    }
+
+    /// Cash is the quote asset balance (free + locked). Positions cover the base assets
+    /// of symbols seen via `exchange_info`; equity marks them at the last bar close.
+    async fn account(&mut self) -> BrokerResult<AccountSnapshot> {
+        let body = request_signed(self, Method::GET, "/api/v3/account", Vec::new()).await?;
+        let balances: HashMap<String, f64> = body["balances"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|b| (b["asset"].as_str().unwrap_or_default().to_string(), num(&b["free"]) + num(&b["locked"])))
+            .collect();
+        let cash = balances.get(&self.config.quote_asset).copied().unwrap_or(0.0);
+        let positions: HashMap<Symbol, f64> = self
+            .symbols
+            .values()
+            .filter(|info| info.quote_asset == self.config.quote_asset)
+            .filter_map(|info| balances.get(&info.base_asset).filter(|q| **q > 0.0).map(|q| (info.symbol.clone(), *q)))
+            .collect();
+        let equity = cash
+            + positions
+                .iter()
+                .map(|(s, q)| q * self.last_prices.get(s).copied().unwrap_or(0.0))
+                .sum::<f64>();
+        Ok(AccountSnapshot { cash, positions, equity })
+    }
+
+    /// Commissions paid in other assets are converted to the quote asset here.
+    async fn on_bar(&mut self, symbol: &str, bar: &Bar) -> BrokerResult<Vec<Fill>> {
+        self.last_prices.insert(symbol.to_string(), bar.close);
+        let mut received = Vec::new();
+        if let Some(rx) = self.fills.as_mut() {
+            while let Ok(trade) = rx.try_recv() {
+                received.push(trade);
+            }
+        }
+        let mut fills = Vec::with_capacity(received.len());
+        for StreamFill { mut fill, commission_asset } in received {
+            if let Some(asset) = commission_asset {
+                fill.fee *= self.quote_price(&asset).await?;
+            }
+            fills.push(fill);
+        }
+        Ok(fills)
+    }
+}
+
+async fn request_signed(broker: &BinanceBroker, method: Method, path: &str, params: Vec<(&str, String)>) -> BrokerResult<Value> {
+    request(&broker.client, &broker.config, method, path, params, Auth::Signed).await
+}
+
+async fn request(
+    client: &Client,
+    config: &BinanceConfig,
+    method: Method,
+    path: &str,
+    mut params: Vec<(&str, String)>,
+    auth: Auth,
+) -> BrokerResult<Value> {
+    if let Auth::Signed = auth {
+        params.push(("recvWindow", config.recv_window.to_string()));
+        params.push(("timestamp", chrono::Utc::now().timestamp_millis().to_string()));
+    }
+    let mut query = params.iter().map(|(k, v)| format!("{}={}", k, encode(v))).collect::<Vec<_>>().join("&");
+    if let Auth::Signed = auth {
+        let signature = sign(&config.secret_key, &query);
+        query = if query.is_empty() { format!("signature={}", signature) } else { format!("{}&signature={}", query, signature) };
+    }
+    let url = if query.is_empty() { format!("{}{}", config.base_url, path) } else { format!("{}{}?{}", config.base_url, path, query) };
+
+    let mut req = client.request(method, url);
+    if !matches!(auth, Auth::None) {
+        req = req.header("X-MBX-APIKEY", &config.api_key);
+    }
+    let resp = req.send().await?;
+    let status = resp.status();
+    let text = resp.text().await?;
+    if status.is_success() {
+        return Ok(serde_json::from_str(&text)?);
+    }
+    match serde_json::from_str::<Value>(&text) {
+        Ok(body) if body["code"].is_i64() => Err(Box::new(BinanceError {
+            status: status.as_u16(),
+            code: body["code"].as_i64().unwrap_or_default(),
+            msg: body["msg"].as_str().unwrap_or_default().to_string(),
+        })),
+        _ => Err(format!("HTTP {} from {}: {}", status, path, text).into()),
+    }
+}
+
+/// Hex HMAC-SHA256 of the query string, as Binance's `SIGNED` endpoints expect.
+pub fn sign(secret: &str, query: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(query.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Rounds down to a multiple of `step` and prints with the step's decimals.
+fn round_to_step(value: f64, step: f64) -> String {
+    if step <= 0.0 {
+        return value.to_string();
+    }
+    let decimals = (-step.log10()).round().max(0.0) as usize;
+    format!("{:.*}", decimals, (value / step + 1e-9).floor() * step)
+}
+
+/// Binance sends most numbers as strings.
+fn num(v: &Value) -> f64 {
+    match v {
+        Value::String(s) => s.parse().unwrap_or(0.0),
+        other => other.as_f64().unwrap_or(0.0),
+    }
+}
+
+fn parse_symbol_info(s: &Value) -> SymbolInfo {
+    let mut info = SymbolInfo {
+        symbol: s["symbol"].as_str().unwrap_or_default().to_string(),
+        status: s["status"].as_str().unwrap_or_default().to_string(),
+        base_asset: s["baseAsset"].as_str().unwrap_or_default().to_string(),
+        quote_asset: s["quoteAsset"].as_str().unwrap_or_default().to_string(),
+        tick_size: 0.0,
+        step_size: 0.0,
+        min_qty: 0.0,
+        min_notional: 0.0,
+    };
+    for f in s["filters"].as_array().into_iter().flatten() {
+        match f["filterType"].as_str() {
+            Some("PRICE_FILTER") => info.tick_size = num(&f["tickSize"]),
+            Some("LOT_SIZE") => {
+                info.step_size = num(&f["stepSize"]);
+                info.min_qty = num(&f["minQty"]);
+            }
+            Some("NOTIONAL") | Some("MIN_NOTIONAL") => info.min_notional = num(&f["minNotional"]),
+            _ => {}
+        }
+    }
+    info
+}
+
+/// A trade from an `executionReport` event. Commission paid in the base asset is
+/// converted to quote at the fill price; other assets (e.g. BNB) are left for `on_bar`.
+fn parse_execution_report(text: &str) -> Option<StreamFill> {
+    let mut v: Value = serde_json::from_str(text).ok()?;
+    if let Some(event) = v.get_mut("event") {
+        v = event.take();
+    }
+    if v["e"] != "executionReport" || v["x"] != "TRADE" {
+        return None;
+    }
+    let symbol = v["s"].as_str()?.to_string();
+    let price = num(&v["L"]);
+    let commission = num(&v["n"]);
+    let (fee, commission_asset) = match v["N"].as_str() {
+        Some(asset) if symbol.starts_with(asset) => (commission * price, None),
+        Some(asset) if !symbol.ends_with(asset) => (commission, Some(asset.to_string())),
+        _ => (commission, None),
+    };
+    let fill = Fill {
+        timestamp: DateTime::from_timestamp_millis(v["T"].as_i64()?)?.to_rfc3339(),
+        side: if v["S"] == "SELL" { OrderSide::Sell } else { OrderSide::Buy },
+        quantity: num(&v["l"]),
+        price,
+        fee,
+        kind: FillKind::Trade,
+        symbol,
+    };
+    Some(StreamFill { fill, commission_asset })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{header, method, path, query_param};
+    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+    fn signed_correctly(req: &Request) -> bool {
+        let query = req.url.query().unwrap_or_default();
+        match query.rsplit_once("&signature=") {
+            Some((payload, signature)) => sign("secret", payload) == signature,
+            None => false,
+        }
+    }
+
+    async fn mock_exchange() -> (MockServer, BinanceBroker) {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v3/exchangeInfo"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"symbols": [{
+                "symbol": "BTCUSDT", "status": "TRADING", "baseAsset": "BTC", "quoteAsset": "USDT",
+                "filters": [
+                    {"filterType": "PRICE_FILTER", "tickSize": "0.01000000"},
+                    {"filterType": "LOT_SIZE", "stepSize": "0.00001000", "minQty": "0.00001000"},
+                    {"filterType": "NOTIONAL", "minNotional": "5.00000000"}
+                ]
+            }]})))
+            .mount(&server)
+            .await;
+        let broker = BinanceBroker::new(BinanceConfig::new("key", "secret").with_base_url(&server.uri()));
+        (server, broker)
+    }
+
+    #[test]
+    fn test_signature_matches_api_docs() {
+        let secret = "NhqPtmdSJYdKjVHjA7PZj4Mge3R5YNiP1e3UZjInClVN65XAbvqqM6A7H5fATj0j";
+        let query = "symbol=LTCBTC&side=BUY&type=LIMIT&timeInForce=GTC&quantity=1&price=0.1&recvWindow=5000&timestamp=1499827319559";
+        assert_eq!(sign(secret, query), "c8db56825ae71d6d79447849e617115f4a920fa2acdcab2b053c4b2838bd6b71");
+        assert_eq!(round_to_step(0.123456789, 0.00001), "0.12345");
+    }
+
+    #[tokio::test]
+    async fn test_order_lifecycle_against_mock() {
+        let (server, mut broker) = mock_exchange().await;
+        Mock::given(method("POST"))
+            .and(path("/api/v3/order"))
+            .and(header("X-MBX-APIKEY", "key"))
+            .and(query_param("quantity", "0.12345"))
+            .and(query_param("price", "43210.12"))
+            .and(signed_correctly)
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "symbol": "BTCUSDT", "orderId": 28, "price": "43210.12", "origQty": "0.12345",
+                "executedQty": "0.00000", "cummulativeQuoteQty": "0.00", "status": "NEW",
+                "type": "LIMIT", "side": "BUY"
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/api/v3/order"))
+            .and(query_param("orderId", "28"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "symbol": "BTCUSDT", "orderId": 28, "price": "43210.12", "origQty": "0.12345",
+                "executedQty": "0.01000", "cummulativeQuoteQty": "432.10", "status": "CANCELED",
+                "type": "LIMIT", "side": "BUY"
+            })))
+            .mount(&server)
+            .await;
+
+        let order = broker
+            .place_order(OrderRequest::limit("BTCUSDT", OrderSide::Buy, 0.123456789, 43210.129))
+            .await
+            .unwrap();
+        assert_eq!((order.id, order.status), (28, OrderStatus::New));
+        let canceled = broker.cancel_order(28).await.unwrap();
+        assert_eq!(canceled.status, OrderStatus::Canceled);
+        assert!((canceled.avg_fill_price - 43210.0).abs() < 1e-6);
+        assert!(broker.cancel_order(99).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_api_errors_are_mapped() {
+        let (server, mut broker) = mock_exchange().await;
+        Mock::given(method("POST"))
+            .and(path("/api/v3/order"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(json!({"code": -2010, "msg": "Account has insufficient balance for requested action."})))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/api/v3/openOrders"))
+            .respond_with(ResponseTemplate::new(401).set_body_json(json!({"code": -2015, "msg": "Invalid API-key, IP, or permissions for action."})))
+            .mount(&server)
+            .await;
+
+        let order = broker.place_order(OrderRequest::market("BTCUSDT", OrderSide::Buy, 1.0)).await.unwrap();
+        assert_eq!(order.status, OrderStatus::Rejected);
+        let again = broker.place_order(OrderRequest::market("BTCUSDT", OrderSide::Buy, 1.0)).await.unwrap();
+        assert_ne!(order.id, again.id);
+        assert_eq!(broker.order_status(order.id).await.unwrap().status, OrderStatus::Rejected);
+        let err = broker.open_orders().await.unwrap_err();
+        let api = err.downcast_ref::<BinanceError>().unwrap();
+        assert_eq!((api.status, api.code), (401, -2015));
+    }
+
+    #[tokio::test]
+    async fn test_account_and_user_stream_fills() {
+        let (server, mut broker) = mock_exchange().await;
+        broker.exchange_info(&["BTCUSDT"]).await.unwrap();
+        Mock::given(method("GET"))
+            .and(path("/api/v3/account"))
+            .and(signed_correctly)
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"balances": [
+                {"asset": "USDT", "free": "900.0", "locked": "100.0"},
+                {"asset": "BTC", "free": "0.5", "locked": "0.0"}
+            ]})))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/v3/userDataStream"))
+            .and(header("X-MBX-APIKEY", "key"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"listenKey": "lk"})))
+            .mount(&server)
+            .await;
+
+        // Websocket stand-in that pushes one execution report.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let ws_url = format!("ws://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            let report = json!({
+                "e": "executionReport", "s": "BTCUSDT", "S": "BUY", "x": "TRADE", "i": 28,
+                "l": "0.5", "L": "40000.00", "n": "0.0005", "N": "BTC", "T": 1_700_000_000_000i64
+            });
+            futures::SinkExt::send(&mut ws, Message::Text(report.to_string())).await.unwrap();
+            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        });
+
+        broker.config.ws_url = ws_url;
+        broker.start_user_stream().await.unwrap();
+        let bar = Bar { timestamp: "t".into(), open: 1.0, high: 1.0, low: 1.0, close: 41_000.0, volume: 1.0 };
+        let mut fills = Vec::new();
+        for _ in 0..50 {
+            fills = broker.on_bar("BTCUSDT", &bar).await.unwrap();
+            if !fills.is_empty() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert_eq!(fills.len(), 1);
+        assert_eq!((fills[0].quantity, fills[0].price, fills[0].fee), (0.5, 40_000.0, 20.0));
+
+        let account = broker.account().await.unwrap();
+        assert_eq!(account.cash, 1_000.0);
+        assert_eq!(account.positions["BTCUSDT"], 0.5);
+        assert_eq!(account.equity, 21_500.0);
+    }
+
+    #[tokio::test]
+    async fn test_filters_enforced_before_sending() {
+        // No order endpoint is mounted, so anything sent would fail with a 404.
+        let (_server, mut broker) = mock_exchange().await;
+        let small = broker.place_order(OrderRequest::limit("BTCUSDT", OrderSide::Buy, 0.0001, 40_000.0)).await.unwrap();
+        let dust = broker.place_order(OrderRequest::market("BTCUSDT", OrderSide::Sell, 0.000001)).await.unwrap();
+        assert_eq!((small.status, dust.status), (OrderStatus::Rejected, OrderStatus::Rejected));
+        assert_ne!(small.id, dust.id);
+        assert!(broker.cancel_order(small.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bnb_commission_converted_to_quote() {
+        let (server, mut broker) = mock_exchange().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v3/ticker/price"))
+            .and(query_param("symbol", "BNBUSDT"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({"symbol": "BNBUSDT", "price": "600.00"})))
+            .mount(&server)
+            .await;
+        let report = json!({
+            "e": "executionReport", "s": "BTCUSDT", "S": "BUY", "x": "TRADE", "i": 28,
+            "l": "0.5", "L": "40000.00", "n": "0.01", "N": "BNB", "T": 1_700_000_000_000i64
+        });
+        let (tx, rx) = mpsc::unbounded_channel();
+        broker.fills = Some(rx);
+        tx.send(parse_execution_report(&report.to_string()).unwrap()).unwrap();
+        let bar = Bar { timestamp: "t".into(), open: 1.0, high: 1.0, low: 1.0, close: 41_000.0, volume: 1.0 };
+        let fills = broker.on_bar("BTCUSDT", &bar).await.unwrap();
+        assert!((fills[0].fee - 6.0).abs() < 1e-9);
+    }
+}
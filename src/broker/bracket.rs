@@ -0,0 +1,346 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::broker::OrderId;
+use crate::data::bar::Bar;
+use crate::data::feed::Symbol;
+use crate::data::order::OrderSide;
+use crate::strategy::indicators::Atr;
+
+pub type BracketId = u64;
+
+/// Where a stop-loss or take-profit sits relative to the entry.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExitLevel {
+    Price(f64),
+    /// Fraction of the entry price (0.02 = 2% away).
+    Percent(f64),
+    /// Multiple of the symbol's ATR at entry.
+    Atr(f64),
+}
+
+/// Trailing stop distance from the best price since entry.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Trail {
+    Percent(f64),
+    /// Multiple of the current ATR.
+    Atr(f64),
+}
+
+/// Protective exits attached to an entry.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BracketSpec {
+    pub stop_loss: Option<ExitLevel>,
+    pub take_profit: Option<ExitLevel>,
+    pub trailing: Option<Trail>,
+}
+
+impl BracketSpec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stop_loss(mut self, level: ExitLevel) -> Self {
+        self.stop_loss = Some(level);
+        self
+    }
+
+    pub fn take_profit(mut self, level: ExitLevel) -> Self {
+        self.take_profit = Some(level);
+        self
+    }
+
+    pub fn trailing(mut self, trail: Trail) -> Self {
+        self.trailing = Some(trail);
+        self
+    }
+}
+
+/// Which exit wins when a bar's range touches both the stop and the target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IntrabarSequence {
+    /// Assume the worst: the stop fills.
+    #[default]
+    StopFirst,
+    TargetFirst,
+    /// Assume the bar visited whichever extreme is nearer its open first
+    /// (O→H→L→C or O→L→H→C).
+    OpenProximity,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitReason {
+    StopLoss,
+    TakeProfit,
+    TrailingStop,
+}
+
+/// A live bracket protecting one position. Stop and target form an OCO pair:
+/// whichever fills first retires the other.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bracket {
+    pub id: BracketId,
+    /// Entry order this bracket belongs to; `None` for stand-alone OCO pairs.
+    pub parent: Option<OrderId>,
+    pub symbol: Symbol,
+    /// Direction of the protected position (`Buy` = long).
+    pub side: OrderSide,
+    pub quantity: f64,
+    pub entry_price: f64,
+    pub stop: Option<f64>,
+    pub target: Option<f64>,
+    pub trailing: Option<Trail>,
+    /// Best price seen since entry (highest for longs, lowest for shorts).
+    pub extreme: f64,
+}
+
+impl Bracket {
+    fn trail_level(&self, atr: Option<f64>) -> Option<f64> {
+        let distance = match self.trailing? {
+            Trail::Percent(p) => self.extreme * p,
+            Trail::Atr(k) => k * atr?,
+        };
+        Some(match self.side {
+            OrderSide::Buy => self.extreme - distance,
+            OrderSide::Sell => self.extreme + distance,
+        })
+    }
+
+    /// Tighter of the fixed and trailing stops, and which one it is.
+    fn effective_stop(&self, atr: Option<f64>) -> Option<(f64, ExitReason)> {
+        let fixed = self.stop.map(|s| (s, ExitReason::StopLoss));
+        let trail = self.trail_level(atr).map(|s| (s, ExitReason::TrailingStop));
+        match (fixed, trail, self.side) {
+            (Some(f), Some(t), OrderSide::Buy) => Some(if t.0 > f.0 { t } else { f }),
+            (Some(f), Some(t), OrderSide::Sell) => Some(if t.0 < f.0 { t } else { f }),
+            (f, t, _) => f.or(t),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BracketExit {
+    pub bracket: BracketId,
+    pub parent: Option<OrderId>,
+    pub symbol: Symbol,
+    /// Side of the closing order.
+    pub side: OrderSide,
+    pub quantity: f64,
+    pub price: f64,
+    pub reason: ExitReason,
+    pub timestamp: String,
+}
+
+/// Engine-side stops, targets and trailing stops, checked against each bar.
+#[derive(Debug, Clone)]
+pub struct BracketManager {
+    pub sequence: IntrabarSequence,
+    atr_period: usize,
+    atr: HashMap<Symbol, Atr>,
+    brackets: BTreeMap<BracketId, Bracket>,
+    next_id: BracketId,
+}
+
+impl Default for BracketManager {
+    fn default() -> Self {
+        Self::new(IntrabarSequence::default(), 14)
+    }
+}
+
+impl BracketManager {
+    pub fn new(sequence: IntrabarSequence, atr_period: usize) -> Self {
+        Self {
+            sequence,
+            atr_period,
+            atr: HashMap::new(),
+            brackets: BTreeMap::new(),
+            next_id: 1,
+        }
+    }
+
+    pub fn atr(&self, symbol: &str) -> Option<f64> {
+        self.atr.get(symbol).and_then(Atr::value)
+    }
+
+    pub fn brackets(&self) -> impl Iterator<Item = &Bracket> {
+        self.brackets.values()
+    }
+
+    pub fn get(&self, id: BracketId) -> Option<&Bracket> {
+        self.brackets.get(&id)
+    }
+
+    /// Protects a position of `quantity` in `side`'s direction entered at `entry_price`.
+    /// ATR-based levels need a warmed-up ATR for the symbol and are skipped otherwise.
+    pub fn open(
+        &mut self,
+        parent: Option<OrderId>,
+        symbol: &str,
+        side: OrderSide,
+        quantity: f64,
+        entry_price: f64,
+        spec: &BracketSpec,
+    ) -> BracketId {
+        let atr = self.atr(symbol);
+        let dir = if side == OrderSide::Buy { 1.0 } else { -1.0 };
+        let level = |l: ExitLevel, toward: f64| match l {
+            ExitLevel::Price(p) => Some(p),
+            ExitLevel::Percent(p) => Some(entry_price * (1.0 + toward * p)),
+            ExitLevel::Atr(k) => atr.map(|a| entry_price + toward * k * a),
+        };
+        let id = self.next_id;
+        self.next_id += 1;
+        self.brackets.insert(
+            id,
+            Bracket {
+                id,
+                parent,
+                symbol: symbol.to_string(),
+                side,
+                quantity,
+                entry_price,
+                stop: spec.stop_loss.and_then(|l| level(l, -dir)),
+                target: spec.take_profit.and_then(|l| level(l, dir)),
+                trailing: spec.trailing,
+                extreme: entry_price,
+            },
+        );
+        id
+    }
+
+    /// Stand-alone one-cancels-other pair closing an existing position.
+    pub fn oco(&mut self, symbol: &str, side: OrderSide, quantity: f64, stop: f64, target: f64) -> BracketId {
+        let spec = BracketSpec::new().stop_loss(ExitLevel::Price(stop)).take_profit(ExitLevel::Price(target));
+        self.open(None, symbol, side, quantity, (stop + target) / 2.0, &spec)
+    }
+
+    pub fn cancel(&mut self, id: BracketId) -> Option<Bracket> {
+        self.brackets.remove(&id)
+    }
+
+    /// Drops every bracket on `symbol`, e.g. after the position was closed by hand.
+    pub fn cancel_symbol(&mut self, symbol: &str) {
+        self.brackets.retain(|_, b| b.symbol != symbol);
+    }
+
+    /// Checks `symbol`'s brackets against `bar`, retiring those that exit. Gaps through a
+    /// level fill at the open. Afterwards trailing extremes and the ATR take in the bar.
+    pub fn on_bar(&mut self, symbol: &str, bar: &Bar) -> Vec<BracketExit> {
+        let atr = self.atr(symbol);
+        let mut exits = Vec::new();
+        for bracket in self.brackets.values().filter(|b| b.symbol == symbol) {
+            if let Some((price, reason)) = self.trigger(bracket, bar, atr) {
+                exits.push(BracketExit {
+                    bracket: bracket.id,
+                    parent: bracket.parent,
+                    symbol: symbol.to_string(),
+                    side: if bracket.side == OrderSide::Buy { OrderSide::Sell } else { OrderSide::Buy },
+                    quantity: bracket.quantity,
+                    price,
+                    reason,
+                    timestamp: bar.timestamp.clone(),
+                });
+            }
+        }
+        for exit in &exits {
+            self.brackets.remove(&exit.bracket);
+        }
+        for bracket in self.brackets.values_mut().filter(|b| b.symbol == symbol) {
+            bracket.extreme = match bracket.side {
+                OrderSide::Buy => bracket.extreme.max(bar.high),
+                OrderSide::Sell => bracket.extreme.min(bar.low),
+            };
+        }
+        let period = self.atr_period;
+        self.atr.entry(symbol.to_string()).or_insert_with(|| Atr::new(period)).update(bar);
+        exits
+    }
+
+    fn trigger(&self, b: &Bracket, bar: &Bar, atr: Option<f64>) -> Option<(f64, ExitReason)> {
+        let long = b.side == OrderSide::Buy;
+        let stop = b.effective_stop(atr);
+        // Gaps: the open is already through a level.
+        if let Some((s, reason)) = stop
+            && (if long { bar.open <= s } else { bar.open >= s })
+        {
+            return Some((bar.open, reason));
+        }
+        if let Some(t) = b.target
+            && (if long { bar.open >= t } else { bar.open <= t })
+        {
+            return Some((bar.open, ExitReason::TakeProfit));
+        }
+
+        let stop_hit = stop.filter(|(s, _)| if long { bar.low <= *s } else { bar.high >= *s });
+        let target_hit = b.target.filter(|t| if long { bar.high >= *t } else { bar.low <= *t });
+        match (stop_hit, target_hit) {
+            (Some(s), None) => Some(s),
+            (None, Some(t)) => Some((t, ExitReason::TakeProfit)),
+            (Some(s), Some(t)) => {
+                let target_first = match self.sequence {
+                    IntrabarSequence::StopFirst => false,
+                    IntrabarSequence::TargetFirst => true,
+                    IntrabarSequence::OpenProximity => {
+                        let high_first = (bar.high - bar.open) <= (bar.open - bar.low);
+                        high_first == long
+                    }
+                };
+                Some(if target_first { (t, ExitReason::TakeProfit) } else { s })
+            }
+            (None, None) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bar(open: f64, high: f64, low: f64, close: f64) -> Bar {
+        Bar { timestamp: "t".into(), open, high, low, close, volume: 1.0 }
+    }
+
+    #[test]
+    fn test_stop_and_target_with_sequencing() {
+        let spec = BracketSpec::new().stop_loss(ExitLevel::Percent(0.05)).take_profit(ExitLevel::Percent(0.10));
+        let mut mgr = BracketManager::default();
+        let id = mgr.open(Some(7), "X", OrderSide::Buy, 2.0, 100.0, &spec);
+        assert_eq!((mgr.get(id).unwrap().stop, mgr.get(id).unwrap().target), (Some(95.0), Some(110.00000000000001)));
+        assert!(mgr.on_bar("X", &bar(100.0, 104.0, 96.0, 101.0)).is_empty());
+
+        // Both levels inside one bar: the stop wins by default.
+        let exits = mgr.on_bar("X", &bar(101.0, 111.0, 94.0, 100.0));
+        assert_eq!((exits[0].reason, exits[0].price, exits[0].side), (ExitReason::StopLoss, 95.0, OrderSide::Sell));
+        assert_eq!(exits[0].parent, Some(7));
+        assert_eq!(mgr.brackets().count(), 0);
+
+        let mut mgr = BracketManager::new(IntrabarSequence::OpenProximity, 14);
+        mgr.open(None, "X", OrderSide::Buy, 1.0, 100.0, &spec);
+        let exits = mgr.on_bar("X", &bar(108.0, 111.0, 94.0, 100.0));
+        assert_eq!(exits[0].reason, ExitReason::TakeProfit);
+
+        // Short with a gap through the stop fills at the open.
+        let mut mgr = BracketManager::default();
+        mgr.oco("X", OrderSide::Sell, 1.0, 105.0, 90.0);
+        let exits = mgr.on_bar("X", &bar(107.0, 108.0, 106.0, 107.0));
+        assert_eq!((exits[0].price, exits[0].side), (107.0, OrderSide::Buy));
+    }
+
+    #[test]
+    fn test_trailing_stops() {
+        let mut mgr = BracketManager::default();
+        mgr.open(None, "X", OrderSide::Buy, 1.0, 100.0, &BracketSpec::new().trailing(Trail::Percent(0.1)));
+        assert!(mgr.on_bar("X", &bar(100.0, 120.0, 99.0, 118.0)).is_empty());
+        let exits = mgr.on_bar("X", &bar(118.0, 119.0, 107.0, 110.0));
+        assert_eq!((exits[0].reason, exits[0].price), (ExitReason::TrailingStop, 108.0));
+
+        let mut mgr = BracketManager::new(IntrabarSequence::StopFirst, 2);
+        mgr.on_bar("X", &bar(100.0, 101.0, 99.0, 100.0));
+        mgr.on_bar("X", &bar(100.0, 101.0, 99.0, 100.0));
+        assert_eq!(mgr.atr("X"), Some(2.0));
+        let spec = BracketSpec::new().stop_loss(ExitLevel::Atr(1.0)).trailing(Trail::Atr(1.5));
+        mgr.open(None, "X", OrderSide::Sell, 1.0, 100.0, &spec);
+        assert_eq!(mgr.brackets().next().unwrap().stop, Some(102.0));
+        let exits = mgr.on_bar("X", &bar(99.0, 102.5, 98.0, 101.0));
+        assert_eq!((exits[0].reason, exits[0].price), (ExitReason::StopLoss, 102.0));
+    }
+}
@@ -0,0 +1,415 @@
+pub mod binance;
+pub mod bracket;
+pub mod paper;
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::future::Future;
+
+use chrono::Utc;
+
+use crate::broker::bracket::BracketSpec;
+use crate::data::bar::Bar;
+use crate::data::depth::BookLevels;
+use crate::data::feed::Symbol;
+use crate::data::order::OrderSide;
+use crate::data::stream::BarReceiver;
+use crate::portfolio::Fill;
+use crate::portfolio::rebalance::{RebalanceConfig, rebalance_orders};
+use crate::risk::sizing::{PositionSizer, SizingContext};
+use crate::risk::{RiskEvent, RiskManager};
+use crate::strategy::Strategy;
+use crate::strategy::indicators::Atr;
+
+pub type OrderId = u64;
+
+pub type BrokerResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OrderType {
+    Market,
+    Limit(f64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderRequest {
+    pub symbol: Symbol,
+    pub side: OrderSide,
+    pub order_type: OrderType,
+    pub quantity: f64,
+}
+
+impl OrderRequest {
+    pub fn market(symbol: &str, side: OrderSide, quantity: f64) -> Self {
+        Self { symbol: symbol.to_string(), side, order_type: OrderType::Market, quantity }
+    }
+
+    pub fn limit(symbol: &str, side: OrderSide, quantity: f64, price: f64) -> Self {
+        Self { symbol: symbol.to_string(), side, order_type: OrderType::Limit(price), quantity }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderStatus {
+    New,
+    PartiallyFilled,
+    Filled,
+    Canceled,
+    Rejected,
+}
+
+impl OrderStatus {
+    pub fn is_open(&self) -> bool {
+        matches!(self, OrderStatus::New | OrderStatus::PartiallyFilled)
+    }
+}
+
+/// The broker's view of an order after placement.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrokerOrder {
+    pub id: OrderId,
+    pub request: OrderRequest,
+    pub status: OrderStatus,
+    pub filled_quantity: f64,
+    pub avg_fill_price: f64,
+}
+
+/// Cash in the account currency, position sizes by symbol, and their marked value.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AccountSnapshot {
+    pub cash: f64,
+    pub positions: HashMap<Symbol, f64>,
+    pub equity: f64,
+}
+
+/// Order routing and account state, implemented by simulated and live venues so the
+/// same strategy loop can run against either.
+pub trait Broker: Send {
+    fn place_order(&mut self, request: OrderRequest) -> impl Future<Output = BrokerResult<BrokerOrder>> + Send;
+
+    fn cancel_order(&mut self, id: OrderId) -> impl Future<Output = BrokerResult<BrokerOrder>> + Send;
+
+    fn order_status(&mut self, id: OrderId) -> impl Future<Output = BrokerResult<BrokerOrder>> + Send;
+
+    fn open_orders(&mut self) -> impl Future<Output = BrokerResult<Vec<BrokerOrder>>> + Send;
+
+    fn account(&mut self) -> impl Future<Output = BrokerResult<AccountSnapshot>> + Send;
+
+    /// Called for each closed bar; returns fills that happened since the last call.
+    /// Simulated brokers match resting orders against the bar here.
+    fn on_bar(&mut self, symbol: &str, bar: &Bar) -> impl Future<Output = BrokerResult<Vec<Fill>>> + Send;
+
+    /// Places an entry whose stop-loss, take-profit and trailing stop go live once it
+    /// fills. Brokers without managed exits refuse it.
+    fn place_bracket(
+        &mut self,
+        request: OrderRequest,
+        spec: BracketSpec,
+    ) -> impl Future<Output = BrokerResult<BrokerOrder>> + Send {
+        let _ = spec;
+        async move { Err(format!("Bracket orders are not supported (entry {:?} {})", request.side, request.symbol).into()) }
+    }
+}
+
+/// Decides whether and at what price an order fills against a bar.
+pub trait FillModel: Send + Sync {
+    /// `(price, is_maker)` if `order` fills in full on `bar`.
+    fn fill(&self, order: &BrokerOrder, bar: &Bar) -> Option<(f64, bool)>;
+
+    /// As `fill`, given the book as of the previous bar's close when L2 data is being
+    /// replayed. Models that ignore the book can leave this alone.
+    fn fill_with_book(&self, order: &BrokerOrder, bar: &Bar, _book: Option<&BookLevels>) -> Option<(f64, bool)> {
+        self.fill(order, bar)
+    }
+
+    /// Price a stop triggered at `trigger` on `bar` fills at once it goes to market.
+    fn stop_fill(&self, _side: OrderSide, trigger: f64, _bar: &Bar) -> f64 {
+        trigger
+    }
+}
+
+/// Market orders fill at the bar open plus slippage. Limit orders fill at the open if
+/// it is already through the limit (taker), else at the limit if the bar trades through it (maker).
+#[derive(Debug, Clone, Copy)]
+pub struct BarFill {
+    pub slippage_rate: f64,
+}
+
+impl Default for BarFill {
+    fn default() -> Self {
+        Self { slippage_rate: 0.0005 }
+    }
+}
+
+impl FillModel for BarFill {
+    fn fill(&self, order: &BrokerOrder, bar: &Bar) -> Option<(f64, bool)> {
+        let side = order.request.side;
+        match (order.request.order_type, side) {
+            (OrderType::Market, OrderSide::Buy) => Some((bar.open * (1.0 + self.slippage_rate), false)),
+            (OrderType::Market, OrderSide::Sell) => Some((bar.open * (1.0 - self.slippage_rate), false)),
+            (OrderType::Limit(limit), OrderSide::Buy) if bar.open <= limit => Some((bar.open, false)),
+            (OrderType::Limit(limit), OrderSide::Buy) => (bar.low <= limit).then_some((limit, true)),
+            (OrderType::Limit(limit), OrderSide::Sell) if bar.open >= limit => Some((bar.open, false)),
+            (OrderType::Limit(limit), OrderSide::Sell) => (bar.high >= limit).then_some((limit, true)),
+        }
+    }
+
+    fn stop_fill(&self, side: OrderSide, trigger: f64, _bar: &Bar) -> f64 {
+        match side {
+            OrderSide::Buy => trigger * (1.0 + self.slippage_rate),
+            OrderSide::Sell => trigger * (1.0 - self.slippage_rate),
+        }
+    }
+}
+
+/// Fills against the replayed book, or as `fallback` without one. Market orders and
+/// marketable limits cross the spread and walk the visible levels, with any quantity
+/// beyond them at the last level, capped at the limit. Resting limits fill at their
+/// price once the bar trades through it; a bar that only touches the limit fills them
+/// only if the displayed queue ahead plus the order is within `touch_volume_share` of
+/// the bar's volume.
+#[derive(Debug, Clone, Copy)]
+pub struct BookFill {
+    pub fallback: BarFill,
+    pub touch_volume_share: f64,
+}
+
+impl Default for BookFill {
+    fn default() -> Self {
+        Self { fallback: BarFill::default(), touch_volume_share: 0.1 }
+    }
+}
+
+impl FillModel for BookFill {
+    fn fill(&self, order: &BrokerOrder, bar: &Bar) -> Option<(f64, bool)> {
+        self.fallback.fill(order, bar)
+    }
+
+    fn stop_fill(&self, side: OrderSide, trigger: f64, bar: &Bar) -> f64 {
+        self.fallback.stop_fill(side, trigger, bar)
+    }
+
+    fn fill_with_book(&self, order: &BrokerOrder, bar: &Bar, book: Option<&BookLevels>) -> Option<(f64, bool)> {
+        let Some((book, top)) = book.and_then(|b| Some((b, b.top()?))) else {
+            return self.fallback.fill(order, bar);
+        };
+        let (side, quantity) = (order.request.side, order.request.quantity);
+        let limit = match order.request.order_type {
+            OrderType::Market => None,
+            OrderType::Limit(limit) => Some(limit),
+        };
+        let marketable = match (limit, side) {
+            (None, _) => true,
+            (Some(limit), OrderSide::Buy) => limit >= top.ask,
+            (Some(limit), OrderSide::Sell) => limit <= top.bid,
+        };
+        if marketable {
+            let (avg, filled) = book.sweep(side, quantity)?;
+            let last = match side {
+                OrderSide::Buy => book.asks.last(),
+                OrderSide::Sell => book.bids.last(),
+            }?;
+            let price = (avg * filled + last.0 * (quantity - filled)) / quantity;
+            let price = match (limit, side) {
+                (Some(limit), OrderSide::Buy) => price.min(limit),
+                (Some(limit), OrderSide::Sell) => price.max(limit),
+                (None, _) => price,
+            };
+            return Some((price, false));
+        }
+
+        let limit = limit?;
+        let (gapped, through, touched) = match side {
+            OrderSide::Buy => (bar.open <= limit, bar.low < limit, bar.low <= limit),
+            OrderSide::Sell => (bar.open >= limit, bar.high > limit, bar.high >= limit),
+        };
+        if gapped {
+            return Some((bar.open, false));
+        }
+        let queue = book.queue_ahead(side, limit) + quantity;
+        (through || (touched && queue <= bar.volume * self.touch_volume_share)).then_some((limit, true))
+    }
+}
+
+pub trait FeeModel: Send + Sync {
+    fn fee(&self, notional: f64, is_maker: bool) -> f64;
+}
+
+/// Fee as a fraction of notional, with separate maker and taker rates.
+#[derive(Debug, Clone, Copy)]
+pub struct PercentageFee {
+    pub maker_rate: f64,
+    pub taker_rate: f64,
+}
+
+impl Default for PercentageFee {
+    fn default() -> Self {
+        Self { maker_rate: 0.001, taker_rate: 0.001 }
+    }
+}
+
+impl FeeModel for PercentageFee {
+    fn fee(&self, notional: f64, is_maker: bool) -> f64 {
+        notional.abs() * if is_maker { self.maker_rate } else { self.taker_rate }
+    }
+}
+
+/// What happened over one `run_live` session.
+#[derive(Debug, Clone, Default)]
+pub struct LiveSummary {
+    pub bars: usize,
+    pub orders: usize,
+    /// Orders refused by the risk manager or the broker.
+    pub rejected: usize,
+    pub fills: Vec<Fill>,
+    pub risk_events: Vec<RiskEvent>,
+    pub account: AccountSnapshot,
+}
+
+/// Feeds each streamed bar to the broker (collecting fills), updates `risk` with the
+/// marked equity, then asks the strategy. Targets are traded towards per `rebalance`;
+/// otherwise `sizer` turns signals into quantities, with exits capped at the position so
+/// they never reverse it. Orders that pass the risk checks become market orders for the
+/// next bar; entries carry the strategy's `bracket`, if it has one. If the kill switch
+/// trips, working orders are cancelled, positions flattened and the strategy's orders
+/// dropped from then on. Runs until the stream closes.
+pub async fn run_live<B: Broker>(
+    broker: &mut B,
+    strategy: &dyn Strategy,
+    sizer: &dyn PositionSizer,
+    rebalance: &RebalanceConfig,
+    mut bars: BarReceiver,
+    risk: &mut RiskManager,
+) -> BrokerResult<LiveSummary> {
+    let mut summary = LiveSummary::default();
+    let mut atr: HashMap<Symbol, Atr> = HashMap::new();
+    while let Some(sb) = bars.recv().await {
+        let mut step = BarStep { strategy, sizer, rebalance, risk, atr: &mut atr, summary: &mut summary };
+        step.run(broker, &sb.symbol, &sb.bar, None).await?;
+    }
+    summary.account = broker.account().await?;
+    Ok(summary)
+}
+
+/// The per-bar loop shared by `run_live` and the backtest engine, so paper, live and
+/// backtests apply the same sizing, rebalancing and risk checks.
+pub(crate) struct BarStep<'a> {
+    pub strategy: &'a dyn Strategy,
+    pub sizer: &'a dyn PositionSizer,
+    pub rebalance: &'a RebalanceConfig,
+    pub risk: &'a mut RiskManager,
+    pub atr: &'a mut HashMap<Symbol, Atr>,
+    pub summary: &'a mut LiveSummary,
+}
+
+impl BarStep<'_> {
+    /// Runs one closed `bar` for `symbol`; `book` is the order book as of its close, if known.
+    pub async fn run<B: Broker>(&mut self, broker: &mut B, symbol: &str, bar: &Bar, book: Option<&BookLevels>) -> BrokerResult<()> {
+        let (strategy, sizer, risk) = (self.strategy, self.sizer, &mut *self.risk);
+        let summary = &mut *self.summary;
+        summary.bars += 1;
+        let symbol_atr = self.atr.entry(symbol.to_string()).or_insert_with(|| Atr::new(14)).update(bar);
+        for fill in broker.on_bar(symbol, bar).await? {
+            println!("✅ {} {:?} {} {} @ {:.2}", fill.timestamp, fill.side, fill.quantity, fill.symbol, fill.price);
+            summary.fills.push(fill);
+        }
+
+        let account = broker.account().await?;
+        let date = bar.datetime().map_or_else(|_| Utc::now().date_naive(), |t| t.date_naive());
+        if risk.on_equity(account.equity, date, &bar.timestamp) {
+            println!("🛑 Kill switch tripped at {} — flattening and halting", bar.timestamp);
+            for order in broker.open_orders().await? {
+                broker.cancel_order(order.id).await?;
+            }
+            for request in RiskManager::flatten_orders(&account.positions) {
+                broker.place_order(request).await?;
+                summary.orders += 1;
+            }
+        }
+        if risk.is_halted() {
+            // Only the flattening orders trade from here; `account` predates them, so
+            // strategy orders checked against it could reopen what is being closed.
+            for event in risk.take_events() {
+                strategy.on_risk_event(&event);
+                summary.risk_events.push(event);
+            }
+            return Ok(());
+        }
+
+        let mut position = account.positions.get(symbol).copied().unwrap_or(0.0);
+        let mut bracket = None;
+        let requests = if let Some(target) = strategy.target(bar) {
+            // Orders still working count towards the target, so they aren't sent twice.
+            let mut projected = account.positions.clone();
+            for order in broker.open_orders().await? {
+                let signed = match order.request.side {
+                    OrderSide::Buy => order.request.quantity - order.filled_quantity,
+                    OrderSide::Sell => order.filled_quantity - order.request.quantity,
+                };
+                *projected.entry(order.request.symbol).or_default() += signed;
+            }
+            // The orders below start from there, so the risk checks must too.
+            position = projected.get(symbol).copied().unwrap_or(0.0);
+            rebalance_orders(
+                &HashMap::from([(symbol.to_string(), target)]),
+                &projected,
+                &HashMap::from([(symbol.to_string(), bar.close)]),
+                account.equity,
+                self.rebalance,
+            )
+        } else if let Some(signal) = strategy.signal_with_book(bar, book) {
+            let ctx = SizingContext {
+                equity: account.equity,
+                cash: account.cash,
+                price: bar.close,
+                position,
+                atr: symbol_atr,
+            };
+            let reducing = match signal.side {
+                OrderSide::Buy => position < 0.0,
+                OrderSide::Sell => position > 0.0,
+            };
+            let quantity = if reducing {
+                sizer.exit_size(&signal, &ctx).min(position.abs())
+            } else {
+                sizer.size(&signal, &ctx)
+            };
+            bracket = strategy.bracket();
+            if quantity > 0.0 { vec![OrderRequest::market(symbol, signal.side, quantity)] } else { Vec::new() }
+        } else {
+            Vec::new()
+        };
+
+        for request in requests {
+            let open = broker.open_orders().await?.len();
+            match risk.check(&request, bar.close, position, open, &bar.timestamp) {
+                Ok(()) => {
+                    let signed = match request.side {
+                        OrderSide::Buy => request.quantity,
+                        OrderSide::Sell => -request.quantity,
+                    };
+                    let order = match bracket {
+                        Some(spec) if (position + signed).abs() > position.abs() => {
+                            broker.place_bracket(request, spec).await?
+                        }
+                        _ => broker.place_order(request).await?,
+                    };
+                    summary.orders += 1;
+                    if order.status == OrderStatus::Rejected {
+                        summary.rejected += 1;
+                    }
+                }
+                Err(reason) => {
+                    println!("⛔ {} order rejected: {}", symbol, reason);
+                    summary.rejected += 1;
+                }
+            }
+        }
+
+        for event in risk.take_events() {
+            strategy.on_risk_event(&event);
+            summary.risk_events.push(event);
+        }
+        Ok(())
+    }
+}
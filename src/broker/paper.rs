@@ -0,0 +1,679 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::broker::bracket::{BracketId, BracketManager, BracketSpec, ExitReason, IntrabarSequence};
+use crate::broker::{
+    AccountSnapshot, BarFill, Broker, BrokerOrder, BrokerResult, FeeModel, FillModel, OrderId, OrderRequest,
+    OrderStatus, OrderType, PercentageFee,
+};
+use chrono::NaiveDate;
+
+use crate::data::bar::Bar;
+use crate::data::continuous::RollEvent;
+use crate::data::corporate_actions::{ActionKind, AdjustmentMode, CorporateActions};
+use crate::data::depth::BookLevels;
+use crate::data::feed::Symbol;
+use crate::data::order::OrderSide;
+use crate::portfolio::borrow::BorrowAccrual;
+use crate::portfolio::margin::{MarginConfig, MarginManager};
+use crate::portfolio::{Fill, FillKind, Portfolio};
+
+/// Simulated broker: orders rest until the next bar for their symbol, then fill per
+/// the fill model, are charged per the fee model and are booked into a `Portfolio`.
+/// Entries placed with `place_bracket` get engine-managed stops and targets once filled.
+pub struct PaperBroker {
+    portfolio: Portfolio,
+    fill_model: Box<dyn FillModel>,
+    fee_model: Box<dyn FeeModel>,
+    allow_short: bool,
+    orders: BTreeMap<OrderId, BrokerOrder>,
+    next_id: OrderId,
+    last_prices: HashMap<Symbol, f64>,
+    brackets: BracketManager,
+    pending_brackets: HashMap<OrderId, BracketSpec>,
+    margin: Option<MarginManager>,
+    borrow: Option<BorrowAccrual>,
+    trade_pnls: Vec<f64>,
+    books: HashMap<Symbol, BookLevels>,
+    pending_roll: Option<RollEvent>,
+}
+
+impl PaperBroker {
+    pub fn new(starting_cash: f64) -> Self {
+        Self {
+            portfolio: Portfolio::new(starting_cash),
+            fill_model: Box::new(BarFill::default()),
+            fee_model: Box::new(PercentageFee::default()),
+            allow_short: false,
+            orders: BTreeMap::new(),
+            next_id: 1,
+            last_prices: HashMap::new(),
+            brackets: BracketManager::default(),
+            pending_brackets: HashMap::new(),
+            margin: None,
+            borrow: None,
+            trade_pnls: Vec::new(),
+            books: HashMap::new(),
+            pending_roll: None,
+        }
+    }
+
+    pub fn with_fill_model(mut self, model: impl FillModel + 'static) -> Self {
+        self.fill_model = Box::new(model);
+        self
+    }
+
+    pub fn with_fee_model(mut self, model: impl FeeModel + 'static) -> Self {
+        self.fee_model = Box::new(model);
+        self
+    }
+
+    /// Spot accounts (the default) reject sells larger than the position held.
+    pub fn allow_short(mut self, allow: bool) -> Self {
+        self.allow_short = allow;
+        self
+    }
+
+    /// How a bar that crosses both a stop and a target is resolved (stop first by default).
+    pub fn with_intrabar_sequence(mut self, sequence: IntrabarSequence) -> Self {
+        self.brackets.sequence = sequence;
+        self
+    }
+
+    /// Trades as a margin (futures) account: orders need initial margin rather than the
+    /// full notional, shorts are allowed, and positions are liquidated at the maintenance level.
+    pub fn with_margin(mut self, config: MarginConfig) -> Self {
+        self.margin = Some(MarginManager::new(config));
+        self.allow_short = true;
+        self.borrow = self.borrow.take().map(BorrowAccrual::without_cash);
+        self
+    }
+
+    /// Charges interest on shorts and negative cash as bar timestamps cross each
+    /// accrual boundary. In a margin account negative cash is not a loan and is free.
+    pub fn with_borrow(mut self, accrual: BorrowAccrual) -> Self {
+        self.borrow = Some(if self.margin.is_some() { accrual.without_cash() } else { accrual });
+        self
+    }
+
+    pub fn margin(&self) -> Option<&MarginManager> {
+        self.margin.as_ref()
+    }
+
+    pub fn brackets(&self) -> &BracketManager {
+        &self.brackets
+    }
+
+    /// Protects an existing position in `side`'s direction with a stop/target pair.
+    pub fn place_oco(&mut self, symbol: &str, side: OrderSide, quantity: f64, stop: f64, target: f64) -> BracketId {
+        self.brackets.oco(symbol, side, quantity, stop, target)
+    }
+
+    pub fn cancel_bracket(&mut self, id: BracketId) -> bool {
+        self.brackets.cancel(id).is_some()
+    }
+
+    pub fn portfolio(&self) -> &Portfolio {
+        &self.portfolio
+    }
+
+    /// Moves orders working on `roll.from` to `roll.to`, with limits shifted by the roll
+    /// gap, so they fill on the roll bar. The position follows once that bar's orders
+    /// have filled, at the roll prices (its close) with taker fees on both legs; brackets
+    /// on the old contract are cancelled then.
+    pub fn schedule_roll(&mut self, roll: &RollEvent) {
+        let gap = roll.to_price - roll.from_price;
+        for order in self.orders.values_mut().filter(|o| o.status.is_open() && o.request.symbol == roll.from) {
+            order.request.symbol = roll.to.clone();
+            if let OrderType::Limit(price) = &mut order.request.order_type {
+                *price += gap;
+            }
+        }
+        self.pending_roll = Some(roll.clone());
+    }
+
+    fn roll(&mut self, roll: &RollEvent) {
+        self.brackets.cancel_symbol(&roll.from);
+        for mut fill in self.portfolio.roll_fills(roll) {
+            fill.fee = self.fee_model.fee(fill.quantity * fill.price, false);
+            self.book(fill);
+        }
+        self.last_prices.remove(&roll.from);
+        self.last_prices.insert(roll.to.clone(), roll.to_price);
+    }
+
+    /// The book for `symbol` as of the latest close, passed to the fill model when the
+    /// next bar fills working orders. `None` clears it.
+    pub fn set_book(&mut self, symbol: &str, book: Option<BookLevels>) {
+        match book {
+            Some(book) => self.books.insert(symbol.to_string(), book),
+            None => self.books.remove(symbol),
+        };
+    }
+
+    /// Realized PnL of each fill that reduced a position, net of its fee.
+    pub fn trade_pnls(&self) -> &[f64] {
+        &self.trade_pnls
+    }
+
+    /// Applies corporate actions going ex on `date` (see `Portfolio::apply_corporate_actions`).
+    /// Call before the first bar of that date.
+    pub fn apply_corporate_actions(
+        &mut self,
+        actions: &CorporateActions,
+        date: NaiveDate,
+        adjustment: Option<AdjustmentMode>,
+    ) -> f64 {
+        if adjustment.is_none() {
+            // Keep marks on raw prices in step with the rescaled positions.
+            for action in actions.on(date) {
+                if let ActionKind::Split(shares) | ActionKind::Bonus(shares) = action.kind
+                    && let Some(price) = self.last_prices.get_mut(&action.symbol)
+                {
+                    *price /= shares;
+                }
+            }
+        }
+        self.portfolio.apply_corporate_actions(actions, date, adjustment)
+    }
+
+    fn get(&self, id: OrderId) -> BrokerResult<BrokerOrder> {
+        self.orders.get(&id).cloned().ok_or_else(|| format!("Unknown order id {}", id).into())
+    }
+
+    /// Whether the account can take the fill right now.
+    fn affordable(&self, order: &BrokerOrder, price: f64, fee: f64) -> bool {
+        let qty = order.request.quantity;
+        if let Some(margin) = &self.margin {
+            return margin.check_order(&self.portfolio, &order.request, price, fee, &self.last_prices).is_ok();
+        }
+        let symbol = &order.request.symbol;
+        // Until a scheduled roll is booked, the position is still in the old contract.
+        let rolling = self.pending_roll.as_ref().filter(|r| &r.to == symbol).map_or(0.0, |r| self.portfolio.position(&r.from));
+        match order.request.side {
+            OrderSide::Buy => qty * price + fee <= self.portfolio.cash,
+            OrderSide::Sell => self.allow_short || qty <= self.portfolio.position(symbol) + rolling + 1e-12,
+        }
+    }
+
+    /// Books `fill`; brackets protecting the position go with it once it is flat or
+    /// has changed sides.
+    fn book(&mut self, fill: Fill) {
+        let fee = fill.fee;
+        let symbol = fill.symbol.clone();
+        let before = self.portfolio.position(&symbol);
+        let realized = match self.margin.as_mut() {
+            Some(margin) => margin.apply_fill(&mut self.portfolio, fill),
+            None => self.portfolio.apply_fill(fill),
+        };
+        if realized != 0.0 {
+            self.trade_pnls.push(realized - fee);
+        }
+        let after = self.portfolio.position(&symbol);
+        if before != 0.0 && (after.abs() <= 1e-12 || after.signum() != before.signum()) {
+            self.brackets.cancel_symbol(&symbol);
+        }
+    }
+}
+
+impl Broker for PaperBroker {
+    async fn place_order(&mut self, request: OrderRequest) -> BrokerResult<BrokerOrder> {
+        if request.quantity.is_nan() || request.quantity <= 0.0 {
+            return Err(format!("Order quantity must be positive, got {}", request.quantity).into());
+        }
+        if let OrderType::Limit(price) = request.order_type
+            && (price.is_nan() || price <= 0.0)
+        {
+            return Err(format!("Limit price must be positive, got {}", price).into());
+        }
+        let order = BrokerOrder {
+            id: self.next_id,
+            request,
+            status: OrderStatus::New,
+            filled_quantity: 0.0,
+            avg_fill_price: 0.0,
+        };
+        self.next_id += 1;
+        self.orders.insert(order.id, order.clone());
+        Ok(order)
+    }
+
+    async fn cancel_order(&mut self, id: OrderId) -> BrokerResult<BrokerOrder> {
+        let order = self.orders.get_mut(&id).ok_or_else(|| format!("Unknown order id {}", id))?;
+        if !order.status.is_open() {
+            return Err(format!("Order {} is already {:?}", id, order.status).into());
+        }
+        order.status = OrderStatus::Canceled;
+        self.pending_brackets.remove(&id);
+        Ok(order.clone())
+    }
+
+    async fn order_status(&mut self, id: OrderId) -> BrokerResult<BrokerOrder> {
+        self.get(id)
+    }
+
+    /// Exits are checked from the bar after the entry fills, and are dropped when the
+    /// position is closed or reversed by other orders.
+    async fn place_bracket(&mut self, request: OrderRequest, spec: BracketSpec) -> BrokerResult<BrokerOrder> {
+        let order = self.place_order(request).await?;
+        self.pending_brackets.insert(order.id, spec);
+        Ok(order)
+    }
+
+    async fn open_orders(&mut self) -> BrokerResult<Vec<BrokerOrder>> {
+        Ok(self.orders.values().filter(|o| o.status.is_open()).cloned().collect())
+    }
+
+    async fn account(&mut self) -> BrokerResult<AccountSnapshot> {
+        Ok(AccountSnapshot {
+            cash: self.portfolio.cash,
+            positions: self.portfolio.positions.iter().map(|(s, p)| (s.clone(), p.quantity)).collect(),
+            equity: self.portfolio.equity(&self.last_prices),
+        })
+    }
+
+    async fn on_bar(&mut self, symbol: &str, bar: &Bar) -> BrokerResult<Vec<Fill>> {
+        let pending: Vec<OrderId> = self
+            .orders
+            .values()
+            .filter(|o| o.status.is_open() && o.request.symbol == symbol)
+            .map(|o| o.id)
+            .collect();
+        if let Some(borrow) = self.borrow.as_mut()
+            && let Ok(now) = bar.datetime()
+        {
+            borrow.accrue(&mut self.portfolio, now, &self.last_prices);
+        }
+        let mut fills = Vec::new();
+        for exit in self.brackets.on_bar(symbol, bar) {
+            // The position may have been reduced by hand since the bracket was set.
+            let held = self.portfolio.position(symbol);
+            let available = if exit.side == OrderSide::Sell { held } else { -held };
+            let quantity = exit.quantity.min(available);
+            if quantity <= 1e-12 {
+                continue;
+            }
+            // Targets rest as limits; stops go to market and slip like any market order.
+            let is_maker = exit.reason == ExitReason::TakeProfit;
+            let price = if is_maker { exit.price } else { self.fill_model.stop_fill(exit.side, exit.price, bar) };
+            let fill = Fill {
+                timestamp: exit.timestamp,
+                symbol: exit.symbol,
+                side: exit.side,
+                quantity,
+                price,
+                fee: self.fee_model.fee(quantity * price, is_maker),
+                kind: FillKind::Trade,
+            };
+            self.book(fill.clone());
+            fills.push(fill);
+        }
+        for id in pending {
+            let order = self.orders[&id].clone();
+            let Some((price, is_maker)) = self.fill_model.fill_with_book(&order, bar, self.books.get(symbol)) else {
+                continue;
+            };
+            let fee = self.fee_model.fee(order.request.quantity * price, is_maker);
+            let status = if self.affordable(&order, price, fee) {
+                let fill = Fill {
+                    timestamp: bar.timestamp.clone(),
+                    symbol: symbol.to_string(),
+                    side: order.request.side,
+                    quantity: order.request.quantity,
+                    price,
+                    fee,
+                    kind: FillKind::Trade,
+                };
+                self.book(fill.clone());
+                fills.push(fill);
+                OrderStatus::Filled
+            } else {
+                OrderStatus::Rejected
+            };
+            let entry = self.orders.get_mut(&id).expect("pending order exists");
+            entry.status = status;
+            if status == OrderStatus::Filled {
+                entry.filled_quantity = entry.request.quantity;
+                entry.avg_fill_price = price;
+            }
+            if let Some(spec) = self.pending_brackets.remove(&id)
+                && status == OrderStatus::Filled
+            {
+                let side = order.request.side;
+                self.brackets.open(Some(id), symbol, side, order.request.quantity, price, &spec);
+            }
+        }
+        if let Some(roll) = self.pending_roll.take_if(|r| r.to == symbol) {
+            self.roll(&roll);
+        }
+        self.last_prices.insert(symbol.to_string(), bar.close);
+        if let Some(margin) = self.margin.as_mut()
+            && let Some(liquidation) = margin.on_bar(&self.portfolio, symbol, bar, &self.last_prices)
+        {
+            println!("💥 {} liquidated: {} @ {:.2}", symbol, liquidation.quantity, liquidation.price);
+            // Booked like any fill, so the loss and fee count as a closed trade.
+            self.book(liquidation.clone());
+            for order in self.orders.values_mut().filter(|o| o.status.is_open() && o.request.symbol == symbol) {
+                order.status = OrderStatus::Canceled;
+            }
+            fills.push(liquidation);
+        }
+        Ok(fills)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::broker::run_live;
+    use crate::data::stream::{DataFeed, ReplayServer};
+    use crate::data::order::Order;
+    use crate::portfolio::rebalance::{RebalanceConfig, Target};
+    use crate::risk::sizing::FixedQuantity;
+    use crate::strategy::Strategy;
+    use crate::risk::{RiskEvent, RiskLimits, RiskManager};
+    use crate::strategy::always_buy::AlwaysBuy;
+
+    fn bar(ts: &str, open: f64, low: f64, high: f64, close: f64) -> Bar {
+        Bar { timestamp: ts.into(), open, high, low, close, volume: 20_000.0 }
+    }
+
+    #[tokio::test]
+    async fn test_market_and_limit_fills() {
+        let mut broker = PaperBroker::new(1_000.0)
+            .with_fill_model(BarFill { slippage_rate: 0.0 })
+            .with_fee_model(PercentageFee { maker_rate: 0.0, taker_rate: 0.01 });
+        let buy = broker.place_order(OrderRequest::market("X", OrderSide::Buy, 2.0)).await.unwrap();
+        let sell = broker.place_order(OrderRequest::limit("X", OrderSide::Sell, 2.0, 120.0)).await.unwrap();
+
+        let fills = broker.on_bar("X", &bar("t1", 100.0, 95.0, 110.0, 105.0)).await.unwrap();
+        assert_eq!(fills.len(), 1);
+        assert_eq!((fills[0].price, fills[0].fee), (100.0, 2.0));
+        assert_eq!(broker.order_status(buy.id).await.unwrap().status, OrderStatus::Filled);
+        assert_eq!(broker.open_orders().await.unwrap().len(), 1);
+
+        let fills = broker.on_bar("X", &bar("t2", 110.0, 105.0, 125.0, 118.0)).await.unwrap();
+        assert_eq!((fills[0].price, fills[0].fee), (120.0, 0.0));
+        assert_eq!(broker.order_status(sell.id).await.unwrap().avg_fill_price, 120.0);
+        let account = broker.account().await.unwrap();
+        assert_eq!(account.cash, 1_038.0);
+        assert!(account.positions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_unaffordable_and_naked_sells() {
+        let mut broker = PaperBroker::new(100.0);
+        let buy = broker.place_order(OrderRequest::market("X", OrderSide::Buy, 5.0)).await.unwrap();
+        let sell = broker.place_order(OrderRequest::market("X", OrderSide::Sell, 1.0)).await.unwrap();
+        assert!(broker.place_order(OrderRequest::market("X", OrderSide::Buy, 0.0)).await.is_err());
+        assert!(broker.on_bar("X", &bar("t1", 50.0, 50.0, 50.0, 50.0)).await.unwrap().is_empty());
+        assert_eq!(broker.order_status(buy.id).await.unwrap().status, OrderStatus::Rejected);
+        assert_eq!(broker.order_status(sell.id).await.unwrap().status, OrderStatus::Rejected);
+        assert!(broker.cancel_order(buy.id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bracket_exits_after_entry_fills() {
+        use crate::broker::bracket::ExitLevel;
+        let mut broker = PaperBroker::new(1_000.0)
+            .with_fill_model(BarFill { slippage_rate: 0.0 })
+            .with_fee_model(PercentageFee { maker_rate: 0.0, taker_rate: 0.0 });
+        let spec = BracketSpec::new().stop_loss(ExitLevel::Percent(0.05)).take_profit(ExitLevel::Percent(0.1));
+        broker.place_bracket(OrderRequest::market("X", OrderSide::Buy, 2.0), spec).await.unwrap();
+
+        // The entry bar dips below the stop, but exits only arm from the next bar.
+        broker.on_bar("X", &bar("t1", 100.0, 90.0, 101.0, 100.0)).await.unwrap();
+        assert_eq!(broker.brackets().brackets().count(), 1);
+        let fills = broker.on_bar("X", &bar("t2", 100.0, 94.0, 111.0, 100.0)).await.unwrap();
+        assert_eq!((fills[0].side, fills[0].price), (OrderSide::Sell, 95.0));
+        assert_eq!(broker.portfolio().position("X"), 0.0);
+        assert_eq!(broker.brackets().brackets().count(), 0);
+
+        // An OCO over a position that was since sold by hand does nothing.
+        broker.place_oco("X", OrderSide::Buy, 1.0, 90.0, 120.0);
+        assert!(broker.on_bar("X", &bar("t3", 100.0, 80.0, 130.0, 100.0)).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_bracket_dropped_when_position_closes_and_stops_slip() {
+        use crate::broker::bracket::ExitLevel;
+        let mut broker = PaperBroker::new(1_000.0)
+            .with_fill_model(BarFill { slippage_rate: 0.01 })
+            .with_fee_model(PercentageFee { maker_rate: 0.0, taker_rate: 0.0 });
+        let stop = || BracketSpec::new().stop_loss(ExitLevel::Price(95.0));
+        broker.place_bracket(OrderRequest::limit("X", OrderSide::Buy, 2.0, 100.0), stop()).await.unwrap();
+        broker.on_bar("X", &bar("t1", 100.0, 99.0, 101.0, 100.0)).await.unwrap();
+        broker.place_order(OrderRequest::market("X", OrderSide::Sell, 2.0)).await.unwrap();
+        broker.on_bar("X", &bar("t2", 100.0, 99.0, 101.0, 100.0)).await.unwrap();
+        assert_eq!(broker.brackets().brackets().count(), 0);
+
+        // A plain re-entry isn't stopped out by the old bracket.
+        broker.place_order(OrderRequest::limit("X", OrderSide::Buy, 2.0, 100.0)).await.unwrap();
+        broker.on_bar("X", &bar("t3", 100.0, 99.0, 101.0, 100.0)).await.unwrap();
+        assert!(broker.on_bar("X", &bar("t4", 100.0, 90.0, 101.0, 96.0)).await.unwrap().is_empty());
+        assert_eq!(broker.portfolio().position("X"), 2.0);
+
+        // A stop that does trigger goes to market and pays slippage.
+        broker.place_bracket(OrderRequest::limit("X", OrderSide::Buy, 1.0, 96.0), stop()).await.unwrap();
+        broker.on_bar("X", &bar("t5", 96.0, 96.0, 97.0, 96.0)).await.unwrap();
+        let fills = broker.on_bar("X", &bar("t6", 96.0, 94.0, 97.0, 95.0)).await.unwrap();
+        assert_eq!((fills[0].side, fills[0].quantity), (OrderSide::Sell, 1.0));
+        assert!((fills[0].price - 95.0 * 0.99).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_book_fill_waits_for_queue_on_touch() {
+        use crate::broker::BookFill;
+        let mut broker = PaperBroker::new(1_000.0).with_fill_model(BookFill::default());
+        broker.set_book("X", Some(BookLevels { timestamp_ms: 0, bids: vec![(99.0, 50.0)], asks: vec![(101.0, 50.0)] }));
+        let order = broker.place_order(OrderRequest::limit("X", OrderSide::Buy, 1.0, 99.0)).await.unwrap();
+        // A touch of 99 on 100 traded can't get through the 50 displayed ahead.
+        let mut touch = bar("t1", 100.0, 99.0, 101.0, 100.0);
+        touch.volume = 100.0;
+        assert!(broker.on_bar("X", &touch).await.unwrap().is_empty());
+        let fills = broker.on_bar("X", &bar("t2", 100.0, 98.5, 101.0, 100.0)).await.unwrap();
+        assert_eq!(fills[0].price, 99.0);
+        assert_eq!(broker.order_status(order.id).await.unwrap().status, OrderStatus::Filled);
+    }
+
+    #[tokio::test]
+    async fn test_margin_account_liquidates_leveraged_short() {
+        use crate::portfolio::margin::MarginEvent;
+        let config = MarginConfig { leverage: 5.0, maintenance_rate: 0.01, liquidation_fee_rate: 0.01, ..Default::default() };
+        let mut broker = PaperBroker::new(1_000.0)
+            .with_fill_model(BarFill { slippage_rate: 0.0 })
+            .with_fee_model(PercentageFee { maker_rate: 0.0, taker_rate: 0.0 })
+            .with_margin(config);
+        // 5x is the most the account can carry.
+        let too_big = broker.place_order(OrderRequest::market("X", OrderSide::Sell, 51.0)).await.unwrap();
+        broker.on_bar("X", &bar("t1", 100.0, 100.0, 100.0, 100.0)).await.unwrap();
+        assert_eq!(broker.order_status(too_big.id).await.unwrap().status, OrderStatus::Rejected);
+
+        broker.place_order(OrderRequest::market("X", OrderSide::Sell, 50.0)).await.unwrap();
+        broker.on_bar("X", &bar("t2", 100.0, 100.0, 101.0, 101.0)).await.unwrap();
+        assert_eq!(broker.portfolio().position("X"), -50.0);
+        let fills = broker.on_bar("X", &bar("t3", 101.0, 100.0, 125.0, 120.0)).await.unwrap();
+        let liquidation = fills.last().unwrap();
+        assert_eq!(liquidation.kind, FillKind::Liquidation);
+        assert_eq!(broker.portfolio().position("X"), 0.0);
+        let entry = broker.portfolio().blotter[0].price;
+        let loss = (entry - liquidation.price) * 50.0 - liquidation.fee;
+        assert!(loss < 0.0);
+        assert_eq!(broker.trade_pnls(), &[loss]);
+        assert!(matches!(broker.margin().unwrap().events().last(), Some(MarginEvent::Liquidation { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_short_pays_borrow_interest() {
+        use crate::portfolio::borrow::{AccrualPeriod, BorrowRates};
+        let mut broker = PaperBroker::new(1_000.0)
+            .with_fill_model(BarFill { slippage_rate: 0.0 })
+            .with_fee_model(PercentageFee { maker_rate: 0.0, taker_rate: 0.0 })
+            .allow_short(true)
+            .with_borrow(BorrowAccrual::new(BorrowRates::new(0.876), AccrualPeriod::Hourly));
+        broker.place_order(OrderRequest::market("X", OrderSide::Sell, 10.0)).await.unwrap();
+        broker.on_bar("X", &bar("2024-01-01T00:00:00Z", 100.0, 100.0, 100.0, 100.0)).await.unwrap();
+        broker.on_bar("X", &bar("2024-01-01T01:00:00Z", 100.0, 100.0, 100.0, 100.0)).await.unwrap();
+        broker.on_bar("X", &bar("2024-01-01T02:00:00Z", 100.0, 100.0, 100.0, 100.0)).await.unwrap();
+        // Short from the first bar's open; 1000 * 0.876 / 8760 = 0.1 at 01:00 and 02:00.
+        assert!((broker.portfolio().borrow_cost - 0.2).abs() < 1e-12);
+        assert!((broker.portfolio().cash - 1_999.8).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_margin_long_pays_no_cash_rate() {
+        use crate::portfolio::borrow::{AccrualPeriod, BorrowRates};
+        let mut broker = PaperBroker::new(1_000.0)
+            .with_fill_model(BarFill { slippage_rate: 0.0 })
+            .with_fee_model(PercentageFee { maker_rate: 0.0, taker_rate: 0.0 })
+            .with_borrow(BorrowAccrual::new(BorrowRates::new(0.0).with_cash_rate(0.876), AccrualPeriod::Hourly))
+            .with_margin(MarginConfig { leverage: 5.0, ..Default::default() });
+        // 3000 of notional on 1000 of margin leaves cash at -2000, which is not a loan.
+        broker.place_order(OrderRequest::market("X", OrderSide::Buy, 30.0)).await.unwrap();
+        broker.on_bar("X", &bar("2024-01-01T00:00:00Z", 100.0, 100.0, 100.0, 100.0)).await.unwrap();
+        broker.on_bar("X", &bar("2024-01-01T01:00:00Z", 100.0, 100.0, 100.0, 100.0)).await.unwrap();
+        assert!(broker.portfolio().cash < 0.0);
+        assert_eq!(broker.portfolio().borrow_cost, 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_run_live_on_replayed_bars() {
+        let bars = vec![
+            bar("2024-01-01T00:00:00Z", 100.0, 99.0, 106.0, 105.0),
+            bar("2024-01-01T01:00:00Z", 105.0, 104.0, 108.0, 107.0),
+            bar("2024-01-01T02:00:00Z", 107.0, 100.0, 107.0, 101.0),
+        ];
+        let (rx, _) = ReplayServer::new("X", bars).spawn(8);
+        let mut broker = PaperBroker::new(1_000.0);
+        let summary = run_live(&mut broker, &AlwaysBuy, &FixedQuantity(1.0), &RebalanceConfig::default(), rx, &mut RiskManager::default()).await.unwrap();
+        assert_eq!((summary.bars, summary.orders, summary.fills.len()), (3, 2, 2));
+        assert_eq!(summary.account.positions["X"], 2.0);
+        assert!((summary.account.equity - (summary.account.cash + 2.0 * 101.0)).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_run_live_exit_is_not_resized() {
+        use crate::risk::sizing::PercentEquity;
+        struct InAndOut;
+        impl Strategy for InAndOut {
+            fn generate_signal(&self, bar: &Bar) -> Option<Order> {
+                let side = match bar.timestamp.as_str() {
+                    "2024-01-01T00:00:00Z" => OrderSide::Buy,
+                    "2024-01-01T01:00:00Z" => OrderSide::Sell,
+                    _ => return None,
+                };
+                Some(Order { side, price: bar.close, quantity: 1, timestamp: bar.timestamp.clone() })
+            }
+        }
+        let bars = vec![
+            bar("2024-01-01T00:00:00Z", 100.0, 99.0, 106.0, 105.0),
+            bar("2024-01-01T01:00:00Z", 104.0, 100.0, 120.0, 118.0),
+            bar("2024-01-01T02:00:00Z", 118.0, 117.0, 119.0, 118.0),
+        ];
+        let (rx, _) = ReplayServer::new("X", bars).spawn(8);
+        let mut broker = PaperBroker::new(1_000.0).allow_short(true);
+        let summary = run_live(&mut broker, &InAndOut, &PercentEquity(0.5), &RebalanceConfig::default(), rx, &mut RiskManager::default()).await.unwrap();
+        // Half the equity went in; the sell closes exactly that even though equity grew.
+        assert_eq!(summary.fills.len(), 2);
+        assert!((summary.fills[0].quantity - 500.0 / 105.0).abs() < 1e-9);
+        assert_eq!(summary.fills[1].quantity, summary.fills[0].quantity);
+        assert_eq!(summary.account.positions.get("X").copied().unwrap_or(0.0), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_run_live_places_strategy_brackets() {
+        use crate::broker::bracket::ExitLevel;
+        struct Protected;
+        impl Strategy for Protected {
+            fn generate_signal(&self, bar: &Bar) -> Option<Order> {
+                AlwaysBuy.generate_signal(bar)
+            }
+            fn bracket(&self) -> Option<BracketSpec> {
+                Some(BracketSpec::new().stop_loss(ExitLevel::Price(102.0)))
+            }
+        }
+        let bars = vec![
+            bar("2024-01-01T00:00:00Z", 100.0, 99.0, 106.0, 105.0),
+            bar("2024-01-01T01:00:00Z", 105.0, 104.0, 108.0, 107.0),
+            bar("2024-01-01T02:00:00Z", 107.0, 100.0, 107.0, 101.0),
+        ];
+        let (rx, _) = ReplayServer::new("X", bars).spawn(8);
+        let mut broker = PaperBroker::new(1_000.0);
+        let summary = run_live(&mut broker, &Protected, &FixedQuantity(1.0), &RebalanceConfig::default(), rx, &mut RiskManager::default()).await.unwrap();
+        // The first entry is stopped out on the last bar, as the second one fills and arms.
+        assert_eq!(summary.fills.len(), 3);
+        assert_eq!((summary.fills[1].side, summary.fills[1].price), (OrderSide::Sell, 102.0 * (1.0 - 0.0005)));
+        assert_eq!(summary.account.positions["X"], 1.0);
+        assert_eq!(broker.brackets().brackets().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_run_live_trades_towards_target_weight() {
+        struct HalfLong;
+        impl Strategy for HalfLong {
+            fn generate_signal(&self, _bar: &Bar) -> Option<Order> {
+                None
+            }
+            fn target(&self, _bar: &Bar) -> Option<Target> {
+                Some(Target::Weight(0.5))
+            }
+        }
+        let bars = vec![
+            bar("2024-01-01T00:00:00Z", 100.0, 100.0, 100.0, 100.0),
+            bar("2024-01-01T01:00:00Z", 100.0, 100.0, 100.0, 100.0),
+            bar("2024-01-01T02:00:00Z", 102.0, 102.0, 102.0, 102.0),
+        ];
+        let (rx, _) = ReplayServer::new("X", bars).spawn(8);
+        let mut broker = PaperBroker::new(1_000.0)
+            .with_fill_model(BarFill { slippage_rate: 0.0 })
+            .with_fee_model(PercentageFee { maker_rate: 0.0, taker_rate: 0.0 });
+        let rebalance = RebalanceConfig { threshold: 0.05, ..Default::default() };
+        let summary = run_live(&mut broker, &HalfLong, &FixedQuantity(1.0), &rebalance, rx, &mut RiskManager::default())
+            .await
+            .unwrap();
+        // One order to get to 50%; the later drift is inside the 5% band.
+        assert_eq!(summary.orders, 1);
+        assert_eq!(summary.account.positions["X"], 5.0);
+    }
+
+    #[tokio::test]
+    async fn test_target_orders_are_risk_checked_against_working_orders() {
+        struct OneLong;
+        impl Strategy for OneLong {
+            fn generate_signal(&self, _bar: &Bar) -> Option<Order> {
+                None
+            }
+            fn target(&self, _bar: &Bar) -> Option<Target> {
+                Some(Target::Quantity(1.0))
+            }
+        }
+        let mut broker = PaperBroker::new(1_000.0).allow_short(true);
+        broker.place_order(OrderRequest::limit("X", OrderSide::Buy, 3.0, 50.0)).await.unwrap();
+        let (rx, _) = ReplayServer::new("X", vec![bar("2024-01-01T00:00:00Z", 100.0, 99.0, 101.0, 100.0)]).spawn(8);
+        let mut risk = RiskManager::new(RiskLimits::default().with_max_position(1.0));
+        let summary = run_live(&mut broker, &OneLong, &FixedQuantity(1.0), &RebalanceConfig::default(), rx, &mut risk)
+            .await
+            .unwrap();
+        // Selling 2 against the working buy of 3 reduces towards the limit, so it passes.
+        assert_eq!((summary.orders, summary.rejected), (1, 0));
+    }
+
+    #[tokio::test]
+    async fn test_run_live_kill_switch_flattens() {
+        let bars = vec![
+            bar("2024-01-01T00:00:00Z", 100.0, 99.0, 106.0, 105.0),
+            bar("2024-01-01T01:00:00Z", 105.0, 104.0, 108.0, 107.0),
+            bar("2024-01-01T02:00:00Z", 107.0, 10.0, 107.0, 10.0),
+            bar("2024-01-01T03:00:00Z", 10.0, 9.0, 12.0, 11.0),
+            bar("2024-01-01T04:00:00Z", 11.0, 10.0, 13.0, 12.0),
+        ];
+        let (rx, _) = ReplayServer::new("X", bars).spawn(8);
+        let mut broker = PaperBroker::new(200.0).with_fee_model(PercentageFee { maker_rate: 0.0, taker_rate: 0.0 });
+        let mut risk = RiskManager::new(RiskLimits::default().with_max_drawdown(0.3));
+        let summary = run_live(&mut broker, &AlwaysBuy, &FixedQuantity(1.0), &RebalanceConfig::default(), rx, &mut risk).await.unwrap();
+        assert!(risk.is_halted());
+        assert!(summary.account.positions.is_empty());
+        // Two entries and the flattening sell; later buy signals are dropped.
+        assert_eq!(summary.orders, 3);
+        assert!(summary.risk_events.iter().any(|e| matches!(e, RiskEvent::KillSwitch { .. })));
+    }
+}
@@ -1,3 +1,5 @@
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+
 #[derive(Debug, Clone)]
 pub struct Bar {
     pub timestamp: String,
@@ -8,6 +10,28 @@ pub struct Bar {
     pub volume: f64,
 }
 
+impl Bar {
+    /// Parses `timestamp` as RFC 3339, falling back to a naive `YYYY-MM-DDTHH:MM:SS` read as UTC.
+    pub fn datetime(&self) -> Result<DateTime<Utc>, chrono::ParseError> {
+        match DateTime::parse_from_rfc3339(&self.timestamp) {
+            Ok(dt) => Ok(dt.with_timezone(&Utc)),
+            Err(_) => NaiveDateTime::parse_from_str(&self.timestamp, "%Y-%m-%dT%H:%M:%S")
+                .or_else(|_| NaiveDateTime::parse_from_str(&self.timestamp, "%Y-%m-%d %H:%M:%S"))
+                .map(|naive| naive.and_utc()),
+        }
+    }
+}
+
+/// Bar spacing, taken as the smallest gap between the first few bars.
+pub fn infer_interval(bars: &[Bar]) -> Option<Duration> {
+    let times: Vec<_> = bars.iter().take(16).filter_map(|b| b.datetime().ok()).collect();
+    times
+        .windows(2)
+        .map(|w| (w[1] - w[0]).abs())
+        .filter(|d| *d > Duration::zero())
+        .min()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -24,4 +48,21 @@ mod tests {
         };
         assert_eq!(bar.close, 1.5);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_bar_datetime_formats() {
+        let mut bar = Bar {
+            timestamp: "2025-10-24T00:15:00+00:00".to_string(),
+            open: 1.0,
+            high: 2.0,
+            low: 0.5,
+            close: 1.5,
+            volume: 140.00,
+        };
+        let rfc = bar.datetime().unwrap();
+        bar.timestamp = "2025-10-24T00:15:00".to_string();
+        assert_eq!(bar.datetime().unwrap(), rfc);
+        bar.timestamp = "not a date".to_string();
+        assert!(bar.datetime().is_err());
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+
+/// One trading session, in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Session {
+    pub open: DateTime<Utc>,
+    pub close: DateTime<Utc>,
+}
+
+impl Session {
+    pub fn contains(&self, at: DateTime<Utc>) -> bool {
+        self.open <= at && at < self.close
+    }
+}
+
+pub trait TradingCalendar: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn timezone(&self) -> Tz;
+
+    /// Session for a local trading date, `None` on weekends and holidays.
+    fn session(&self, date: NaiveDate) -> Option<Session>;
+
+    /// Number of bars of `interval` in an average trading year, used for annualization.
+    fn periods_per_year(&self, interval: Duration) -> f64;
+
+    fn is_trading_day(&self, date: NaiveDate) -> bool {
+        self.session(date).is_some()
+    }
+
+    /// Local calendar date of an instant in the exchange's time zone.
+    fn local_date(&self, at: DateTime<Utc>) -> NaiveDate {
+        at.with_timezone(&self.timezone()).date_naive()
+    }
+
+    /// The session `at` falls in, if the market is open then.
+    fn session_at(&self, at: DateTime<Utc>) -> Option<Session> {
+        self.session(self.local_date(at)).filter(|s| s.contains(at))
+    }
+
+    fn is_open(&self, at: DateTime<Utc>) -> bool {
+        self.session_at(at).is_some()
+    }
+}
+
+/// Crypto venues: every UTC day is one 24h session.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Crypto247;
+
+impl TradingCalendar for Crypto247 {
+    fn name(&self) -> &str {
+        "24/7"
+    }
+
+    fn timezone(&self) -> Tz {
+        Tz::UTC
+    }
+
+    fn session(&self, date: NaiveDate) -> Option<Session> {
+        let open = date.and_time(NaiveTime::MIN).and_utc();
+        Some(Session {
+            open,
+            close: open + Duration::days(1),
+        })
+    }
+
+    fn periods_per_year(&self, interval: Duration) -> f64 {
+        Duration::days(365).num_seconds() as f64 / interval.num_seconds().max(1) as f64
+    }
+}
+
+/// A weekday exchange with a fixed local session, a holiday list and early closes.
+#[derive(Debug, Clone)]
+pub struct ExchangeCalendar {
+    pub name: String,
+    pub timezone: Tz,
+    pub open: NaiveTime,
+    pub close: NaiveTime,
+    pub holidays: HashSet<NaiveDate>,
+    /// Early-close days mapped to their local close time.
+    pub half_days: HashMap<NaiveDate, NaiveTime>,
+    pub trading_days_per_year: f64,
+}
+
+impl ExchangeCalendar {
+    pub fn new(name: &str, timezone: Tz, open: NaiveTime, close: NaiveTime) -> Self {
+        Self {
+            name: name.to_string(),
+            timezone,
+            open,
+            close,
+            holidays: HashSet::new(),
+            half_days: HashMap::new(),
+            trading_days_per_year: 252.0,
+        }
+    }
+
+    /// NSE cash market, 09:15–15:30 IST, with the published 2024–2025 holidays.
+    pub fn nse() -> Self {
+        let mut cal = Self::new("NSE", Tz::Asia__Kolkata, hm(9, 15), hm(15, 30));
+        cal.trading_days_per_year = 248.0;
+        cal.holidays = dates(&[
+            "2024-01-22", "2024-01-26", "2024-03-08", "2024-03-25", "2024-03-29",
+            "2024-04-11", "2024-04-17", "2024-05-01", "2024-05-20", "2024-06-17",
+            "2024-07-17", "2024-08-15", "2024-10-02", "2024-11-01", "2024-11-15",
+            "2024-11-20", "2024-12-25",
+            "2025-02-26", "2025-03-14", "2025-03-31", "2025-04-10", "2025-04-14",
+            "2025-04-18", "2025-05-01", "2025-08-15", "2025-08-27", "2025-10-02",
+            "2025-10-21", "2025-10-22", "2025-11-05", "2025-12-25",
+        ]);
+        cal
+    }
+
+    /// NYSE regular hours, 09:30–16:00 New York time, with 2024–2026 holidays and 13:00 early closes.
+    pub fn nyse() -> Self {
+        let mut cal = Self::new("NYSE", Tz::America__New_York, hm(9, 30), hm(16, 0));
+        cal.holidays = dates(&[
+            "2024-01-01", "2024-01-15", "2024-02-19", "2024-03-29", "2024-05-27",
+            "2024-06-19", "2024-07-04", "2024-09-02", "2024-11-28", "2024-12-25",
+            "2025-01-01", "2025-01-09", "2025-01-20", "2025-02-17", "2025-04-18",
+            "2025-05-26", "2025-06-19", "2025-07-04", "2025-09-01", "2025-11-27",
+            "2025-12-25",
+            "2026-01-01", "2026-01-19", "2026-02-16", "2026-04-03", "2026-05-25",
+            "2026-06-19", "2026-07-03", "2026-09-07", "2026-11-26", "2026-12-25",
+        ]);
+        cal.half_days = dates(&[
+            "2024-07-03", "2024-11-29", "2024-12-24",
+            "2025-07-03", "2025-11-28", "2025-12-24",
+            "2026-11-27", "2026-12-24",
+        ])
+        .into_iter()
+        .map(|d| (d, hm(13, 0)))
+        .collect();
+        cal
+    }
+
+    pub fn with_holiday(mut self, date: NaiveDate) -> Self {
+        self.holidays.insert(date);
+        self
+    }
+
+    pub fn with_half_day(mut self, date: NaiveDate, close: NaiveTime) -> Self {
+        self.half_days.insert(date, close);
+        self
+    }
+
+    fn to_utc(&self, date: NaiveDate, time: NaiveTime) -> Option<DateTime<Utc>> {
+        self.timezone
+            .from_local_datetime(&date.and_time(time))
+            .earliest()
+            .map(|t| t.with_timezone(&Utc))
+    }
+}
+
+impl TradingCalendar for ExchangeCalendar {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn timezone(&self) -> Tz {
+        self.timezone
+    }
+
+    fn session(&self, date: NaiveDate) -> Option<Session> {
+        if matches!(date.weekday(), Weekday::Sat | Weekday::Sun) || self.holidays.contains(&date) {
+            return None;
+        }
+        let close = self.half_days.get(&date).copied().unwrap_or(self.close);
+        Some(Session {
+            open: self.to_utc(date, self.open)?,
+            close: self.to_utc(date, close)?,
+        })
+    }
+
+    fn periods_per_year(&self, interval: Duration) -> f64 {
+        let session_secs = (self.close - self.open).num_seconds() as f64;
+        let bars_per_day = (session_secs / interval.num_seconds().max(1) as f64).ceil();
+        self.trading_days_per_year * bars_per_day
+    }
+}
+
+fn hm(hour: u32, minute: u32) -> NaiveTime {
+    NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+}
+
+fn dates(list: &[&str]) -> HashSet<NaiveDate> {
+    list.iter()
+        .map(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").unwrap())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_nse_session_in_utc() {
+        let nse = ExchangeCalendar::nse();
+        let day = NaiveDate::from_ymd_opt(2024, 10, 1).unwrap();
+        let session = nse.session(day).unwrap();
+        assert_eq!(session.open, utc("2024-10-01T03:45:00Z"));
+        assert_eq!(session.close, utc("2024-10-01T10:00:00Z"));
+        assert!(nse.is_open(utc("2024-10-01T09:59:00Z")));
+        assert!(!nse.is_open(utc("2024-10-01T10:00:00Z")));
+        assert!(!nse.is_trading_day(NaiveDate::from_ymd_opt(2024, 10, 2).unwrap()));
+    }
+
+    #[test]
+    fn test_nyse_dst_and_half_day() {
+        let nyse = ExchangeCalendar::nyse();
+        let summer = nyse.session(NaiveDate::from_ymd_opt(2025, 7, 3).unwrap()).unwrap();
+        assert_eq!(summer.open, utc("2025-07-03T13:30:00Z"));
+        assert_eq!(summer.close, utc("2025-07-03T17:00:00Z"));
+        let winter = nyse.session(NaiveDate::from_ymd_opt(2025, 12, 1).unwrap()).unwrap();
+        assert_eq!(winter.open, utc("2025-12-01T14:30:00Z"));
+        assert!(nyse.session(NaiveDate::from_ymd_opt(2025, 12, 6).unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_periods_per_year() {
+        assert_eq!(Crypto247.periods_per_year(Duration::hours(1)), 8760.0);
+        assert_eq!(ExchangeCalendar::nyse().periods_per_year(Duration::hours(1)), 252.0 * 7.0);
+        assert_eq!(ExchangeCalendar::nse().periods_per_year(Duration::days(1)), 248.0);
+    }
+}
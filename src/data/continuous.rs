@@ -0,0 +1,236 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::data::bar::Bar;
+use crate::data::feed::Symbol;
+
+/// One dated contract, e.g. `BTCUSD_250328`.
+#[derive(Debug, Clone)]
+pub struct FuturesContract {
+    pub symbol: Symbol,
+    pub expiry: DateTime<Utc>,
+    pub bars: Vec<Bar>,
+    /// Open interest aligned with `bars`; may be empty unless the roll rule needs it.
+    pub open_interest: Vec<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RollRule {
+    /// Roll once the front contract is this close to expiry.
+    DaysBeforeExpiry(i64),
+    /// Roll when the next contract trades more volume than the front one.
+    VolumeCrossover,
+    /// Roll when the next contract's open interest exceeds the front one's.
+    OpenInterestCrossover,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackAdjust {
+    /// Raw prices, with the roll gaps left in.
+    None,
+    /// Shift history by the price gap at each roll.
+    Difference,
+    /// Scale history by the price ratio at each roll; keeps returns intact.
+    Ratio,
+}
+
+#[derive(Debug, Clone)]
+pub struct RollEvent {
+    /// Index in `ContinuousSeries::bars` of the first bar from the new contract.
+    pub index: usize,
+    pub timestamp: String,
+    pub from: Symbol,
+    pub to: Symbol,
+    pub from_price: f64,
+    pub to_price: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ContinuousSeries {
+    pub bars: Vec<Bar>,
+    /// Contract each bar was taken from.
+    pub active: Vec<Symbol>,
+    pub rolls: Vec<RollEvent>,
+}
+
+impl ContinuousSeries {
+    /// Roll that takes effect at bar `index`, if any.
+    pub fn roll_at(&self, index: usize) -> Option<&RollEvent> {
+        self.rolls.iter().find(|r| r.index == index)
+    }
+}
+
+/// A contract's bar at one timestamp, with its open interest if known.
+struct Quote {
+    bar: Bar,
+    open_interest: Option<f64>,
+}
+
+/// Stitches dated contracts into one continuous series. Contracts are ordered by
+/// expiry; the front contract is always rolled by its expiry, whatever the rule.
+pub fn build_continuous(
+    mut contracts: Vec<FuturesContract>,
+    rule: RollRule,
+    adjust: BackAdjust,
+) -> Result<ContinuousSeries, Box<dyn Error + Send + Sync>> {
+    contracts.sort_by_key(|c| c.expiry);
+    if rule == RollRule::OpenInterestCrossover
+        && let Some(c) = contracts.iter().find(|c| c.open_interest.len() != c.bars.len())
+    {
+        return Err(format!("{}: open interest not aligned with bars", c.symbol).into());
+    }
+
+    let mut timeline: BTreeMap<DateTime<Utc>, Vec<Option<Quote>>> = BTreeMap::new();
+    for (idx, contract) in contracts.iter().enumerate() {
+        for (i, bar) in contract.bars.iter().enumerate() {
+            let ts = bar
+                .datetime()
+                .map_err(|e| format!("{}: bad timestamp {:?}: {}", contract.symbol, bar.timestamp, e))?;
+            let slot = timeline.entry(ts).or_insert_with(|| (0..contracts.len()).map(|_| None).collect());
+            slot[idx] = Some(Quote {
+                bar: bar.clone(),
+                open_interest: contract.open_interest.get(i).copied(),
+            });
+        }
+    }
+
+    let mut series = ContinuousSeries {
+        bars: Vec::new(),
+        active: Vec::new(),
+        rolls: Vec::new(),
+    };
+    let last_ts: Vec<Option<DateTime<Utc>>> = contracts
+        .iter()
+        .map(|c| c.bars.iter().filter_map(|b| b.datetime().ok()).max())
+        .collect();
+    let mut front = 0usize;
+    let mut last_front_close: Option<f64> = None;
+
+    for (ts, quotes) in &timeline {
+        while front + 1 < contracts.len() {
+            let next = front + 1;
+            let Some(next_q) = &quotes[next] else {
+                break;
+            };
+            let cur_q = quotes[front].as_ref();
+            // Past expiry, or past the contract's last bar: it can no longer be held.
+            let dead = *ts >= contracts[front].expiry || last_ts[front].is_none_or(|last| *ts > last);
+            let due = dead
+                || match rule {
+                    RollRule::DaysBeforeExpiry(days) => *ts >= contracts[front].expiry - Duration::days(days),
+                    RollRule::VolumeCrossover => cur_q.is_some_and(|c| next_q.bar.volume > c.bar.volume),
+                    RollRule::OpenInterestCrossover => cur_q.is_some_and(|c| {
+                        next_q.open_interest.unwrap_or(0.0) > c.open_interest.unwrap_or(0.0)
+                    }),
+                };
+            if !due {
+                break;
+            }
+            if let Some(from_price) = cur_q.map(|c| c.bar.close).or(last_front_close) {
+                series.rolls.push(RollEvent {
+                    index: series.bars.len(),
+                    timestamp: next_q.bar.timestamp.clone(),
+                    from: contracts[front].symbol.clone(),
+                    to: contracts[next].symbol.clone(),
+                    from_price,
+                    to_price: next_q.bar.close,
+                });
+            }
+            front = next;
+            last_front_close = None;
+        }
+
+        if let Some(q) = &quotes[front] {
+            series.bars.push(q.bar.clone());
+            series.active.push(contracts[front].symbol.clone());
+            last_front_close = Some(q.bar.close);
+        }
+    }
+
+    // Walk rolls newest-first so each gap is applied to everything before it.
+    for roll in series.rolls.iter().rev() {
+        for bar in &mut series.bars[..roll.index] {
+            match adjust {
+                BackAdjust::None => {}
+                BackAdjust::Difference => {
+                    let gap = roll.to_price - roll.from_price;
+                    bar.open += gap;
+                    bar.high += gap;
+                    bar.low += gap;
+                    bar.close += gap;
+                }
+                BackAdjust::Ratio => {
+                    let ratio = roll.to_price / roll.from_price;
+                    bar.open *= ratio;
+                    bar.high *= ratio;
+                    bar.low *= ratio;
+                    bar.close *= ratio;
+                }
+            }
+        }
+    }
+
+    Ok(series)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bar(hour: u32, close: f64, volume: f64) -> Bar {
+        Bar {
+            timestamp: format!("2025-03-01T{:02}:00:00+00:00", hour),
+            open: close,
+            high: close,
+            low: close,
+            close,
+            volume,
+        }
+    }
+
+    fn contracts() -> Vec<FuturesContract> {
+        let expiry = |h: u32| DateTime::parse_from_rfc3339(&format!("2025-03-01T{:02}:00:00Z", h)).unwrap().to_utc();
+        vec![
+            FuturesContract {
+                symbol: "Q2".into(),
+                expiry: expiry(23),
+                bars: vec![bar(0, 110.0, 1.0), bar(1, 111.0, 5.0), bar(2, 112.0, 50.0), bar(3, 113.0, 60.0)],
+                open_interest: vec![],
+            },
+            FuturesContract {
+                symbol: "Q1".into(),
+                expiry: expiry(3),
+                bars: vec![bar(0, 100.0, 50.0), bar(1, 101.0, 40.0), bar(2, 102.0, 10.0)],
+                open_interest: vec![],
+            },
+        ]
+    }
+
+    #[test]
+    fn test_volume_crossover_with_difference_adjust() {
+        let series = build_continuous(contracts(), RollRule::VolumeCrossover, BackAdjust::Difference).unwrap();
+        assert_eq!(series.active, vec!["Q1", "Q1", "Q2", "Q2"]);
+        let roll = series.roll_at(2).unwrap();
+        assert_eq!((roll.from_price, roll.to_price), (102.0, 112.0));
+        assert_eq!(series.bars[0].close, 110.0);
+        assert_eq!(series.bars[2].close, 112.0);
+    }
+
+    #[test]
+    fn test_days_before_expiry_with_ratio_adjust() {
+        let series = build_continuous(contracts(), RollRule::DaysBeforeExpiry(0), BackAdjust::Ratio).unwrap();
+        // Q1 expires at 03:00 and stops printing after 02:00, so the roll happens at 03:00.
+        assert_eq!(series.active, vec!["Q1", "Q1", "Q1", "Q2"]);
+        assert_eq!(series.rolls.len(), 1);
+        assert_eq!(series.rolls[0].from_price, 102.0);
+        assert_eq!(series.rolls[0].to_price, 113.0);
+    }
+
+    #[test]
+    fn test_open_interest_requires_alignment() {
+        let err = build_continuous(contracts(), RollRule::OpenInterestCrossover, BackAdjust::None);
+        assert!(err.is_err());
+    }
+}
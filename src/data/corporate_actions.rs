@@ -0,0 +1,281 @@
+use std::error::Error;
+
+use chrono::NaiveDate;
+use csv::{ReaderBuilder, Trim};
+
+use crate::data::bar::Bar;
+use crate::data::calendar::TradingCalendar;
+use crate::data::feed::Symbol;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ActionKind {
+    /// Shares held after the split per share held before (a 1:5 face-value split is 5.0).
+    Split(f64),
+    /// Shares held after the issue per share held before (a 1:1 bonus is 2.0).
+    Bonus(f64),
+    /// Cash paid per share.
+    Dividend(f64),
+}
+
+#[derive(Debug, Clone)]
+pub struct CorporateAction {
+    pub symbol: Symbol,
+    pub ex_date: NaiveDate,
+    pub kind: ActionKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdjustmentMode {
+    /// Back-adjust for splits and bonus issues only; dividends stay as price drops.
+    PriceOnly,
+    /// Also back-adjust for dividends, so the series tracks reinvested total return.
+    TotalReturn,
+}
+
+/// Corporate actions table, kept sorted by ex-date.
+#[derive(Debug, Clone, Default)]
+pub struct CorporateActions {
+    actions: Vec<CorporateAction>,
+}
+
+impl CorporateActions {
+    pub fn new(mut actions: Vec<CorporateAction>) -> Self {
+        actions.sort_by_key(|a| a.ex_date);
+        Self { actions }
+    }
+
+    /// Reads `symbol,ex_date,action,value` rows with a header line. `action` is
+    /// `split`, `bonus` or `dividend`; split and bonus values are either a
+    /// multiplier (`5`) or a ratio (`5:1` split, `1:1` bonus = one new per one held).
+    pub fn load_csv(path: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let mut rdr = ReaderBuilder::new().trim(Trim::All).from_path(path)?;
+        let mut actions = Vec::new();
+        for result in rdr.records() {
+            let record = result?;
+            if record.len() < 4 {
+                return Err(format!("{}: expected 4 columns, got {:?}", path, record).into());
+            }
+            let ex_date = NaiveDate::parse_from_str(&record[1], "%Y-%m-%d")?;
+            let value = &record[3];
+            let kind = match record[2].to_ascii_lowercase().as_str() {
+                "split" => ActionKind::Split(parse_ratio(value, false)?),
+                "bonus" => ActionKind::Bonus(parse_ratio(value, true)?),
+                "dividend" => ActionKind::Dividend(value.parse()?),
+                other => return Err(format!("{}: unknown action {:?}", path, other).into()),
+            };
+            actions.push(CorporateAction {
+                symbol: record[0].to_string(),
+                ex_date,
+                kind,
+            });
+        }
+        Ok(Self::new(actions))
+    }
+
+    pub fn for_symbol<'a>(&'a self, symbol: &'a str) -> impl Iterator<Item = &'a CorporateAction> {
+        self.actions.iter().filter(move |a| a.symbol == symbol)
+    }
+
+    /// Actions going ex on `date`, across all symbols.
+    pub fn on(&self, date: NaiveDate) -> impl Iterator<Item = &CorporateAction> {
+        self.actions.iter().filter(move |a| a.ex_date == date)
+    }
+
+    /// Shares held today per share held on `date`, from splits and bonus issues going ex after it.
+    pub fn share_factor_after(&self, symbol: &str, date: NaiveDate) -> f64 {
+        self.for_symbol(symbol)
+            .filter(|a| a.ex_date > date)
+            .map(|a| match a.kind {
+                ActionKind::Split(shares) | ActionKind::Bonus(shares) => shares,
+                ActionKind::Dividend(_) => 1.0,
+            })
+            .product()
+    }
+
+    /// Back-adjusts a raw series so the latest bars keep their traded prices.
+    /// Bars dated before an ex-date are scaled; dates are read in the calendar's time zone.
+    pub fn adjust(
+        &self,
+        symbol: &str,
+        bars: &[Bar],
+        mode: AdjustmentMode,
+        calendar: &dyn TradingCalendar,
+    ) -> Vec<Bar> {
+        let dates: Vec<Option<NaiveDate>> = bars
+            .iter()
+            .map(|b| b.datetime().ok().map(|t| calendar.local_date(t)))
+            .collect();
+
+        let mut price_factor = vec![1.0; bars.len()];
+        let mut volume_factor = vec![1.0; bars.len()];
+
+        for action in self.for_symbol(symbol) {
+            let before = |i: usize| dates[i].is_some_and(|d| d < action.ex_date);
+            let (price_mult, volume_mult) = match action.kind {
+                ActionKind::Split(shares) | ActionKind::Bonus(shares) => (1.0 / shares, shares),
+                ActionKind::Dividend(per_share) => {
+                    if mode == AdjustmentMode::PriceOnly {
+                        continue;
+                    }
+                    // Scale by the dividend's share of the last close before the ex-date.
+                    let Some(prev) = (0..bars.len()).rev().find(|&i| before(i)) else {
+                        continue;
+                    };
+                    let prev_close = bars[prev].close;
+                    if prev_close <= 0.0 || per_share >= prev_close {
+                        continue;
+                    }
+                    (1.0 - per_share / prev_close, 1.0)
+                }
+            };
+            for i in (0..bars.len()).filter(|&i| before(i)) {
+                price_factor[i] *= price_mult;
+                volume_factor[i] *= volume_mult;
+            }
+        }
+
+        bars.iter()
+            .zip(price_factor.iter().zip(&volume_factor))
+            .map(|(bar, (&pf, &vf))| Bar {
+                timestamp: bar.timestamp.clone(),
+                open: bar.open * pf,
+                high: bar.high * pf,
+                low: bar.low * pf,
+                close: bar.close * pf,
+                volume: bar.volume * vf,
+            })
+            .collect()
+    }
+}
+
+/// `"5"` → 5.0; `"a:b"` → a/b for splits, (a+b)/b for bonus issues.
+fn parse_ratio(value: &str, bonus: bool) -> Result<f64, Box<dyn Error + Send + Sync>> {
+    let ratio = match value.split_once(':') {
+        Some((a, b)) => {
+            let (a, b): (f64, f64) = (a.trim().parse()?, b.trim().parse()?);
+            if b <= 0.0 {
+                return Err(format!("bad ratio {:?}", value).into());
+            }
+            if bonus { (a + b) / b } else { a / b }
+        }
+        None => value.parse()?,
+    };
+    if ratio <= 0.0 {
+        return Err(format!("bad ratio {:?}", value).into());
+    }
+    Ok(ratio)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::calendar::ExchangeCalendar;
+    use crate::portfolio::{Fill, FillKind, Portfolio};
+    use crate::data::order::OrderSide;
+
+    fn bar(ts: &str, close: f64) -> Bar {
+        Bar {
+            timestamp: ts.to_string(),
+            open: close,
+            high: close,
+            low: close,
+            close,
+            volume: 1_000.0,
+        }
+    }
+
+    fn series() -> Vec<Bar> {
+        vec![
+            bar("2024-09-30T18:30:00+00:00", 1000.0), // 2024-10-01 IST
+            bar("2024-10-02T18:30:00+00:00", 200.0),  // 2024-10-03 IST, post split
+            bar("2024-10-03T18:30:00+00:00", 190.0),
+        ]
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn actions() -> CorporateActions {
+        CorporateActions::new(vec![
+            CorporateAction { symbol: "INFY".into(), ex_date: date("2024-10-03"), kind: ActionKind::Split(5.0) },
+            CorporateAction { symbol: "INFY".into(), ex_date: date("2024-10-04"), kind: ActionKind::Dividend(10.0) },
+        ])
+    }
+
+    #[test]
+    fn test_split_back_adjustment() {
+        let nse = ExchangeCalendar::nse();
+        let adjusted = actions().adjust("INFY", &series(), AdjustmentMode::PriceOnly, &nse);
+        assert_eq!(adjusted[0].close, 200.0);
+        assert_eq!(adjusted[0].volume, 5_000.0);
+        assert_eq!(adjusted[1].close, 200.0);
+        assert_eq!(adjusted[2].close, 190.0);
+    }
+
+    #[test]
+    fn test_total_return_adjusts_for_dividend() {
+        let nse = ExchangeCalendar::nse();
+        let adjusted = actions().adjust("INFY", &series(), AdjustmentMode::TotalReturn, &nse);
+        assert!((adjusted[1].close - 190.0).abs() < 1e-9);
+        assert!((adjusted[0].close - 190.0).abs() < 1e-9);
+        assert_eq!(adjusted[2].close, 190.0);
+    }
+
+    #[test]
+    fn test_load_csv_and_pay_dividend() {
+        let path = std::env::temp_dir().join(format!("quantx-ca-{}.csv", std::process::id()));
+        std::fs::write(
+            &path,
+            "symbol,ex_date,action,value\nINFY,2024-10-04,dividend,10\nTCS,2024-10-03,bonus,1:1\n",
+        )
+        .unwrap();
+        let table = CorporateActions::load_csv(path.to_str().unwrap()).unwrap();
+        assert_eq!(table.for_symbol("TCS").next().unwrap().kind, ActionKind::Bonus(2.0));
+
+        let mut pf = Portfolio::new(0.0);
+        pf.apply_fill(Fill {
+            timestamp: "2024-10-03T04:00:00+00:00".into(),
+            symbol: "INFY".into(),
+            side: OrderSide::Buy,
+            quantity: 50.0,
+            price: 200.0,
+            fee: 0.0,
+            kind: FillKind::Trade,
+        });
+        assert_eq!(pf.apply_corporate_actions(&table, date("2024-10-04"), None), 500.0);
+        assert_eq!(pf.cash, -10_000.0 + 500.0);
+    }
+
+    #[test]
+    fn test_dividend_before_split() {
+        let table = CorporateActions::new(vec![
+            CorporateAction { symbol: "INFY".into(), ex_date: date("2024-10-01"), kind: ActionKind::Dividend(10.0) },
+            CorporateAction { symbol: "INFY".into(), ex_date: date("2024-10-03"), kind: ActionKind::Split(5.0) },
+        ]);
+        let holding = |quantity: f64, price: f64| {
+            let mut pf = Portfolio::new(0.0);
+            pf.apply_fill(Fill {
+                timestamp: "2024-09-30T04:00:00+00:00".into(),
+                symbol: "INFY".into(),
+                side: OrderSide::Buy,
+                quantity,
+                price,
+                fee: 0.0,
+                kind: FillKind::Trade,
+            });
+            pf
+        };
+
+        // 50 back-adjusted shares were 10 real ones when the dividend went ex.
+        let mut adjusted = holding(50.0, 200.0);
+        assert_eq!(adjusted.apply_corporate_actions(&table, date("2024-10-01"), Some(AdjustmentMode::PriceOnly)), 100.0);
+        adjusted.apply_corporate_actions(&table, date("2024-10-03"), Some(AdjustmentMode::PriceOnly));
+        assert_eq!(adjusted.position("INFY"), 50.0);
+
+        let mut raw = holding(10.0, 1000.0);
+        assert_eq!(raw.apply_corporate_actions(&table, date("2024-10-01"), None), 100.0);
+        raw.apply_corporate_actions(&table, date("2024-10-03"), None);
+        assert_eq!(raw.positions["INFY"], crate::portfolio::Position { quantity: 50.0, avg_price: 200.0 });
+    }
+}
@@ -0,0 +1,513 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+
+use chrono::Duration;
+use csv::ReaderBuilder;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::data::bar::Bar;
+use crate::data::order::OrderSide;
+
+type LoadResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+/// Best bid and ask at a point in time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TopOfBook {
+    pub timestamp_ms: i64,
+    pub bid: f64,
+    pub bid_qty: f64,
+    pub ask: f64,
+    pub ask_qty: f64,
+}
+
+impl TopOfBook {
+    pub fn mid(&self) -> f64 {
+        (self.bid + self.ask) / 2.0
+    }
+
+    pub fn spread(&self) -> f64 {
+        self.ask - self.bid
+    }
+
+    pub fn spread_bps(&self) -> f64 {
+        self.spread() / self.mid() * 10_000.0
+    }
+
+    /// Price a marketable order pays: the ask for buys, the bid for sells.
+    pub fn taker_price(&self, side: OrderSide) -> f64 {
+        match side {
+            OrderSide::Buy => self.ask,
+            OrderSide::Sell => self.bid,
+        }
+    }
+}
+
+/// The top levels of a book, best first on each side: `(price, quantity)`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BookLevels {
+    pub timestamp_ms: i64,
+    pub bids: Vec<(f64, f64)>,
+    pub asks: Vec<(f64, f64)>,
+}
+
+impl BookLevels {
+    pub fn top(&self) -> Option<TopOfBook> {
+        let (&(bid, bid_qty), &(ask, ask_qty)) = (self.bids.first()?, self.asks.first()?);
+        Some(TopOfBook { timestamp_ms: self.timestamp_ms, bid, bid_qty, ask, ask_qty })
+    }
+
+    /// Average price of a market order for `quantity` walking the visible levels,
+    /// and how much of it the visible depth could fill.
+    pub fn sweep(&self, side: OrderSide, quantity: f64) -> Option<(f64, f64)> {
+        let levels = match side {
+            OrderSide::Buy => &self.asks,
+            OrderSide::Sell => &self.bids,
+        };
+        let (mut left, mut cost) = (quantity, 0.0);
+        for &(price, qty) in levels {
+            if left <= 0.0 {
+                break;
+            }
+            let take = left.min(qty);
+            cost += take * price;
+            left -= take;
+        }
+        let filled = quantity - left;
+        (filled > 0.0).then(|| (cost / filled, filled))
+    }
+
+    /// Displayed quantity a new passive order at `price` would queue behind.
+    pub fn queue_ahead(&self, side: OrderSide, price: f64) -> f64 {
+        let levels = match side {
+            OrderSide::Buy => &self.bids,
+            OrderSide::Sell => &self.asks,
+        };
+        levels.iter().filter(|(p, _)| *p == price).map(|(_, q)| q).sum()
+    }
+}
+
+/// Full-depth REST snapshot (`GET /api/v3/depth`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthSnapshot {
+    pub timestamp_ms: i64,
+    pub last_update_id: u64,
+    pub bids: Vec<(f64, f64)>,
+    pub asks: Vec<(f64, f64)>,
+}
+
+/// One `depthUpdate` diff event. A quantity of 0 removes the level.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthUpdate {
+    pub event_time: i64,
+    pub first_update_id: u64,
+    pub final_update_id: u64,
+    /// `pu` on futures streams: the previous event's final update id.
+    pub prev_final_update_id: Option<u64>,
+    pub bids: Vec<(f64, f64)>,
+    pub asks: Vec<(f64, f64)>,
+}
+
+/// Price key with a total order, so levels can live in a `BTreeMap`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Price(f64);
+
+impl Eq for Price {}
+
+impl PartialOrd for Price {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Price {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SequenceGap {
+    pub event_time: i64,
+    /// Update id the book needed next.
+    pub expected: u64,
+    /// First update id the event actually carried.
+    pub received: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApplyOutcome {
+    Applied,
+    /// Already covered by the snapshot.
+    Stale,
+    /// Waiting for a snapshot (at start or after a gap).
+    Unsynced,
+    Gap,
+}
+
+/// Rebuilds an L2 book from a snapshot plus diffs, following Binance's sync rules.
+/// After a gap the book stops applying diffs until the next snapshot.
+#[derive(Debug, Clone, Default)]
+pub struct BookReconstructor {
+    bids: BTreeMap<Price, f64>,
+    asks: BTreeMap<Price, f64>,
+    last_update_id: Option<u64>,
+    /// True until the first diff after a snapshot has been bridged.
+    awaiting_first: bool,
+    timestamp_ms: i64,
+    pub gaps: Vec<SequenceGap>,
+}
+
+impl BookReconstructor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.last_update_id.is_some()
+    }
+
+    pub fn apply_snapshot(&mut self, snapshot: &DepthSnapshot) {
+        self.bids.clear();
+        self.asks.clear();
+        set_levels(&mut self.bids, &snapshot.bids);
+        set_levels(&mut self.asks, &snapshot.asks);
+        self.last_update_id = Some(snapshot.last_update_id);
+        self.awaiting_first = true;
+        self.timestamp_ms = snapshot.timestamp_ms;
+    }
+
+    pub fn apply_update(&mut self, update: &DepthUpdate) -> ApplyOutcome {
+        let Some(last) = self.last_update_id else {
+            return ApplyOutcome::Unsynced;
+        };
+        if update.final_update_id <= last {
+            return ApplyOutcome::Stale;
+        }
+        let in_sequence = if self.awaiting_first {
+            update.first_update_id <= last + 1
+        } else {
+            match update.prev_final_update_id {
+                Some(pu) => pu == last,
+                None => update.first_update_id == last + 1,
+            }
+        };
+        if !in_sequence {
+            self.gaps.push(SequenceGap {
+                event_time: update.event_time,
+                expected: last + 1,
+                received: update.first_update_id,
+            });
+            self.last_update_id = None;
+            return ApplyOutcome::Gap;
+        }
+        set_levels(&mut self.bids, &update.bids);
+        set_levels(&mut self.asks, &update.asks);
+        self.last_update_id = Some(update.final_update_id);
+        self.awaiting_first = false;
+        self.timestamp_ms = update.event_time;
+        ApplyOutcome::Applied
+    }
+
+    pub fn levels(&self, depth: usize) -> BookLevels {
+        BookLevels {
+            timestamp_ms: self.timestamp_ms,
+            bids: self.bids.iter().rev().take(depth).map(|(p, q)| (p.0, *q)).collect(),
+            asks: self.asks.iter().take(depth).map(|(p, q)| (p.0, *q)).collect(),
+        }
+    }
+}
+
+fn set_levels(side: &mut BTreeMap<Price, f64>, levels: &[(f64, f64)]) {
+    for &(price, qty) in levels {
+        if qty == 0.0 {
+            side.remove(&Price(price));
+        } else {
+            side.insert(Price(price), qty);
+        }
+    }
+}
+
+/// Anything that can report the book as of a time, stepping forward only.
+pub trait BookSource {
+    fn advance_to(&mut self, timestamp_ms: i64) -> Option<BookLevels>;
+}
+
+#[derive(Debug, Clone)]
+pub enum DepthEvent {
+    Snapshot(DepthSnapshot),
+    Update(DepthUpdate),
+}
+
+impl DepthEvent {
+    fn time(&self) -> i64 {
+        match self {
+            DepthEvent::Snapshot(s) => s.timestamp_ms,
+            DepthEvent::Update(u) => u.event_time,
+        }
+    }
+}
+
+/// Replays snapshots and diffs in time order through a `BookReconstructor`.
+pub struct DepthReplay {
+    events: Vec<DepthEvent>,
+    next: usize,
+    depth: usize,
+    pub book: BookReconstructor,
+}
+
+impl DepthReplay {
+    /// `depth` is how many levels per side `advance_to` reports.
+    pub fn new(mut events: Vec<DepthEvent>, depth: usize) -> Self {
+        events.sort_by_key(DepthEvent::time);
+        Self { events, next: 0, depth, book: BookReconstructor::new() }
+    }
+}
+
+impl BookSource for DepthReplay {
+    fn advance_to(&mut self, timestamp_ms: i64) -> Option<BookLevels> {
+        while let Some(event) = self.events.get(self.next) {
+            if event.time() > timestamp_ms {
+                break;
+            }
+            match event {
+                DepthEvent::Snapshot(s) => self.book.apply_snapshot(s),
+                // Gaps are recorded in `book.gaps`; the book stays unsynced until the next snapshot.
+                DepthEvent::Update(u) => {
+                    self.book.apply_update(u);
+                }
+            }
+            self.next += 1;
+        }
+        self.book.is_synced().then(|| self.book.levels(self.depth))
+    }
+}
+
+/// Replays `bookTicker` quotes as a one-level book.
+pub struct TickerReplay {
+    quotes: Vec<TopOfBook>,
+    next: usize,
+    current: Option<TopOfBook>,
+}
+
+impl TickerReplay {
+    pub fn new(mut quotes: Vec<TopOfBook>) -> Self {
+        quotes.sort_by_key(|q| q.timestamp_ms);
+        Self { quotes, next: 0, current: None }
+    }
+}
+
+impl BookSource for TickerReplay {
+    fn advance_to(&mut self, timestamp_ms: i64) -> Option<BookLevels> {
+        while let Some(q) = self.quotes.get(self.next).filter(|q| q.timestamp_ms <= timestamp_ms) {
+            self.current = Some(*q);
+            self.next += 1;
+        }
+        self.current.map(|q| BookLevels {
+            timestamp_ms: q.timestamp_ms,
+            bids: vec![(q.bid, q.bid_qty)],
+            asks: vec![(q.ask, q.ask_qty)],
+        })
+    }
+}
+
+/// Pairs each bar with the book as of `bar time + lag`. Pass the bar interval as
+/// `lag` when bar timestamps are open times, so the book matches the bar's close.
+pub struct BarBookFeed<S: BookSource> {
+    bars: std::vec::IntoIter<Bar>,
+    source: S,
+    lag: Duration,
+}
+
+impl<S: BookSource> BarBookFeed<S> {
+    pub fn new(bars: Vec<Bar>, source: S, lag: Duration) -> Self {
+        Self { bars: bars.into_iter(), source, lag }
+    }
+}
+
+impl<S: BookSource> Iterator for BarBookFeed<S> {
+    type Item = (Bar, Option<BookLevels>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let bar = self.bars.next()?;
+        let book = match bar.datetime() {
+            Ok(t) => self.source.advance_to((t + self.lag).timestamp_millis()),
+            Err(_) => None,
+        };
+        Some((bar, book))
+    }
+}
+
+#[derive(Deserialize)]
+struct RawSnapshot {
+    #[serde(rename = "lastUpdateId")]
+    last_update_id: u64,
+    #[serde(rename = "E", alias = "T", default)]
+    timestamp_ms: i64,
+    bids: Vec<[String; 2]>,
+    asks: Vec<[String; 2]>,
+}
+
+#[derive(Deserialize)]
+struct RawUpdate {
+    #[serde(rename = "E")]
+    event_time: i64,
+    #[serde(rename = "U")]
+    first_update_id: u64,
+    #[serde(rename = "u")]
+    final_update_id: u64,
+    #[serde(rename = "pu", default)]
+    prev_final_update_id: Option<u64>,
+    #[serde(rename = "b")]
+    bids: Vec<[String; 2]>,
+    #[serde(rename = "a")]
+    asks: Vec<[String; 2]>,
+}
+
+fn parse_levels(raw: &[[String; 2]]) -> LoadResult<Vec<(f64, f64)>> {
+    raw.iter().map(|[p, q]| Ok((p.parse()?, q.parse()?))).collect()
+}
+
+/// Loads a depth snapshot saved from the REST endpoint.
+pub fn load_depth_snapshot(path: &str) -> LoadResult<DepthSnapshot> {
+    let raw: RawSnapshot = serde_json::from_str(&fs::read_to_string(path)?)?;
+    Ok(DepthSnapshot {
+        timestamp_ms: raw.timestamp_ms,
+        last_update_id: raw.last_update_id,
+        bids: parse_levels(&raw.bids)?,
+        asks: parse_levels(&raw.asks)?,
+    })
+}
+
+/// Loads recorded `depthUpdate` messages, one JSON object per line. Combined-stream
+/// envelopes (`{"stream": ..., "data": {...}}`) are unwrapped.
+pub fn load_depth_updates(path: &str) -> LoadResult<Vec<DepthUpdate>> {
+    let mut updates = Vec::new();
+    for line in fs::read_to_string(path)?.lines().filter(|l| !l.trim().is_empty()) {
+        let mut value: Value = serde_json::from_str(line)?;
+        if let Some(data) = value.get_mut("data") {
+            value = data.take();
+        }
+        let raw: RawUpdate = serde_json::from_value(value)?;
+        updates.push(DepthUpdate {
+            event_time: raw.event_time,
+            first_update_id: raw.first_update_id,
+            final_update_id: raw.final_update_id,
+            prev_final_update_id: raw.prev_final_update_id,
+            bids: parse_levels(&raw.bids)?,
+            asks: parse_levels(&raw.asks)?,
+        });
+    }
+    Ok(updates)
+}
+
+/// Loads a Binance `bookTicker` CSV dump:
+/// `update_id,best_bid_price,best_bid_qty,best_ask_price,best_ask_qty,transaction_time,event_time`.
+pub fn load_book_ticker(path: &str) -> LoadResult<Vec<TopOfBook>> {
+    let mut rdr = ReaderBuilder::new().has_headers(false).flexible(true).from_path(path)?;
+    let mut quotes = Vec::new();
+    for record in rdr.records() {
+        let record = record?;
+        if record.len() < 6 {
+            return Err(format!("Expected at least 6 bookTicker columns, got {}", record.len()).into());
+        }
+        let Ok(bid) = record[1].parse::<f64>() else { continue }; // header
+        quotes.push(TopOfBook {
+            timestamp_ms: record[5].parse()?,
+            bid,
+            bid_qty: record[2].parse()?,
+            ask: record[3].parse()?,
+            ask_qty: record[4].parse()?,
+        });
+    }
+    Ok(quotes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(time: i64, first: u64, last: u64, bids: &[(f64, f64)], asks: &[(f64, f64)]) -> DepthUpdate {
+        DepthUpdate {
+            event_time: time,
+            first_update_id: first,
+            final_update_id: last,
+            prev_final_update_id: None,
+            bids: bids.to_vec(),
+            asks: asks.to_vec(),
+        }
+    }
+
+    fn snapshot(time: i64, id: u64) -> DepthSnapshot {
+        DepthSnapshot {
+            timestamp_ms: time,
+            last_update_id: id,
+            bids: vec![(99.0, 1.0), (98.0, 2.0)],
+            asks: vec![(101.0, 1.0), (102.0, 3.0)],
+        }
+    }
+
+    #[test]
+    fn test_reconstructs_and_detects_gaps() {
+        let mut book = BookReconstructor::new();
+        assert_eq!(book.apply_update(&update(0, 1, 5, &[], &[])), ApplyOutcome::Unsynced);
+        book.apply_snapshot(&snapshot(0, 10));
+        assert_eq!(book.apply_update(&update(1, 5, 10, &[], &[])), ApplyOutcome::Stale);
+        assert_eq!(book.apply_update(&update(2, 8, 12, &[(99.5, 4.0)], &[(101.0, 0.0)])), ApplyOutcome::Applied);
+        let top = book.levels(5).top().unwrap();
+        assert_eq!((top.bid, top.ask), (99.5, 102.0));
+
+        assert_eq!(book.apply_update(&update(3, 15, 16, &[], &[])), ApplyOutcome::Gap);
+        assert_eq!(book.gaps[0], SequenceGap { event_time: 3, expected: 13, received: 15 });
+        assert!(!book.is_synced());
+        book.apply_snapshot(&snapshot(4, 20));
+        assert_eq!(book.apply_update(&update(5, 21, 22, &[], &[])), ApplyOutcome::Applied);
+    }
+
+    #[test]
+    fn test_loaders() {
+        let dir = std::env::temp_dir();
+        let diffs = dir.join(format!("depth_updates_{}.jsonl", std::process::id()));
+        fs::write(
+            &diffs,
+            "{\"e\":\"depthUpdate\",\"E\":1000,\"s\":\"BTCUSDT\",\"U\":11,\"u\":12,\"b\":[[\"99.5\",\"4\"]],\"a\":[]}\n\
+             {\"stream\":\"btcusdt@depth\",\"data\":{\"E\":2000,\"U\":13,\"u\":13,\"pu\":12,\"b\":[],\"a\":[[\"101\",\"0\"]]}}\n",
+        )
+        .unwrap();
+        let updates = load_depth_updates(diffs.to_str().unwrap()).unwrap();
+        assert_eq!(updates.len(), 2);
+        assert_eq!(updates[0].bids, vec![(99.5, 4.0)]);
+        assert_eq!(updates[1].prev_final_update_id, Some(12));
+
+        let ticker = dir.join(format!("book_ticker_{}.csv", std::process::id()));
+        fs::write(
+            &ticker,
+            "update_id,best_bid_price,best_bid_qty,best_ask_price,best_ask_qty,transaction_time,event_time\n\
+             1,99.0,2.0,99.2,1.0,1000,1001\n",
+        )
+        .unwrap();
+        let quotes = load_book_ticker(ticker.to_str().unwrap()).unwrap();
+        assert!((quotes[0].spread() - 0.2).abs() < 1e-9);
+        fs::remove_file(diffs).unwrap();
+        fs::remove_file(ticker).unwrap();
+    }
+
+    #[test]
+    fn test_bars_paired_with_book() {
+        let bar = |ts: &str| Bar { timestamp: ts.into(), open: 100.0, high: 100.0, low: 100.0, close: 100.0, volume: 1.0 };
+        let bars = vec![bar("1970-01-01T00:00:00Z"), bar("1970-01-01T00:00:01Z"), bar("1970-01-01T00:00:02Z")];
+        let events = vec![
+            DepthEvent::Update(update(2500, 11, 12, &[(99.5, 4.0)], &[])),
+            DepthEvent::Snapshot(snapshot(500, 10)),
+        ];
+        let feed: Vec<_> = BarBookFeed::new(bars, DepthReplay::new(events, 10), Duration::seconds(1)).collect();
+        assert!(feed[0].1.is_some());
+        assert_eq!(feed[1].1.as_ref().unwrap().bids[0], (99.0, 1.0));
+        let book = feed[2].1.as_ref().unwrap();
+        assert_eq!(book.bids[0], (99.5, 4.0));
+        assert_eq!(book.sweep(OrderSide::Buy, 2.0), Some((101.5, 2.0)));
+        assert_eq!(book.queue_ahead(OrderSide::Sell, 102.0), 3.0);
+    }
+}
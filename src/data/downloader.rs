@@ -1,9 +1,7 @@
 use reqwest;
 use zip::ZipArchive;
 use std::io::Cursor;
-use std::{fs, io::Write};
-use chrono::{Utc};
-use std::path::Path;
+use std::fs;
 
 pub async fn download_and_extract_for_date(
     symbol: &str,
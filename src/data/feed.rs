@@ -0,0 +1,193 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+
+use chrono::{DateTime, Utc};
+
+use crate::data::bar::Bar;
+
+pub type Symbol = String;
+
+/// What the feed does when a symbol has no bar at a timestamp another symbol printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissingBarPolicy {
+    /// Emit the slice with only the symbols that have a bar at that timestamp.
+    Skip,
+    /// Repeat the symbol's last close as a flat, zero-volume bar.
+    /// Symbols that have not printed their first bar yet stay absent.
+    ForwardFill,
+    /// Hold the timestamp back until every symbol has a bar for it;
+    /// timestamps that never complete are dropped.
+    Wait,
+}
+
+/// All bars sharing one timestamp, keyed by symbol.
+#[derive(Debug, Clone)]
+pub struct FeedSlice {
+    pub timestamp: DateTime<Utc>,
+    pub bars: HashMap<Symbol, Bar>,
+}
+
+/// Merges several symbols' bar series into one timestamp-ordered stream of slices.
+pub struct MultiSymbolFeed {
+    policy: MissingBarPolicy,
+    symbols: Vec<Symbol>,
+    timeline: BTreeMap<DateTime<Utc>, HashMap<Symbol, Bar>>,
+    last_seen: HashMap<Symbol, Bar>,
+}
+
+impl MultiSymbolFeed {
+    pub fn new(policy: MissingBarPolicy) -> Self {
+        Self {
+            policy,
+            symbols: Vec::new(),
+            timeline: BTreeMap::new(),
+            last_seen: HashMap::new(),
+        }
+    }
+
+    pub fn from_series(
+        series: HashMap<Symbol, Vec<Bar>>,
+        policy: MissingBarPolicy,
+    ) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let mut feed = Self::new(policy);
+        let mut symbols: Vec<_> = series.into_iter().collect();
+        symbols.sort_by(|a, b| a.0.cmp(&b.0));
+        for (symbol, bars) in symbols {
+            feed.add_symbol(&symbol, bars)?;
+        }
+        Ok(feed)
+    }
+
+    /// Adds a symbol's bars. Input order does not matter; a later bar with the same
+    /// timestamp replaces an earlier one.
+    pub fn add_symbol(&mut self, symbol: &str, bars: Vec<Bar>) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if !self.symbols.iter().any(|s| s == symbol) {
+            self.symbols.push(symbol.to_string());
+        }
+        for bar in bars {
+            let ts = bar
+                .datetime()
+                .map_err(|e| format!("{}: bad timestamp {:?}: {}", symbol, bar.timestamp, e))?;
+            self.timeline
+                .entry(ts)
+                .or_default()
+                .insert(symbol.to_string(), bar);
+        }
+        Ok(())
+    }
+
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    /// Number of distinct timestamps still queued (before the missing-bar policy is applied).
+    pub fn remaining(&self) -> usize {
+        self.timeline.len()
+    }
+}
+
+impl Iterator for MultiSymbolFeed {
+    type Item = FeedSlice;
+
+    fn next(&mut self) -> Option<FeedSlice> {
+        while let Some((timestamp, mut bars)) = self.timeline.pop_first() {
+            for (symbol, bar) in &bars {
+                self.last_seen.insert(symbol.clone(), bar.clone());
+            }
+
+            match self.policy {
+                MissingBarPolicy::Skip => {}
+                MissingBarPolicy::Wait => {
+                    if bars.len() < self.symbols.len() {
+                        continue;
+                    }
+                }
+                MissingBarPolicy::ForwardFill => {
+                    for symbol in &self.symbols {
+                        if bars.contains_key(symbol) {
+                            continue;
+                        }
+                        if let Some(prev) = self.last_seen.get(symbol) {
+                            bars.insert(
+                                symbol.clone(),
+                                Bar {
+                                    timestamp: timestamp.to_rfc3339(),
+                                    open: prev.close,
+                                    high: prev.close,
+                                    low: prev.close,
+                                    close: prev.close,
+                                    volume: 0.0,
+                                },
+                            );
+                        }
+                    }
+                }
+            }
+
+            return Some(FeedSlice { timestamp, bars });
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bar(ts: &str, close: f64) -> Bar {
+        Bar {
+            timestamp: ts.to_string(),
+            open: close,
+            high: close,
+            low: close,
+            close,
+            volume: 100.0,
+        }
+    }
+
+    fn feed(policy: MissingBarPolicy) -> MultiSymbolFeed {
+        let mut feed = MultiSymbolFeed::new(policy);
+        feed.add_symbol(
+            "BTCUSDT",
+            vec![
+                bar("2025-01-01T02:00:00+00:00", 102.0),
+                bar("2025-01-01T00:00:00+00:00", 100.0),
+            ],
+        )
+        .unwrap();
+        feed.add_symbol(
+            "ETHUSDT",
+            vec![
+                bar("2025-01-01T00:00:00+00:00", 10.0),
+                bar("2025-01-01T01:00:00+00:00", 11.0),
+                bar("2025-01-01T02:00:00+00:00", 12.0),
+            ],
+        )
+        .unwrap();
+        feed
+    }
+
+    #[test]
+    fn test_skip_emits_partial_slices_in_order() {
+        let slices: Vec<_> = feed(MissingBarPolicy::Skip).collect();
+        assert_eq!(slices.len(), 3);
+        assert!(slices.windows(2).all(|w| w[0].timestamp < w[1].timestamp));
+        assert_eq!(slices[1].bars.len(), 1);
+        assert!(slices[1].bars.contains_key("ETHUSDT"));
+    }
+
+    #[test]
+    fn test_forward_fill_repeats_last_close() {
+        let slices: Vec<_> = feed(MissingBarPolicy::ForwardFill).collect();
+        let filled = &slices[1].bars["BTCUSDT"];
+        assert_eq!(filled.close, 100.0);
+        assert_eq!(filled.volume, 0.0);
+    }
+
+    #[test]
+    fn test_wait_only_emits_complete_slices() {
+        let slices: Vec<_> = feed(MissingBarPolicy::Wait).collect();
+        assert_eq!(slices.len(), 2);
+        assert!(slices.iter().all(|s| s.bars.len() == 2));
+    }
+}
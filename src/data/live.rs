@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::time::Duration;
+
+use chrono::DateTime;
+use futures::StreamExt;
+use reqwest::Client;
+use serde_json::Value;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::data::bar::Bar;
+use crate::data::feed::Symbol;
+use crate::data::stream::{BarReceiver, BarSender, DataFeed, StreamBar};
+
+type FeedResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+#[derive(Debug, Clone)]
+pub struct KlineFeedConfig {
+    pub symbols: Vec<Symbol>,
+    /// Binance interval code, e.g. `1m`, `1h`, `1d`.
+    pub interval: String,
+    /// Stream root; the combined-stream path is appended.
+    pub ws_url: String,
+    /// REST root used for backfills.
+    pub rest_url: String,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Give up after this many failed connects in a row (`None` retries forever).
+    pub max_retries: Option<usize>,
+    /// Reconnect if the socket is silent for this long.
+    pub idle_timeout: Duration,
+}
+
+impl KlineFeedConfig {
+    pub fn new(symbols: &[&str], interval: &str) -> Self {
+        Self {
+            symbols: symbols.iter().map(|s| s.to_uppercase()).collect(),
+            interval: interval.to_string(),
+            ws_url: "wss://stream.binance.com:9443".to_string(),
+            rest_url: "https://api.binance.com".to_string(),
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            max_retries: None,
+            idle_timeout: Duration::from_secs(300),
+        }
+    }
+
+    fn stream_url(&self) -> String {
+        let streams: Vec<String> = self
+            .symbols
+            .iter()
+            .map(|s| format!("{}@kline_{}", s.to_lowercase(), self.interval))
+            .collect();
+        format!("{}/stream?streams={}", self.ws_url.trim_end_matches('/'), streams.join("/"))
+    }
+}
+
+/// Live Binance kline feed. Emits each bar once, when it closes; reconnects with
+/// exponential backoff and fills any bars missed while disconnected from REST.
+pub struct BinanceKlineFeed {
+    config: KlineFeedConfig,
+    client: Client,
+    interval_ms: i64,
+    /// Open time of the last bar sent, per symbol.
+    last_open: HashMap<Symbol, i64>,
+}
+
+impl BinanceKlineFeed {
+    pub fn new(config: KlineFeedConfig) -> FeedResult<Self> {
+        let interval_ms = interval_millis(&config.interval).ok_or_else(|| format!("Unsupported interval {}", config.interval))?;
+        Ok(Self {
+            config,
+            client: Client::new(),
+            interval_ms,
+            last_open: HashMap::new(),
+        })
+    }
+
+    async fn run(mut self, tx: BarSender) -> usize {
+        let url = self.config.stream_url();
+        let mut backoff = self.config.initial_backoff;
+        let mut failures = 0;
+        let mut sent = 0;
+        loop {
+            match connect_async(url.as_str()).await {
+                Ok((mut ws, _)) => {
+                    println!("🔌 Connected to {}", url);
+                    backoff = self.config.initial_backoff;
+                    failures = 0;
+                    loop {
+                        let msg = match tokio::time::timeout(self.config.idle_timeout, ws.next()).await {
+                            Ok(Some(Ok(msg))) => msg,
+                            Ok(Some(Err(e))) => {
+                                eprintln!("⚠️ Kline stream error: {}", e);
+                                break;
+                            }
+                            Ok(None) => break,
+                            Err(_) => {
+                                eprintln!("⚠️ Kline stream idle for {:?}, reconnecting", self.config.idle_timeout);
+                                break;
+                            }
+                        };
+                        let Message::Text(text) = msg else { continue };
+                        let Some((symbol, open_time, bar)) = parse_closed_kline(&text) else { continue };
+                        match self.deliver(&tx, symbol, open_time, bar).await {
+                            Some(n) => sent += n,
+                            None => return sent,
+                        }
+                    }
+                    eprintln!("⚠️ Kline stream disconnected");
+                }
+                Err(e) => eprintln!("⚠️ Failed to connect to kline stream: {}", e),
+            }
+            failures += 1;
+            if self.config.max_retries.is_some_and(|max| failures > max) {
+                eprintln!("⚠️ Giving up on kline stream after {} failed attempts", failures);
+                return sent;
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(self.config.max_backoff);
+        }
+    }
+
+    /// Sends `bar`, first backfilling any bars between it and the last one sent.
+    /// Returns how many went out, or `None` once the receiver has gone away.
+    async fn deliver(&mut self, tx: &BarSender, symbol: Symbol, open_time: i64, bar: Bar) -> Option<usize> {
+        let last = self.last_open.get(&symbol).copied();
+        if last.is_some_and(|l| open_time <= l) {
+            return Some(0);
+        }
+        let mut bars = Vec::new();
+        if let Some(last) = last
+            && open_time - last > self.interval_ms
+        {
+            match self.backfill(&symbol, last + self.interval_ms, open_time - 1).await {
+                Ok(missing) => {
+                    println!("🩹 Backfilled {} {} bars", missing.len(), symbol);
+                    bars.extend(missing);
+                }
+                Err(e) => eprintln!("⚠️ Backfill for {} failed: {}", symbol, e),
+            }
+        }
+        bars.push((open_time, bar));
+
+        let mut sent = 0;
+        for (time, bar) in bars {
+            if tx.send(StreamBar { symbol: symbol.clone(), bar }).await.is_err() {
+                return None;
+            }
+            self.last_open.insert(symbol.clone(), time);
+            sent += 1;
+        }
+        Some(sent)
+    }
+
+    /// Closed klines with open times in `start..=end`, paging through `/api/v3/klines`.
+    async fn backfill(&self, symbol: &str, start: i64, end: i64) -> FeedResult<Vec<(i64, Bar)>> {
+        let mut bars = Vec::new();
+        let mut from = start;
+        while from <= end {
+            let url = format!("{}/api/v3/klines", self.config.rest_url.trim_end_matches('/'));
+            let query = [
+                ("symbol", symbol.to_string()),
+                ("interval", self.config.interval.clone()),
+                ("startTime", from.to_string()),
+                ("endTime", end.to_string()),
+                ("limit", "1000".to_string()),
+            ];
+            let resp = self.client.get(url).query(&query).send().await?;
+            if !resp.status().is_success() {
+                return Err(format!("HTTP {} from klines", resp.status()).into());
+            }
+            let rows: Vec<Vec<Value>> = resp.json().await?;
+            let Some(last) = rows.last().and_then(|r| r.first()).and_then(Value::as_i64) else {
+                break;
+            };
+            for row in &rows {
+                if let Some(parsed) = parse_rest_kline(row) {
+                    bars.push(parsed);
+                }
+            }
+            from = last + self.interval_ms;
+        }
+        Ok(bars)
+    }
+}
+
+impl DataFeed for BinanceKlineFeed {
+    fn spawn(self, buffer: usize) -> (BarReceiver, JoinHandle<usize>) {
+        let (tx, rx) = mpsc::channel(buffer.max(1));
+        (rx, tokio::spawn(self.run(tx)))
+    }
+}
+
+/// Milliseconds per Binance interval code (`1M` is calendar-based and unsupported).
+pub fn interval_millis(interval: &str) -> Option<i64> {
+    let split = interval.len().checked_sub(1)?;
+    let (n, unit) = interval.split_at(split);
+    let n: i64 = n.parse().ok()?;
+    let unit_ms = match unit {
+        "s" => 1_000,
+        "m" => 60_000,
+        "h" => 3_600_000,
+        "d" => 86_400_000,
+        "w" => 7 * 86_400_000,
+        _ => return None,
+    };
+    Some(n * unit_ms)
+}
+
+fn num(v: &Value) -> Option<f64> {
+    match v {
+        Value::String(s) => s.parse().ok(),
+        other => other.as_f64(),
+    }
+}
+
+fn bar_at(open_time: i64, o: &Value, h: &Value, l: &Value, c: &Value, v: &Value) -> Option<Bar> {
+    Some(Bar {
+        timestamp: DateTime::from_timestamp_millis(open_time)?.to_rfc3339(),
+        open: num(o)?,
+        high: num(h)?,
+        low: num(l)?,
+        close: num(c)?,
+        volume: num(v)?,
+    })
+}
+
+/// `(symbol, open_time, bar)` from a kline event whose bar has closed (`"x": true`).
+/// Accepts raw and combined-stream (`{"stream", "data"}`) messages.
+fn parse_closed_kline(text: &str) -> Option<(Symbol, i64, Bar)> {
+    let mut v: Value = serde_json::from_str(text).ok()?;
+    if let Some(data) = v.get_mut("data") {
+        v = data.take();
+    }
+    let k = v.get("k")?;
+    if v["e"] != "kline" || k["x"] != true {
+        return None;
+    }
+    let open_time = k["t"].as_i64()?;
+    let bar = bar_at(open_time, &k["o"], &k["h"], &k["l"], &k["c"], &k["v"])?;
+    Some((k["s"].as_str()?.to_string(), open_time, bar))
+}
+
+/// REST kline row: `[open_time, open, high, low, close, volume, close_time, ...]`.
+fn parse_rest_kline(row: &[Value]) -> Option<(i64, Bar)> {
+    let open_time = row.first()?.as_i64()?;
+    Some((open_time, bar_at(open_time, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const MINUTE: i64 = 60_000;
+    const T0: i64 = 1_700_000_040_000;
+
+    fn kline(minute: i64, close: f64, closed: bool) -> String {
+        json!({"stream": "btcusdt@kline_1m", "data": {"e": "kline", "E": T0 + minute * MINUTE, "s": "BTCUSDT", "k": {
+            "t": T0 + minute * MINUTE, "T": T0 + (minute + 1) * MINUTE - 1, "s": "BTCUSDT", "i": "1m",
+            "o": "100.0", "h": "110.0", "l": "90.0", "c": close.to_string(), "v": "5.0", "x": closed
+        }}})
+        .to_string()
+    }
+
+    #[test]
+    fn test_interval_millis() {
+        assert_eq!(interval_millis("1m"), Some(60_000));
+        assert_eq!(interval_millis("4h"), Some(4 * 3_600_000));
+        assert_eq!(interval_millis("1M"), None);
+        assert!(parse_closed_kline(&kline(0, 1.0, false)).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reconnects_and_backfills_gap() {
+        // Websocket stand-in: the first session replays minutes 0-1 then drops,
+        // the second starts at minute 4, so minutes 2-3 must come from REST.
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let ws_url = format!("ws://{}", listener.local_addr().unwrap());
+        let sessions = vec![
+            vec![kline(0, 101.0, false), kline(0, 102.0, true), kline(1, 103.0, true)],
+            vec![kline(1, 103.0, true), kline(4, 106.0, false), kline(4, 107.0, true)],
+        ];
+        tokio::spawn(async move {
+            for recorded in sessions {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+                for msg in recorded {
+                    futures::SinkExt::send(&mut ws, Message::Text(msg)).await.unwrap();
+                }
+                let _ = futures::SinkExt::close(&mut ws).await;
+            }
+        });
+
+        let rest = MockServer::start().await;
+        let row = |minute: i64, close: &str| {
+            json!([T0 + minute * MINUTE, "100.0", "110.0", "90.0", close, "5.0", T0 + (minute + 1) * MINUTE - 1])
+        };
+        Mock::given(method("GET"))
+            .and(path("/api/v3/klines"))
+            .and(query_param("startTime", (T0 + 2 * MINUTE).to_string()))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([row(2, "104.0"), row(3, "105.0")])))
+            .expect(1)
+            .mount(&rest)
+            .await;
+
+        let mut config = KlineFeedConfig::new(&["BTCUSDT"], "1m");
+        config.ws_url = ws_url;
+        config.rest_url = rest.uri();
+        config.initial_backoff = Duration::from_millis(10);
+        config.max_retries = Some(1);
+        let (mut rx, handle) = BinanceKlineFeed::new(config).unwrap().spawn(16);
+
+        let mut closes = Vec::new();
+        while let Some(sb) = rx.recv().await {
+            assert_eq!(sb.symbol, "BTCUSDT");
+            closes.push(sb.bar.close);
+        }
+        assert_eq!(closes, vec![102.0, 103.0, 104.0, 105.0, 107.0]);
+        assert_eq!(handle.await.unwrap(), 5);
+    }
+}
@@ -1,45 +1,325 @@
 use crate::data::bar::Bar;
-use csv::ReaderBuilder;
+use crate::data::feed::Symbol;
+use csv::{ReaderBuilder, StringRecord, Trim};
+use std::collections::HashMap;
 use std::{error::Error, fs::File, io::BufReader};
-use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+type LoadResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+/// A column addressed by position or by header name (matched case-insensitively).
+#[derive(Debug, Clone)]
+pub enum Column {
+    Index(usize),
+    Name(String),
+}
+
+impl Column {
+    pub fn name(name: &str) -> Self {
+        Column::Name(name.to_string())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum TimestampFormat {
+    /// Integer epoch with the unit guessed from its magnitude.
+    /// Binance spot files switched from milliseconds to microseconds in 2025.
+    EpochAuto,
+    EpochSeconds,
+    EpochMillis,
+    EpochMicros,
+    /// A chrono format string, e.g. `%Y-%m-%d %H:%M:%S`. Date-only patterns give midnight.
+    Pattern(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct ColumnMap {
+    pub timestamp: Column,
+    pub open: Column,
+    pub high: Column,
+    pub low: Column,
+    pub close: Column,
+    pub volume: Column,
+    /// Set for files holding several symbols (e.g. a bhavcopy).
+    pub symbol: Option<Column>,
+}
+
+/// Describes how to read one CSV layout into `Bar`s.
+#[derive(Debug, Clone)]
+pub struct LoaderSpec {
+    pub delimiter: u8,
+    pub has_headers: bool,
+    pub columns: ColumnMap,
+    pub timestamp_format: TimestampFormat,
+    /// Zone of wall-clock timestamps in the file. Epoch timestamps are always UTC.
+    pub timezone: Tz,
+    /// Rows are kept only when every `(column, value)` pair matches.
+    pub filters: Vec<(Column, String)>,
+}
+
+impl LoaderSpec {
+    /// Binance kline archives: headerless, epoch open time, OHLCV in columns 1..=5.
+    pub fn binance() -> Self {
+        Self {
+            delimiter: b',',
+            has_headers: false,
+            columns: ColumnMap {
+                timestamp: Column::Index(0),
+                open: Column::Index(1),
+                high: Column::Index(2),
+                low: Column::Index(3),
+                close: Column::Index(4),
+                volume: Column::Index(5),
+                symbol: None,
+            },
+            timestamp_format: TimestampFormat::EpochAuto,
+            timezone: Tz::UTC,
+            filters: Vec::new(),
+        }
+    }
+
+    /// Yahoo Finance history download (`Date,Open,High,Low,Close,Adj Close,Volume`).
+    pub fn yahoo_finance() -> Self {
+        Self {
+            delimiter: b',',
+            has_headers: true,
+            columns: ColumnMap {
+                timestamp: Column::name("Date"),
+                open: Column::name("Open"),
+                high: Column::name("High"),
+                low: Column::name("Low"),
+                close: Column::name("Close"),
+                volume: Column::name("Volume"),
+                symbol: None,
+            },
+            timestamp_format: TimestampFormat::Pattern("%Y-%m-%d".to_string()),
+            timezone: Tz::America__New_York,
+            filters: Vec::new(),
+        }
+    }
+
+    /// NSE equity bhavcopy: one row per symbol per day, `EQ` series only.
+    pub fn nse_bhavcopy() -> Self {
+        Self {
+            delimiter: b',',
+            has_headers: true,
+            columns: ColumnMap {
+                timestamp: Column::name("TIMESTAMP"),
+                open: Column::name("OPEN"),
+                high: Column::name("HIGH"),
+                low: Column::name("LOW"),
+                close: Column::name("CLOSE"),
+                volume: Column::name("TOTTRDQTY"),
+                symbol: Some(Column::name("SYMBOL")),
+            },
+            timestamp_format: TimestampFormat::Pattern("%d-%b-%Y".to_string()),
+            timezone: Tz::Asia__Kolkata,
+            filters: vec![(Column::name("SERIES"), "EQ".to_string())],
+        }
+    }
+}
 
 pub struct CsvLoader {
     pub path: String,
+    pub spec: LoaderSpec,
 }
 
 impl CsvLoader {
     pub fn new(path: &str) -> Self {
+        Self::with_spec(path, LoaderSpec::binance())
+    }
+
+    pub fn with_spec(path: &str, spec: LoaderSpec) -> Self {
         Self {
             path: path.to_string(),
+            spec,
         }
     }
 
-    pub fn load(&self) -> Result<Vec<Bar>, Box<dyn Error + Send + Sync>> {
+    pub fn load(&self) -> LoadResult<Vec<Bar>> {
+        Ok(self.read_rows()?.into_iter().map(|(_, bar)| bar).collect())
+    }
+
+    /// Splits a multi-symbol file into one series per symbol, in file order.
+    pub fn load_by_symbol(&self) -> LoadResult<HashMap<Symbol, Vec<Bar>>> {
+        if self.spec.columns.symbol.is_none() {
+            return Err(format!("{}: loader spec has no symbol column", self.path).into());
+        }
+        let mut series: HashMap<Symbol, Vec<Bar>> = HashMap::new();
+        for (symbol, bar) in self.read_rows()? {
+            series.entry(symbol.unwrap_or_default()).or_default().push(bar);
+        }
+        Ok(series)
+    }
+
+    fn read_rows(&self) -> LoadResult<Vec<(Option<Symbol>, Bar)>> {
         let file = File::open(&self.path)?;
-        // Binance files have no header row
         let mut rdr = ReaderBuilder::new()
-            .has_headers(false)
+            .delimiter(self.spec.delimiter)
+            .has_headers(self.spec.has_headers)
+            .trim(Trim::All)
             .from_reader(BufReader::new(file));
 
-        let mut bars = Vec::new();
+        let headers = if self.spec.has_headers {
+            Some(rdr.headers()?.clone())
+        } else {
+            None
+        };
+        let resolve = |col: &Column| resolve_column(col, headers.as_ref());
+
+        let cols = &self.spec.columns;
+        let ts_idx = resolve(&cols.timestamp)?;
+        let ohlcv = [
+            resolve(&cols.open)?,
+            resolve(&cols.high)?,
+            resolve(&cols.low)?,
+            resolve(&cols.close)?,
+            resolve(&cols.volume)?,
+        ];
+        let symbol_idx = cols.symbol.as_ref().map(&resolve).transpose()?;
+        let filters = self
+            .spec
+            .filters
+            .iter()
+            .map(|(col, value)| Ok((resolve(col)?, value.as_str())))
+            .collect::<LoadResult<Vec<_>>>()?;
+
+        let mut rows = Vec::new();
         for result in rdr.records() {
             let record = result?;
-            let ts_micro: i64 = record[0].parse::<i64>()?;
-            let ts_sec = ts_micro / 1_000_000;
-            let naive = NaiveDateTime::from_timestamp_opt(ts_sec, 0).ok_or("Invalid timestamp")?;
-            let datetime = DateTime::<Utc>::from_utc(naive, Utc);
-            let timestamp = datetime.to_rfc3339();
+            if !filters.iter().all(|(idx, value)| record.get(*idx) == Some(*value)) {
+                continue;
+            }
+
+            let datetime = self.parse_timestamp(field(&record, ts_idx)?)?;
+            let mut values = [0.0; 5];
+            for (value, idx) in values.iter_mut().zip(ohlcv) {
+                *value = field(&record, idx)?.parse::<f64>()?;
+            }
+            let symbol = match symbol_idx {
+                Some(idx) => Some(field(&record, idx)?.to_string()),
+                None => None,
+            };
 
             let bar = Bar {
-                timestamp,
-                open: record[1].parse::<f64>()?,
-                high: record[2].parse::<f64>()?,
-                low: record[3].parse::<f64>()?,
-                close: record[4].parse::<f64>()?,
-                volume: record[5].parse::<f64>()?,
+                timestamp: datetime.to_rfc3339(),
+                open: values[0],
+                high: values[1],
+                low: values[2],
+                close: values[3],
+                volume: values[4],
             };
-            bars.push(bar);
+            rows.push((symbol, bar));
+        }
+        Ok(rows)
+    }
+
+    fn parse_timestamp(&self, raw: &str) -> LoadResult<DateTime<Utc>> {
+        let epoch = |unit_per_sec: i64| -> LoadResult<DateTime<Utc>> {
+            let value = raw.parse::<i64>()?;
+            let secs = value.div_euclid(unit_per_sec);
+            let nanos = (value.rem_euclid(unit_per_sec) * (1_000_000_000 / unit_per_sec)) as u32;
+            Ok(DateTime::from_timestamp(secs, nanos).ok_or("Invalid timestamp")?)
+        };
+
+        match &self.spec.timestamp_format {
+            TimestampFormat::EpochSeconds => epoch(1),
+            TimestampFormat::EpochMillis => epoch(1_000),
+            TimestampFormat::EpochMicros => epoch(1_000_000),
+            TimestampFormat::EpochAuto => {
+                let magnitude = raw.parse::<i64>()?.abs();
+                if magnitude < 100_000_000_000 {
+                    epoch(1)
+                } else if magnitude < 100_000_000_000_000 {
+                    epoch(1_000)
+                } else {
+                    epoch(1_000_000)
+                }
+            }
+            TimestampFormat::Pattern(pattern) => {
+                let naive = NaiveDateTime::parse_from_str(raw, pattern).or_else(|_| {
+                    NaiveDate::parse_from_str(raw, pattern).map(|d| d.and_hms_opt(0, 0, 0).unwrap())
+                })?;
+                let local = self
+                    .spec
+                    .timezone
+                    .from_local_datetime(&naive)
+                    .earliest()
+                    .ok_or_else(|| format!("{} does not exist in {}", raw, self.spec.timezone))?;
+                Ok(local.with_timezone(&Utc))
+            }
+        }
+    }
+}
+
+fn resolve_column(col: &Column, headers: Option<&StringRecord>) -> LoadResult<usize> {
+    match col {
+        Column::Index(idx) => Ok(*idx),
+        Column::Name(name) => {
+            let headers = headers.ok_or_else(|| format!("column {:?} needs a header row", name))?;
+            headers
+                .iter()
+                .position(|h| h.eq_ignore_ascii_case(name))
+                .ok_or_else(|| format!("column {:?} not found in header", name).into())
         }
-        Ok(bars)
+    }
+}
+
+fn field(record: &StringRecord, idx: usize) -> LoadResult<&str> {
+    record
+        .get(idx)
+        .ok_or_else(|| format!("row has no column {}", idx).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_tmp(name: &str, contents: &str) -> String {
+        let path = std::env::temp_dir().join(format!("quantx-loader-{}-{}.csv", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_binance_epoch_units() {
+        let path = write_tmp(
+            "binance",
+            "1704067200000,42283.58,42554.57,42261.02,42475.23,1271.68,0,0,0,0,0,0\n\
+             1735689600000000,93576.00,94509.42,93489.03,94401.14,755.12,0,0,0,0,0,0\n",
+        );
+        let bars = CsvLoader::new(&path).load().unwrap();
+        assert_eq!(bars[0].timestamp, "2024-01-01T00:00:00+00:00");
+        assert_eq!(bars[1].timestamp, "2025-01-01T00:00:00+00:00");
+        assert_eq!(bars[1].close, 94401.14);
+    }
+
+    #[test]
+    fn test_yahoo_headers_and_timezone() {
+        let path = write_tmp(
+            "yahoo",
+            "Date,Open,High,Low,Close,Adj Close,Volume\n2024-07-01,100,110,95,105,104.5,12000\n",
+        );
+        let bars = CsvLoader::with_spec(&path, LoaderSpec::yahoo_finance()).load().unwrap();
+        assert_eq!(bars[0].timestamp, "2024-07-01T04:00:00+00:00");
+        assert_eq!(bars[0].volume, 12000.0);
+    }
+
+    #[test]
+    fn test_bhavcopy_filters_series_and_splits_symbols() {
+        let path = write_tmp(
+            "bhav",
+            "SYMBOL,SERIES,OPEN,HIGH,LOW,CLOSE,LAST,PREVCLOSE,TOTTRDQTY,TOTTRDVAL,TIMESTAMP,TOTALTRADES,ISIN,\n\
+             INFY,EQ,1500,1520,1490,1510,1511,1498,100000,0,01-OCT-2024,10,INE009A01021,\n\
+             INFY,BE,1500,1520,1490,1510,1511,1498,5,0,01-OCT-2024,1,INE009A01021,\n\
+             TCS,EQ,4200,4250,4180,4230,4231,4190,50000,0,01-OCT-2024,10,INE467B01029,\n",
+        );
+        let series = CsvLoader::with_spec(&path, LoaderSpec::nse_bhavcopy())
+            .load_by_symbol()
+            .unwrap();
+        assert_eq!(series.len(), 2);
+        assert_eq!(series["INFY"].len(), 1);
+        assert_eq!(series["TCS"][0].timestamp, "2024-09-30T18:30:00+00:00");
     }
 }
@@ -1,4 +1,11 @@
 pub mod bar;
 pub mod order;
 pub mod loader;
-pub mod downloader;
\ No newline at end of file
+pub mod downloader;
+pub mod feed;
+pub mod calendar;
+pub mod corporate_actions;
+pub mod continuous;
+pub mod depth;
+pub mod stream;
+pub mod live;
@@ -1,4 +1,4 @@
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum OrderSide {
     Buy,
     Sell,
@@ -10,4 +10,30 @@ pub struct Order {
     pub price: f64,
     pub quantity: i64,
     pub timestamp: String,
-}
\ No newline at end of file
+}
+/// What a strategy wants without the how-much: direction, conviction in `[0, 1]` and
+/// optionally where its stop would be. A `PositionSizer` turns it into a quantity.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signal {
+    pub side: OrderSide,
+    pub conviction: f64,
+    pub price: f64,
+    pub timestamp: String,
+    pub stop: Option<f64>,
+    /// The strategy's own quantity, when it came from an `Order` (see `StrategyQuantity`).
+    pub quantity: Option<f64>,
+}
+
+impl From<Order> for Signal {
+    /// Fixed-quantity orders carry full conviction.
+    fn from(order: Order) -> Self {
+        Self {
+            side: order.side,
+            conviction: 1.0,
+            price: order.price,
+            timestamp: order.timestamp,
+            stop: None,
+            quantity: Some(order.quantity as f64),
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use chrono::{DateTime, Utc};
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio::task::JoinHandle;
+
+use crate::data::bar::Bar;
+use crate::data::feed::Symbol;
+
+/// A closed bar delivered by a live or replayed stream.
+#[derive(Debug, Clone)]
+pub struct StreamBar {
+    pub symbol: Symbol,
+    pub bar: Bar,
+}
+
+pub type BarSender = Sender<StreamBar>;
+pub type BarReceiver = Receiver<StreamBar>;
+
+/// A source of closed bars that runs on its own task and delivers them over a channel.
+pub trait DataFeed: Send + 'static {
+    /// Starts streaming. The task ends, closing the channel, when the source is
+    /// exhausted or the receiver is dropped; it returns how many bars were sent.
+    fn spawn(self, buffer: usize) -> (BarReceiver, JoinHandle<usize>);
+}
+
+/// Streams historical bars into a channel, spaced out in (scaled) real time so
+/// paper trading can be exercised without an exchange connection.
+pub struct ReplayServer {
+    symbol: Symbol,
+    bars: Vec<Bar>,
+    speed: f64,
+}
+
+impl ReplayServer {
+    pub fn new(symbol: &str, bars: Vec<Bar>) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            bars,
+            speed: f64::INFINITY,
+        }
+    }
+
+    /// Multiple of real time: 1.0 waits a full bar interval between bars, 3600.0 plays
+    /// an hour per second. Infinite (the default) or non-positive means no waiting.
+    pub fn with_speed(mut self, speed: f64) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    async fn run(self, tx: BarSender) -> usize {
+        let pace = self.speed.is_finite() && self.speed > 0.0;
+        let mut prev: Option<DateTime<Utc>> = None;
+        let mut sent = 0;
+        for bar in self.bars {
+            let time = bar.datetime().ok();
+            if pace && let (Some(prev), Some(time)) = (prev, time) {
+                let gap = (time - prev).to_std().unwrap_or_default();
+                tokio::time::sleep(gap.div_f64(self.speed)).await;
+            }
+            prev = time.or(prev);
+            if tx.send(StreamBar { symbol: self.symbol.clone(), bar }).await.is_err() {
+                break;
+            }
+            sent += 1;
+        }
+        sent
+    }
+}
+
+impl DataFeed for ReplayServer {
+    fn spawn(self, buffer: usize) -> (BarReceiver, JoinHandle<usize>) {
+        let (tx, rx) = mpsc::channel(buffer.max(1));
+        (rx, tokio::spawn(self.run(tx)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_replay_paces_bars() {
+        let bar = |ts: &str| Bar { timestamp: ts.into(), open: 1.0, high: 1.0, low: 1.0, close: 1.0, volume: 1.0 };
+        let bars = vec![bar("2024-01-01T00:00:00Z"), bar("2024-01-01T00:01:00Z"), bar("2024-01-01T00:02:00Z")];
+        let start = std::time::Instant::now();
+        let (mut rx, handle) = ReplayServer::new("BTCUSDT", bars).with_speed(1_200.0).spawn(1);
+        let mut received = Vec::new();
+        while let Some(sb) = rx.recv().await {
+            received.push(sb.bar.timestamp);
+        }
+        assert_eq!(received.len(), 3);
+        assert_eq!(handle.await.unwrap(), 3);
+        // Two one-minute gaps at 1200x: 100 ms in total.
+        assert!(start.elapsed() >= std::time::Duration::from_millis(90));
+    }
+}
@@ -0,0 +1,8 @@
+pub mod backtest;
+pub mod broker;
+pub mod data;
+pub mod optimize;
+pub mod portfolio;
+pub mod risk;
+pub mod simulation;
+pub mod strategy;
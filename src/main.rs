@@ -1,21 +1,71 @@
-mod backtest;
-mod data;
-mod simulation;
-mod strategy;
-
 use chrono::{Duration, Utc};
 use std::sync::Arc;
-use tokio::task::JoinHandle;
 
-use backtest::{backtest_single_day, backtest_ema_crossover};
-use data::{bar::Bar, downloader::download_and_extract_for_date, loader::CsvLoader};
-use simulation::run_simulation;
-use strategy::{always_buy::AlwaysBuy, always_sell::AlwaysSell};
+use quantx::backtest::analytics::{RiskConfig, RiskReport};
+use quantx::backtest::report::{BacktestReport, PerformanceMetrics};
+use quantx::backtest::backtest_ema_crossover::{self, EmaBacktestConfig, run_ema_backtest};
+use quantx::backtest::backtest_single_day;
+use quantx::backtest::multi_strategy::{MultiStrategyConfig, run_multi_strategy};
+use quantx::backtest::monte_carlo::{
+    MonteCarloConfig, bootstrap_returns, perturb_costs, reshuffle_trades, skip_trades,
+};
+use quantx::broker::{paper::PaperBroker, run_live};
+use quantx::data::{
+    bar::{Bar, infer_interval},
+    calendar::{Crypto247, TradingCalendar},
+    downloader::download_and_extract_for_date,
+    live::{BinanceKlineFeed, KlineFeedConfig},
+    loader::{Column, ColumnMap, CsvLoader, LoaderSpec, TimestampFormat},
+    stream::{DataFeed, ReplayServer},
+};
+use quantx::optimize::grid::{grid_search, write_heatmaps, write_results_csv};
+use quantx::optimize::walk_forward::{WalkForwardConfig, WindowMode, walk_forward};
+use quantx::optimize::{BacktestFn, Objective, ParamSet, ParamSpace};
+use quantx::portfolio::allocation::AllocationScheme;
+use quantx::portfolio::margin::MarginConfig;
+use quantx::portfolio::optimization::ReturnMatrix;
+use quantx::portfolio::rebalance::RebalanceConfig;
+use quantx::risk::sizing::FixedQuantity;
+use quantx::risk::{RiskLimits, RiskManager};
+use quantx::simulation::run_simulation;
+use quantx::strategy::{self, always_buy::AlwaysBuy, always_sell::AlwaysSell};
 
 #[tokio::main]
 async fn main() {
-    // run_sync_backtest().await;
-    run_continous_backtest().await;
+    match std::env::args().nth(1).as_deref() {
+        Some("daily") => run_sync_backtest().await,
+        Some("simulate") => run_local_simulations(),
+        Some("optimize") => run_optimization().await,
+        Some("walkforward") => run_walk_forward().await,
+        Some("montecarlo") => run_monte_carlo().await,
+        Some("paper") => run_paper_trading().await,
+        Some("allocate") => run_allocation().await,
+        _ => run_continous_backtest().await,
+    }
+}
+
+/// Replays the bundled NSE sample (`data/historical_data.csv`, IST wall-clock times).
+fn run_local_simulations() {
+    let spec = LoaderSpec {
+        delimiter: b',',
+        has_headers: true,
+        columns: ColumnMap {
+            timestamp: Column::name("timestamp"),
+            open: Column::name("open"),
+            high: Column::name("high"),
+            low: Column::name("low"),
+            close: Column::name("close"),
+            volume: Column::name("volume"),
+            symbol: None,
+        },
+        timestamp_format: TimestampFormat::Pattern("%Y-%m-%dT%H:%M:%S".to_string()),
+        timezone: chrono_tz::Asia::Kolkata,
+        filters: Vec::new(),
+    };
+    match CsvLoader::with_spec("data/historical_data.csv", spec).load() {
+        Ok(bars) => run_strategy_simulations(Arc::new(bars)),
+        Err(e) => eprintln!("⚠️ Failed to load sample data: {}", e),
+    }
 }
 
 fn run_strategy_simulations(bars: Arc<Vec<Bar>>) {
@@ -47,10 +97,8 @@ async fn run_sync_backtest() {
     let sell = Arc::new(AlwaysSell);
     let strategies: Vec<Arc<dyn strategy::Strategy>> = vec![buy.clone(), sell.clone()];
 
-    let mut results: Vec<backtest_single_day::DailyResult> = Vec::new();
-
     let sem = Arc::new(tokio::sync::Semaphore::new(concurrency));
-    let mut handles: Vec<JoinHandle<Option<backtest_single_day::DailyResult>>> = Vec::new();
+    let mut handles = Vec::new();
 
     for days_ago in 1..=days {
         let permit = Arc::clone(&sem).acquire_owned().await.unwrap();
@@ -60,48 +108,64 @@ async fn run_sync_backtest() {
             .format("%Y-%m-%d")
             .to_string();
 
-        let strategies_refs = strategies.clone();
-
         let handle = tokio::spawn(async move {
             let _permit = permit;
-            if let Ok(csv_path) = download_and_extract_for_date(&s, &i, &date).await {
-                let csv_path_for_load = csv_path.clone();
-                if let Ok(Ok(bars)) = tokio::task::spawn_blocking(move || {
-                    let loader = CsvLoader::new(&csv_path_for_load);
-                    loader.load()
-                })
-                .await
-                {
-                    let result =
-                        backtest_single_day::backtest_single_day(&strategies_refs, &bars, &date);
-
-                    let _ = tokio::fs::remove_file(&csv_path).await;
-                    let zip_path = format!("data/market_data/{}-{}-{}.zip", s, i, date);
-                    let _ = tokio::fs::remove_file(zip_path).await;
-                    Some(result)
-                } else {
-                    None
-                }
-            } else {
-                None
+            let csv_path = download_and_extract_for_date(&s, &i, &date).await.ok()?;
+            let csv_path_for_load = csv_path.clone();
+            let bars = tokio::task::spawn_blocking(move || {
+                let loader = CsvLoader::new(&csv_path_for_load);
+                loader.load()
+            })
+            .await;
+
+            let _ = tokio::fs::remove_file(&csv_path).await;
+            let zip_path = format!("data/market_data/{}-{}-{}.zip", s, i, date);
+            let _ = tokio::fs::remove_file(zip_path).await;
+            match bars {
+                Ok(Ok(bars)) => Some((date, bars)),
+                _ => None,
             }
         });
         handles.push(handle);
     }
 
+    let mut days_data = Vec::new();
     for h in handles {
-        if let Ok(opt) = h.await {
-            if let Some(dr) = opt {
-                results.push(dr);
-            }
+        if let Ok(Some(day)) = h.await {
+            days_data.push(day);
         }
     }
+    days_data.sort_by(|a, b| a.0.cmp(&b.0));
+
+    // Days run in order against one risk manager and carry cash forward, so the
+    // drawdown kill switch sees the whole run rather than resetting every day.
+    let mut risk = RiskManager::new(
+        RiskLimits::default()
+            .with_max_position(5.0)
+            .with_max_drawdown(0.1),
+    );
+    let mut cash = backtest_single_day::STARTING_CASH;
+    let mut results: Vec<backtest_single_day::DailyResult> = Vec::new();
+    for (date, bars) in &days_data {
+        let result = backtest_single_day::backtest_single_day(
+            &strategies,
+            symbol,
+            bars,
+            date,
+            cash,
+            &Crypto247,
+            &mut risk,
+        );
+        cash += result.pnl;
+        results.push(result);
+    }
 
     let total_days = results.len();
     let total_pnl: f64 = results.iter().map(|r| r.pnl).sum();
     let wins = results.iter().filter(|r| r.pnl > 0.0).count();
     let losses = results.iter().filter(|r| r.pnl < 0.0).count();
     let total_trades: usize = results.iter().map(|r| r.trades).sum();
+    let total_rejected: usize = results.iter().map(|r| r.rejected).sum();
 
     println!("\n=== Backtest Summary (aggregated) ===");
     println!("Days processed: {}", total_days);
@@ -109,15 +173,41 @@ async fn run_sync_backtest() {
     println!("Losing days: {}", losses);
     println!("Total PnL: {:.4}", total_pnl);
     println!("Total trades: {}", total_trades);
+    println!("Rejected signals: {}", total_rejected);
+    if risk.is_halted() {
+        println!("🛑 Kill switch tripped; trading stopped early");
+    }
+
+    // Daily equity; every day ends flat, so there are no positions to value.
+    let starting_cash = backtest_single_day::STARTING_CASH;
+    let equity_curve: Vec<f64> = results
+        .iter()
+        .scan(starting_cash, |equity, r| {
+            *equity += r.pnl;
+            Some(*equity)
+        })
+        .collect();
+    let mut full = vec![starting_cash];
+    full.extend_from_slice(&equity_curve);
+    let periods_per_year = Crypto247.periods_per_year(Duration::days(1));
+    let report = BacktestReport {
+        starting_cash,
+        final_equity: equity_curve.last().copied().unwrap_or(starting_cash),
+        metrics: PerformanceMetrics::from_equity(&full, periods_per_year),
+        equity_curve,
+        timestamps: results.iter().map(|r| r.date.clone()).collect(),
+        trades: total_trades,
+        ..Default::default()
+    };
+    export_risk(&report, None, periods_per_year, Vec::new(), "daily_risk");
 }
 
-async fn run_continous_backtest() {
-    let symbol = "BTCUSDT";
-    let interval = "1h";
-    let days = 365 * 2 ;
+/// Downloads the last `days` daily kline files and merges them into one chronological series.
+/// Returns the bars and the CSV paths so callers can clean up afterwards.
+async fn download_bars(symbol: &str, interval: &str, days: usize) -> (Vec<Bar>, Vec<String>) {
     let concurrency = 30usize;
 
-    println!("🚀 Downloading ~{} days (≈2 years) of {} {} data...", days, symbol, interval);
+    println!("🚀 Downloading ~{} days of {} {} data...", days, symbol, interval);
 
     let sem = Arc::new(tokio::sync::Semaphore::new(concurrency));
     let mut handles = Vec::new();
@@ -132,11 +222,7 @@ async fn run_continous_backtest() {
 
         let handle = tokio::spawn(async move {
             let _permit = permit;
-            if let Ok(csv_path) = download_and_extract_for_date(&s, &i, &date).await {
-                Some(csv_path)
-            } else {
-                None
-            }
+            download_and_extract_for_date(&s, &i, &date).await.ok()
         });
 
         handles.push(handle);
@@ -164,15 +250,279 @@ async fn run_continous_backtest() {
         }
     }
 
-    println!("📊 Loaded {} bars total — running EMA backtest...", all_bars.len());
-    backtest_ema_crossover::continuous_backtest(&all_bars);
+    (all_bars, all_csvs)
+}
 
+async fn cleanup_csvs(all_csvs: &[String]) {
     println!("🧹 Cleaning up files...");
-    for csv in &all_csvs {
+    for csv in all_csvs {
         let zip = csv.replace(".csv", ".zip");
         let _ = tokio::fs::remove_file(csv).await;
         let _ = tokio::fs::remove_file(zip).await;
     }
+}
+
+/// The default command: the EMA switch on cash, long-only. `--margin` trades it long
+/// and short on 2x cross margin instead, and `--max-drawdown <fraction>` adds a
+/// drawdown kill switch.
+async fn run_continous_backtest() {
+    let mut config = EmaBacktestConfig::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--margin" => config.margin = Some(MarginConfig { leverage: 2.0, ..Default::default() }),
+            "--max-drawdown" => match args.next().and_then(|v| v.parse().ok()) {
+                Some(limit) => config.risk = config.risk.with_max_drawdown(limit),
+                None => eprintln!("⚠️ --max-drawdown needs a fraction, e.g. 0.25"),
+            },
+            other => eprintln!("⚠️ Ignoring unknown argument {}", other),
+        }
+    }
+
+    let (all_bars, all_csvs) = download_bars("BTCUSDT", "1h", 365 * 2).await;
 
+    println!("📊 Loaded {} bars total — running EMA backtest...", all_bars.len());
+    let report = backtest_ema_crossover::continuous_backtest("BTCUSDT", &all_bars, &config, &Crypto247);
+    export_risk(&report, Some(&all_bars), bar_periods(&all_bars), Vec::new(), "continuous_risk");
+
+    cleanup_csvs(&all_csvs).await;
     println!("✅ Continuous EMA crossover backtest completed.");
 }
+
+/// Grid-searches the EMA switch periods (in hours) and writes the ranked table and heatmap.
+async fn run_optimization() {
+    let (all_bars, all_csvs) = download_bars("BTCUSDT", "1h", 365 * 2).await;
+    let bars = Arc::new(all_bars);
+
+    let space = ParamSpace::new()
+        .range("ema_short", 3.0 * 24.0, 15.0 * 24.0, 24.0)
+        .range("ema_long", 10.0 * 24.0, 40.0 * 24.0, 48.0)
+        .with_constraint(|p| p["ema_short"] < p["ema_long"]);
+    let base = EmaBacktestConfig::default();
+    let backtest: BacktestFn = Arc::new(move |params: &ParamSet, bars: &[Bar]| {
+        run_ema_backtest("BTCUSDT", bars, &base.with_params(params), &Crypto247, false)
+    });
+
+    println!("🔍 Running {} backtests over {} bars...", space.grid().len(), bars.len());
+    let results = grid_search(Arc::clone(&bars), &space, Objective::Sharpe, backtest).await;
+
+    println!("\n=== Top parameter sets by {} ===", Objective::Sharpe.name());
+    for r in results.iter().take(10) {
+        println!(
+            "{:?} | Sharpe {:.2} | Return {:.2}% | MaxDD {:.2}% | Trades {}",
+            r.params,
+            r.metrics.sharpe,
+            r.metrics.total_return * 100.0,
+            r.metrics.max_drawdown * 100.0,
+            r.trades
+        );
+    }
+
+    if let Err(e) = write_results_csv("optimization_results.csv", &space, &results) {
+        eprintln!("⚠️ Failed to write results: {}", e);
+    }
+    match write_heatmaps("optimization_heatmap", &space, &results) {
+        Ok(paths) => println!("🗺️  Heatmaps: {}", paths.join(", ")),
+        Err(e) => eprintln!("⚠️ Failed to write heatmaps: {}", e),
+    }
+
+    cleanup_csvs(&all_csvs).await;
+}
+
+/// Walk-forward test of the EMA switch: 6 months in-sample, 1 month out-of-sample, rolling.
+async fn run_walk_forward() {
+    let (all_bars, all_csvs) = download_bars("BTCUSDT", "1h", 365 * 2).await;
+    let bars = Arc::new(all_bars);
+
+    let space = ParamSpace::new()
+        .range("ema_short", 3.0 * 24.0, 15.0 * 24.0, 48.0)
+        .range("ema_long", 10.0 * 24.0, 40.0 * 24.0, 96.0)
+        .with_constraint(|p| p["ema_short"] < p["ema_long"]);
+    let config = WalkForwardConfig {
+        in_sample: 180 * 24,
+        out_of_sample: 30 * 24,
+        mode: WindowMode::Rolling,
+        objective: Objective::Sharpe,
+        warmup: 40 * 24,
+    };
+    let base = EmaBacktestConfig::default();
+    let backtest: BacktestFn = Arc::new(move |params: &ParamSet, bars: &[Bar]| {
+        run_ema_backtest("BTCUSDT", bars, &base.with_params(params), &Crypto247, false)
+    });
+    let periods_per_year = Crypto247.periods_per_year(Duration::hours(1));
+
+    match walk_forward(Arc::clone(&bars), &space, &config, backtest, periods_per_year).await {
+        Ok(result) => {
+            println!("\n=== Walk-forward ({} windows) ===", result.windows.len());
+            for w in &result.windows {
+                println!(
+                    "OOS bars {:>6}..{:<6} {:?} | IS {:.2} | OOS {:.2} | OOS return {:.2}%",
+                    w.out_of_sample.start,
+                    w.out_of_sample.end,
+                    w.params,
+                    w.in_sample_score,
+                    w.out_of_sample_score,
+                    w.out_of_sample_metrics.total_return * 100.0
+                );
+            }
+            for s in &result.stability {
+                println!(
+                    "{}: mean {:.1} std {:.1} range {:.0}..{:.0} changes {}",
+                    s.name, s.mean, s.std, s.min, s.max, s.changes
+                );
+            }
+            println!(
+                "Combined OOS: return {:.2}% | Sharpe {:.2} | MaxDD {:.2}%",
+                result.metrics.total_return * 100.0,
+                result.metrics.sharpe,
+                result.metrics.max_drawdown * 100.0
+            );
+            let _ = result.write_windows_csv("walkforward_windows.csv");
+            let _ = result.write_equity_csv("walkforward_equity.csv");
+        }
+        Err(e) => eprintln!("⚠️ Walk-forward failed: {}", e),
+    }
+
+    cleanup_csvs(&all_csvs).await;
+}
+
+/// Risk profile of `report`, against buy-and-hold `bars` when given, printed and written
+/// to `<stem>.json` and `<stem>.csv`. Every command trades the one symbol, so `bars`
+/// also price the VaR of any positions left open.
+fn export_risk(
+    report: &BacktestReport,
+    bars: Option<&[Bar]>,
+    periods_per_year: f64,
+    correlations: Vec<(String, String, Vec<Option<f64>>)>,
+    stem: &str,
+) {
+    let config = RiskConfig { periods_per_year, ..Default::default() };
+    let mut risk = RiskReport::from_report(report, bars, &config).with_correlations(correlations);
+    if let Some(bars) = bars
+        && let Some(last) = bars.last()
+        && !report.positions.is_empty()
+    {
+        let series = report.positions.keys().map(|s| (s.clone(), bars.to_vec())).collect();
+        let prices = report.positions.keys().map(|s| (s.clone(), last.close)).collect();
+        match ReturnMatrix::from_bars(series) {
+            Ok(history) => risk = risk.with_position_var(&report.positions, &prices, &history, &config),
+            Err(e) => eprintln!("⚠️ No history for position VaR: {}", e),
+        }
+    }
+    risk.print_summary();
+    let (json, csv) = (format!("{}.json", stem), format!("{}.csv", stem));
+    if let Err(e) = risk.write_json(&json).and_then(|_| risk.write_csv(&csv)) {
+        eprintln!("⚠️ Failed to write risk report: {}", e);
+    }
+}
+
+fn bar_periods(bars: &[Bar]) -> f64 {
+    infer_interval(bars).map_or(252.0, |i| Crypto247.periods_per_year(i))
+}
+
+/// Robustness check of the default EMA switch backtest, with its risk profile against
+/// buy-and-hold BTC exported to `risk_report.json` and `risk_report.csv`.
+async fn run_monte_carlo() {
+    let (all_bars, all_csvs) = download_bars("BTCUSDT", "1h", 365 * 2).await;
+
+    let config = EmaBacktestConfig::default();
+    let report = run_ema_backtest("BTCUSDT", &all_bars, &config, &Crypto247, false);
+    println!(
+        "Base run: return {:.2}% | MaxDD {:.2}% | {} closed trades",
+        report.metrics.total_return * 100.0,
+        report.metrics.max_drawdown * 100.0,
+        report.trade_pnls.len()
+    );
+    export_risk(&report, Some(&all_bars), bar_periods(&all_bars), Vec::new(), "risk_report");
+
+    let mc = MonteCarloConfig::default();
+    reshuffle_trades(&report, &mc).print_summary("trade reshuffle", 0.95);
+    skip_trades(&report, 0.1, &mc).print_summary("skip 10% of trades", 0.95);
+    bootstrap_returns(&report, 24, &mc).print_summary("block bootstrap (24 bars)", 0.95);
+    let costs = MonteCarloConfig { runs: 100, ..mc };
+    perturb_costs(0.5, &costs, |fee_mult, slip_mult| {
+        let perturbed = EmaBacktestConfig {
+            commission_rate: config.commission_rate * fee_mult,
+            slippage_rate: config.slippage_rate * slip_mult,
+            ..config.clone()
+        };
+        run_ema_backtest("BTCUSDT", &all_bars, &perturbed, &Crypto247, false)
+    })
+    .print_summary("fees/slippage ±50%", 0.95);
+
+    cleanup_csvs(&all_csvs).await;
+}
+
+/// Gives AlwaysBuy and AlwaysSell their own sub-accounts under each allocation scheme.
+async fn run_allocation() {
+    let (all_bars, all_csvs) = download_bars("BTCUSDT", "1h", 90).await;
+    let strategies: Vec<(String, Arc<dyn strategy::Strategy>)> = vec![
+        ("AlwaysBuy".to_string(), Arc::new(AlwaysBuy)),
+        ("AlwaysSell".to_string(), Arc::new(AlwaysSell)),
+    ];
+    let schemes = [
+        ("equal", "equal", AllocationScheme::Equal),
+        ("fixed 70/30", "fixed", AllocationScheme::Fixed(vec![0.7, 0.3])),
+        ("inverse vol", "inverse_vol", AllocationScheme::InverseVolatility),
+        ("risk parity", "risk_parity", AllocationScheme::RiskParity),
+    ];
+    for (label, stem, scheme) in schemes {
+        println!("\n📊 Allocation: {}", label);
+        let config = MultiStrategyConfig { scheme, ..Default::default() };
+        let report = run_multi_strategy(&strategies, "BTCUSDT", &all_bars, &config, &Crypto247);
+        report.print_summary();
+        let correlations = report.rolling_correlations(RiskConfig::default().window);
+        export_risk(
+            &report.combined,
+            Some(&all_bars),
+            bar_periods(&all_bars),
+            correlations,
+            &format!("allocation_{}_risk", stem),
+        );
+    }
+    cleanup_csvs(&all_csvs).await;
+}
+
+/// Paper-trades Always Buy on BTCUSDT. `paper live` streams 1m klines from Binance;
+/// otherwise hourly bars are replayed, with the optional second argument as the replay
+/// speed in multiples of real time (default: one bar per second).
+async fn run_paper_trading() {
+    let arg = std::env::args().nth(2);
+    if arg.as_deref() == Some("live") {
+        match BinanceKlineFeed::new(KlineFeedConfig::new(&["BTCUSDT"], "1m")) {
+            Ok(feed) => paper_trade(feed).await,
+            Err(e) => eprintln!("⚠️ Failed to start live feed: {}", e),
+        }
+        return;
+    }
+
+    let speed = arg.and_then(|s| s.parse().ok()).unwrap_or(3600.0);
+    let (all_bars, all_csvs) = download_bars("BTCUSDT", "1h", 3).await;
+    println!("📡 Replaying {} bars at {}x into the paper broker...", all_bars.len(), speed);
+    paper_trade(ReplayServer::new("BTCUSDT", all_bars).with_speed(speed)).await;
+    cleanup_csvs(&all_csvs).await;
+}
+
+async fn paper_trade(feed: impl DataFeed) {
+    let (rx, _feed) = feed.spawn(64);
+    let mut broker = PaperBroker::new(1_000_000.0);
+    let mut risk = RiskManager::new(
+        RiskLimits::default()
+            .with_max_position(5.0)
+            .with_max_open_orders(2)
+            .with_max_drawdown(0.1),
+    );
+    match run_live(&mut broker, &AlwaysBuy, &FixedQuantity(1.0), &RebalanceConfig::default(), rx, &mut risk).await {
+        Ok(summary) => {
+            println!("\n=== Paper Trading Summary ===");
+            println!("Bars: {} | Orders: {} | Rejected: {} | Fills: {}", summary.bars, summary.orders, summary.rejected, summary.fills.len());
+            println!("Cash: {:.2} | Equity: {:.2}", summary.account.cash, summary.account.equity);
+            for (symbol, qty) in &summary.account.positions {
+                println!("Position {}: {}", symbol, qty);
+            }
+            println!("Fees paid: {:.2}", broker.portfolio().fees_paid);
+            println!("Risk events: {}", summary.risk_events.len());
+        }
+        Err(e) => eprintln!("⚠️ Paper trading stopped: {}", e),
+    }
+}
@@ -0,0 +1,172 @@
+use std::error::Error;
+use std::sync::Arc;
+
+use csv::Writer;
+
+use crate::data::bar::Bar;
+use crate::optimize::{BacktestFn, Objective, ParamSpace, TrialResult, evaluate_all, rank};
+
+/// Exhaustive search: backtests every valid combination in `space`, best first.
+pub async fn grid_search(
+    bars: Arc<Vec<Bar>>,
+    space: &ParamSpace,
+    objective: Objective,
+    backtest: BacktestFn,
+) -> Vec<TrialResult> {
+    let mut results = evaluate_all(bars, space.grid(), objective, backtest).await;
+    rank(&mut results);
+    results
+}
+
+/// Writes one row per trial: parameters, then score and metrics.
+pub fn write_results_csv(
+    path: &str,
+    space: &ParamSpace,
+    results: &[TrialResult],
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut wtr = Writer::from_path(path)?;
+    let mut header: Vec<String> = space.names().iter().map(|s| s.to_string()).collect();
+    header.extend(
+        [
+            "score",
+            "total_return",
+            "annualized_return",
+            "sharpe",
+            "max_drawdown",
+            "trades",
+            "final_equity",
+        ]
+        .map(String::from),
+    );
+    wtr.write_record(&header)?;
+
+    for r in results {
+        let mut row: Vec<String> = space
+            .names()
+            .iter()
+            .map(|name| r.params.get(*name).map_or(String::new(), |v| v.to_string()))
+            .collect();
+        row.extend([
+            r.score.to_string(),
+            r.metrics.total_return.to_string(),
+            r.metrics.annualized_return.to_string(),
+            r.metrics.sharpe.to_string(),
+            r.metrics.max_drawdown.to_string(),
+            r.trades.to_string(),
+            r.final_equity.to_string(),
+        ]);
+        wtr.write_record(&row)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/// Score surface over two parameters. Each cell holds the best score across
+/// all other parameters; `None` where no trial landed.
+#[derive(Debug, Clone)]
+pub struct Heatmap {
+    pub x_param: String,
+    pub y_param: String,
+    pub x_values: Vec<f64>,
+    pub y_values: Vec<f64>,
+    /// Indexed `[y][x]`.
+    pub cells: Vec<Vec<Option<f64>>>,
+}
+
+pub fn heatmap(results: &[TrialResult], x_param: &str, y_param: &str) -> Heatmap {
+    let axis = |name: &str| {
+        let mut values: Vec<f64> = results.iter().filter_map(|r| r.params.get(name).copied()).collect();
+        values.sort_by(f64::total_cmp);
+        values.dedup();
+        values
+    };
+    let x_values = axis(x_param);
+    let y_values = axis(y_param);
+    let mut cells = vec![vec![None; x_values.len()]; y_values.len()];
+
+    for r in results.iter().filter(|r| !r.score.is_nan()) {
+        let (Some(x), Some(y)) = (r.params.get(x_param), r.params.get(y_param)) else {
+            continue;
+        };
+        let xi = x_values.iter().position(|v| v == x).unwrap();
+        let yi = y_values.iter().position(|v| v == y).unwrap();
+        let cell = &mut cells[yi][xi];
+        *cell = Some(cell.map_or(r.score, |best: f64| best.max(r.score)));
+    }
+
+    Heatmap {
+        x_param: x_param.to_string(),
+        y_param: y_param.to_string(),
+        x_values,
+        y_values,
+        cells,
+    }
+}
+
+impl Heatmap {
+    /// Matrix layout: first row holds the x values, first column the y values.
+    pub fn write_csv(&self, path: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut wtr = Writer::from_path(path)?;
+        let mut header = vec![format!("{}\\{}", self.y_param, self.x_param)];
+        header.extend(self.x_values.iter().map(|v| v.to_string()));
+        wtr.write_record(&header)?;
+        for (y, row) in self.y_values.iter().zip(&self.cells) {
+            let mut record = vec![y.to_string()];
+            record.extend(row.iter().map(|c| c.map_or(String::new(), |v| v.to_string())));
+            wtr.write_record(&record)?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+/// Writes `{prefix}_{x}_{y}.csv` for every pair of parameters in `space`; returns the paths.
+pub fn write_heatmaps(
+    prefix: &str,
+    space: &ParamSpace,
+    results: &[TrialResult],
+) -> Result<Vec<String>, Box<dyn Error + Send + Sync>> {
+    let names = space.names();
+    let mut paths = Vec::new();
+    for (i, x) in names.iter().enumerate() {
+        for y in &names[i + 1..] {
+            let path = format!("{}_{}_{}.csv", prefix, x, y);
+            heatmap(results, x, y).write_csv(&path)?;
+            paths.push(path);
+        }
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtest::report::{BacktestReport, PerformanceMetrics};
+    use crate::optimize::ParamSet;
+
+    fn fake_backtest() -> BacktestFn {
+        Arc::new(|params: &ParamSet, bars: &[Bar]| BacktestReport {
+            trades: bars.len(),
+            metrics: PerformanceMetrics {
+                sharpe: params["a"] - (params["b"] - 2.0).abs(),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_grid_search_ranks_by_objective() {
+        let space = ParamSpace::new().range("a", 1.0, 3.0, 1.0).range("b", 1.0, 3.0, 1.0);
+        let results = grid_search(Arc::new(Vec::new()), &space, Objective::Sharpe, fake_backtest()).await;
+        assert_eq!(results.len(), 9);
+        assert_eq!(results[0].params["a"], 3.0);
+        assert_eq!(results[0].params["b"], 2.0);
+        assert!(results.windows(2).all(|w| w[0].score >= w[1].score));
+
+        let map = heatmap(&results, "a", "b");
+        assert_eq!(map.x_values, vec![1.0, 2.0, 3.0]);
+        assert_eq!(map.cells[1][2], Some(3.0));
+        assert_eq!(map.cells[0][0], Some(0.0));
+    }
+}
@@ -0,0 +1,208 @@
+pub mod grid;
+pub mod search;
+pub mod walk_forward;
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::backtest::report::{BacktestReport, PerformanceMetrics, non_finite};
+use crate::data::bar::Bar;
+
+/// One point in a parameter space, keyed by parameter name.
+pub type ParamSet = BTreeMap<String, f64>;
+
+/// Runs one backtest for a parameter set over a bar slice.
+pub type BacktestFn = Arc<dyn Fn(&ParamSet, &[Bar]) -> BacktestReport + Send + Sync>;
+
+#[derive(Debug, Clone)]
+pub enum ParamValues {
+    /// `start..=end` in steps of `step`.
+    Range { start: f64, end: f64, step: f64 },
+    List(Vec<f64>),
+}
+
+impl ParamValues {
+    pub fn values(&self) -> Vec<f64> {
+        match self {
+            ParamValues::List(values) => values.clone(),
+            ParamValues::Range { start, end, step } => {
+                if *step <= 0.0 {
+                    return vec![*start];
+                }
+                let count = ((end - start) / step + 1e-9).floor().max(0.0) as usize + 1;
+                (0..count).map(|i| start + step * i as f64).collect()
+            }
+        }
+    }
+
+    pub fn min(&self) -> f64 {
+        self.values().into_iter().fold(f64::INFINITY, f64::min)
+    }
+
+    pub fn max(&self) -> f64 {
+        self.values().into_iter().fold(f64::NEG_INFINITY, f64::max)
+    }
+}
+
+/// Named parameters to search over, with an optional validity check on combinations.
+#[derive(Debug, Clone, Default)]
+pub struct ParamSpace {
+    pub params: Vec<(String, ParamValues)>,
+    pub constraint: Option<fn(&ParamSet) -> bool>,
+}
+
+impl ParamSpace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn range(mut self, name: &str, start: f64, end: f64, step: f64) -> Self {
+        self.params.push((name.to_string(), ParamValues::Range { start, end, step }));
+        self
+    }
+
+    pub fn list(mut self, name: &str, values: &[f64]) -> Self {
+        self.params.push((name.to_string(), ParamValues::List(values.to_vec())));
+        self
+    }
+
+    /// Skip combinations for which `check` returns false (e.g. short EMA >= long EMA).
+    pub fn with_constraint(mut self, check: fn(&ParamSet) -> bool) -> Self {
+        self.constraint = Some(check);
+        self
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.params.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    pub fn is_valid(&self, params: &ParamSet) -> bool {
+        self.constraint.is_none_or(|check| check(params))
+    }
+
+    /// Every valid combination, in lexicographic order of the parameter list.
+    pub fn grid(&self) -> Vec<ParamSet> {
+        let mut combos = vec![ParamSet::new()];
+        for (name, values) in &self.params {
+            let values = values.values();
+            combos = combos
+                .into_iter()
+                .flat_map(|base| {
+                    values.iter().map(move |v| {
+                        let mut next = base.clone();
+                        next.insert(name.clone(), *v);
+                        next
+                    })
+                })
+                .collect();
+        }
+        combos.retain(|p| self.is_valid(p));
+        combos
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Objective {
+    Sharpe,
+    TotalReturn,
+    /// Smallest max drawdown wins.
+    MaxDrawdown,
+}
+
+impl Objective {
+    /// Higher is always better.
+    pub fn score(&self, metrics: &PerformanceMetrics) -> f64 {
+        match self {
+            Objective::Sharpe => metrics.sharpe,
+            Objective::TotalReturn => metrics.total_return,
+            Objective::MaxDrawdown => -metrics.max_drawdown,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Objective::Sharpe => "sharpe",
+            Objective::TotalReturn => "total_return",
+            Objective::MaxDrawdown => "max_drawdown",
+        }
+    }
+}
+
+/// Outcome of one parameter set. The equity curve is dropped to keep large searches small.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrialResult {
+    pub params: ParamSet,
+    #[serde(with = "non_finite")]
+    pub score: f64,
+    pub metrics: PerformanceMetrics,
+    pub trades: usize,
+    #[serde(with = "non_finite")]
+    pub final_equity: f64,
+}
+
+/// Backtests every candidate over the shared bars on blocking worker threads,
+/// at most one per CPU core at a time. Results come back in candidate order.
+pub async fn evaluate_all(
+    bars: Arc<Vec<Bar>>,
+    candidates: Vec<ParamSet>,
+    objective: Objective,
+    backtest: BacktestFn,
+) -> Vec<TrialResult> {
+    let workers = std::thread::available_parallelism().map_or(4, |n| n.get());
+    let sem = Arc::new(tokio::sync::Semaphore::new(workers));
+    let mut handles = Vec::with_capacity(candidates.len());
+
+    for params in candidates {
+        let permit = Arc::clone(&sem).acquire_owned().await.unwrap();
+        let bars = Arc::clone(&bars);
+        let backtest = Arc::clone(&backtest);
+        handles.push(tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            let report = backtest(&params, &bars);
+            TrialResult {
+                score: objective.score(&report.metrics),
+                metrics: report.metrics,
+                trades: report.trades,
+                final_equity: report.final_equity,
+                params,
+            }
+        }));
+    }
+
+    let mut results = Vec::with_capacity(handles.len());
+    for h in handles {
+        match h.await {
+            Ok(result) => results.push(result),
+            Err(e) => eprintln!("⚠️ Backtest worker failed: {}", e),
+        }
+    }
+    results
+}
+
+/// Best score first; NaN scores sink to the bottom.
+pub fn rank(results: &mut [TrialResult]) {
+    results.sort_by(|a, b| match (a.score.is_nan(), b.score.is_nan()) {
+        (true, false) => std::cmp::Ordering::Greater,
+        (false, true) => std::cmp::Ordering::Less,
+        _ => b.score.total_cmp(&a.score),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grid_with_constraint() {
+        let space = ParamSpace::new()
+            .range("short", 1.0, 3.0, 1.0)
+            .list("long", &[2.0, 4.0])
+            .with_constraint(|p| p["short"] < p["long"]);
+        let grid = space.grid();
+        assert_eq!(grid.len(), 4);
+        assert!(grid.iter().all(|p| p["short"] < p["long"]));
+        assert_eq!(ParamValues::Range { start: 0.1, end: 0.3, step: 0.1 }.values().len(), 3);
+    }
+}
@@ -0,0 +1,379 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::sync::Arc;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::data::bar::Bar;
+use crate::optimize::{BacktestFn, Objective, ParamSet, ParamSpace, TrialResult, evaluate_all};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchMethod {
+    /// Uniform samples from the space.
+    Random,
+    /// Tournament selection over the best trials so far, uniform crossover,
+    /// and per-parameter mutation with the given probability.
+    Genetic { mutation_rate: f64 },
+    /// Tree-structured Parzen estimator: after `startup` random trials, the best
+    /// `gamma` fraction is modelled against the rest and each batch takes the
+    /// `candidates` draws with the highest good/bad density ratio.
+    Tpe { startup: usize, gamma: f64, candidates: usize },
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchConfig {
+    pub objective: Objective,
+    /// Stop once this many trials have been evaluated (including resumed ones).
+    pub max_evals: usize,
+    /// Trials evaluated in parallel per batch; the population size for `Genetic`.
+    pub batch_size: usize,
+    /// Stop after this many consecutive batches without a new best score.
+    pub patience: Option<usize>,
+}
+
+/// Everything needed to continue a search later. Each batch draws from an RNG
+/// seeded by `(seed, batch)`, so a resumed search matches an uninterrupted one.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchState {
+    pub seed: u64,
+    pub batches: usize,
+    pub stale_batches: usize,
+    pub trials: Vec<TrialResult>,
+}
+
+impl SearchState {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            ..Default::default()
+        }
+    }
+
+    pub fn best(&self) -> Option<&TrialResult> {
+        self.trials
+            .iter()
+            .filter(|t| !t.score.is_nan())
+            .max_by(|a, b| a.score.total_cmp(&b.score))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+}
+
+/// Runs (or resumes) a search until the evaluation budget or patience runs out,
+/// or no unseen valid candidates are left. Returns the updated state.
+pub async fn search(
+    bars: Arc<Vec<Bar>>,
+    space: &ParamSpace,
+    method: SearchMethod,
+    config: &SearchConfig,
+    backtest: BacktestFn,
+    mut state: SearchState,
+) -> SearchState {
+    let dims: Vec<Vec<f64>> = space.params.iter().map(|(_, v)| v.values()).collect();
+    let mut seen: HashSet<Vec<usize>> = state
+        .trials
+        .iter()
+        .filter_map(|t| to_indices(space, &dims, &t.params))
+        .collect();
+
+    while state.trials.len() < config.max_evals
+        && config.patience.is_none_or(|p| state.stale_batches < p)
+    {
+        let mut rng = batch_rng(state.seed, state.batches);
+        let want = config.batch_size.max(1).min(config.max_evals - state.trials.len());
+
+        let candidates = match method {
+            SearchMethod::Random => propose(&mut rng, space, &dims, &mut seen, want, |rng| random_point(rng, &dims)),
+            SearchMethod::Genetic { mutation_rate } => {
+                let parents = top_indices(space, &dims, &state.trials, config.batch_size.max(2));
+                if parents.len() < 2 {
+                    propose(&mut rng, space, &dims, &mut seen, want, |rng| random_point(rng, &dims))
+                } else {
+                    propose(&mut rng, space, &dims, &mut seen, want, |rng| {
+                        let a = tournament(rng, &parents);
+                        let b = tournament(rng, &parents);
+                        let mut child: Vec<usize> = a
+                            .iter()
+                            .zip(b)
+                            .map(|(&x, &y)| if rng.gen_bool(0.5) { x } else { y })
+                            .collect();
+                        for (gene, values) in child.iter_mut().zip(&dims) {
+                            if rng.gen_bool(mutation_rate.clamp(0.0, 1.0)) {
+                                let reach = (values.len() / 5).max(1) as i64;
+                                let shifted = *gene as i64 + rng.gen_range(-reach..=reach);
+                                *gene = shifted.clamp(0, values.len() as i64 - 1) as usize;
+                            }
+                        }
+                        child
+                    })
+                }
+            }
+            SearchMethod::Tpe { startup, gamma, candidates } => {
+                // The model needs a good and a bad group, so NaN-scored trials don't count.
+                let ranked = top_indices(space, &dims, &state.trials, state.trials.len());
+                if state.trials.len() < startup || ranked.len() < 2 {
+                    propose(&mut rng, space, &dims, &mut seen, want, |rng| random_point(rng, &dims))
+                } else {
+                    tpe_batch(&mut rng, space, &dims, &ranked, &mut seen, want, gamma, candidates)
+                }
+            }
+        };
+        if candidates.is_empty() {
+            break;
+        }
+
+        let params: Vec<ParamSet> = candidates.iter().map(|idx| to_params(space, &dims, idx)).collect();
+        let prev_best = state.best().map(|t| t.score);
+        let results = evaluate_all(Arc::clone(&bars), params, config.objective, Arc::clone(&backtest)).await;
+        state.trials.extend(results);
+        state.batches += 1;
+
+        let new_best = state.best().map(|t| t.score);
+        if new_best.is_some() && (prev_best.is_none() || new_best > prev_best) {
+            state.stale_batches = 0;
+        } else {
+            state.stale_batches += 1;
+        }
+    }
+    state
+}
+
+fn batch_rng(seed: u64, batch: usize) -> StdRng {
+    StdRng::seed_from_u64(seed ^ (batch as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15))
+}
+
+fn random_point(rng: &mut StdRng, dims: &[Vec<f64>]) -> Vec<usize> {
+    dims.iter().map(|values| rng.gen_range(0..values.len().max(1))).collect()
+}
+
+/// Draws from `draw` until `want` valid, unseen points are found or attempts run out.
+fn propose(
+    rng: &mut StdRng,
+    space: &ParamSpace,
+    dims: &[Vec<f64>],
+    seen: &mut HashSet<Vec<usize>>,
+    want: usize,
+    mut draw: impl FnMut(&mut StdRng) -> Vec<usize>,
+) -> Vec<Vec<usize>> {
+    let mut out = Vec::new();
+    for _ in 0..want * 50 {
+        if out.len() == want {
+            break;
+        }
+        let point = draw(rng);
+        if !seen.contains(&point) && space.is_valid(&to_params(space, dims, &point)) {
+            seen.insert(point.clone());
+            out.push(point);
+        }
+    }
+    out
+}
+
+fn tournament<'a>(rng: &mut StdRng, ranked: &'a [Vec<usize>]) -> &'a [usize] {
+    // `ranked` is best-first, so the lowest of three random picks wins.
+    let pick = (0..3).map(|_| rng.gen_range(0..ranked.len())).min().unwrap();
+    &ranked[pick]
+}
+
+/// Best `n` trials as index vectors, best first.
+fn top_indices(space: &ParamSpace, dims: &[Vec<f64>], trials: &[TrialResult], n: usize) -> Vec<Vec<usize>> {
+    let mut ranked: Vec<&TrialResult> = trials.iter().filter(|t| !t.score.is_nan()).collect();
+    ranked.sort_by(|a, b| b.score.total_cmp(&a.score));
+    ranked
+        .into_iter()
+        .filter_map(|t| to_indices(space, dims, &t.params))
+        .take(n)
+        .collect()
+}
+
+/// `ranked` holds the scored trials best first; it needs at least two.
+#[allow(clippy::too_many_arguments)]
+fn tpe_batch(
+    rng: &mut StdRng,
+    space: &ParamSpace,
+    dims: &[Vec<f64>],
+    ranked: &[Vec<usize>],
+    seen: &mut HashSet<Vec<usize>>,
+    want: usize,
+    gamma: f64,
+    candidates: usize,
+) -> Vec<Vec<usize>> {
+    let n_good = ((gamma.clamp(0.01, 0.99) * ranked.len() as f64).ceil() as usize).clamp(1, ranked.len() - 1);
+    let (good, bad) = ranked.split_at(n_good);
+    let l: Vec<Vec<f64>> = (0..dims.len()).map(|d| parzen(dims[d].len(), good, d)).collect();
+    let g: Vec<Vec<f64>> = (0..dims.len()).map(|d| parzen(dims[d].len(), bad, d)).collect();
+
+    let mut pool: Vec<(f64, Vec<usize>)> = (0..candidates.max(want))
+        .map(|_| {
+            let point: Vec<usize> = l.iter().map(|density| sample_categorical(rng, density)).collect();
+            let ratio: f64 = point.iter().enumerate().map(|(d, &k)| (l[d][k] / g[d][k]).ln()).sum();
+            (ratio, point)
+        })
+        .collect();
+    pool.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let mut out = Vec::new();
+    for (_, point) in pool {
+        if out.len() == want {
+            break;
+        }
+        if !seen.contains(&point) && space.is_valid(&to_params(space, dims, &point)) {
+            seen.insert(point.clone());
+            out.push(point);
+        }
+    }
+    // Fall back to random points if the model keeps proposing seen ones.
+    if out.len() < want {
+        let more = propose(rng, space, dims, seen, want - out.len(), |rng| random_point(rng, dims));
+        out.extend(more);
+    }
+    out
+}
+
+/// Gaussian-kernel density over a parameter's value indices, with a uniform prior.
+fn parzen(size: usize, points: &[Vec<usize>], dim: usize) -> Vec<f64> {
+    let bandwidth = (size as f64 / 10.0).max(1.0);
+    let mut density = vec![1.0 / size.max(1) as f64; size];
+    for p in points {
+        for (k, slot) in density.iter_mut().enumerate() {
+            let z = (k as f64 - p[dim] as f64) / bandwidth;
+            *slot += (-0.5 * z * z).exp();
+        }
+    }
+    let total: f64 = density.iter().sum();
+    density.iter_mut().for_each(|d| *d /= total);
+    density
+}
+
+fn sample_categorical(rng: &mut StdRng, weights: &[f64]) -> usize {
+    let mut target = rng.r#gen::<f64>();
+    for (k, w) in weights.iter().enumerate() {
+        target -= w;
+        if target <= 0.0 {
+            return k;
+        }
+    }
+    weights.len() - 1
+}
+
+fn to_params(space: &ParamSpace, dims: &[Vec<f64>], idx: &[usize]) -> ParamSet {
+    space
+        .params
+        .iter()
+        .zip(dims)
+        .zip(idx)
+        .map(|(((name, _), values), &i)| (name.clone(), values[i]))
+        .collect()
+}
+
+fn to_indices(space: &ParamSpace, dims: &[Vec<f64>], params: &ParamSet) -> Option<Vec<usize>> {
+    space
+        .params
+        .iter()
+        .zip(dims)
+        .map(|((name, _), values)| {
+            let v = params.get(name)?;
+            values.iter().position(|x| (x - v).abs() < 1e-9)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtest::report::{BacktestReport, PerformanceMetrics};
+
+    fn space() -> ParamSpace {
+        ParamSpace::new().range("a", 0.0, 20.0, 1.0).range("b", 0.0, 20.0, 1.0)
+    }
+
+    fn bowl() -> BacktestFn {
+        Arc::new(|p: &ParamSet, _: &[Bar]| BacktestReport {
+            metrics: PerformanceMetrics {
+                sharpe: -(p["a"] - 7.0).powi(2) - (p["b"] - 3.0).powi(2),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+    }
+
+    fn config(max_evals: usize) -> SearchConfig {
+        SearchConfig {
+            objective: Objective::Sharpe,
+            max_evals,
+            batch_size: 10,
+            patience: None,
+        }
+    }
+
+    async fn run(method: SearchMethod, max_evals: usize, state: SearchState) -> SearchState {
+        search(Arc::new(Vec::new()), &space(), method, &config(max_evals), bowl(), state).await
+    }
+
+    #[tokio::test]
+    async fn test_random_search_is_reproducible_and_resumable() {
+        let a = run(SearchMethod::Random, 30, SearchState::new(7)).await;
+        let b = run(SearchMethod::Random, 30, SearchState::new(7)).await;
+        let partial = run(SearchMethod::Random, 10, SearchState::new(7)).await;
+        let resumed = run(SearchMethod::Random, 30, partial).await;
+        let params = |s: &SearchState| s.trials.iter().map(|t| t.params.clone()).collect::<Vec<_>>();
+        assert_eq!(a.trials.len(), 30);
+        assert_eq!(params(&a), params(&b));
+        assert_eq!(params(&a), params(&resumed));
+    }
+
+    #[tokio::test]
+    async fn test_genetic_and_tpe_converge() {
+        let ga = run(SearchMethod::Genetic { mutation_rate: 0.3 }, 150, SearchState::new(1)).await;
+        assert!(ga.best().unwrap().score >= -2.0);
+        let tpe = run(SearchMethod::Tpe { startup: 20, gamma: 0.25, candidates: 64 }, 150, SearchState::new(1)).await;
+        assert!(tpe.best().unwrap().score >= -2.0);
+    }
+
+    #[tokio::test]
+    async fn test_patience_stops_early_and_state_round_trips() {
+        let flat: BacktestFn = Arc::new(|_: &ParamSet, _: &[Bar]| BacktestReport::default());
+        let cfg = SearchConfig { patience: Some(2), ..config(400) };
+        let state = search(Arc::new(Vec::new()), &space(), SearchMethod::Random, &cfg, flat, SearchState::new(3)).await;
+        assert_eq!(state.batches, 3);
+
+        let path = std::env::temp_dir().join(format!("quantx-search-{}.json", std::process::id()));
+        state.save(path.to_str().unwrap()).unwrap();
+        let loaded = SearchState::load(path.to_str().unwrap()).unwrap();
+        assert_eq!(loaded.trials.len(), state.trials.len());
+        assert_eq!(loaded.stale_batches, 2);
+    }
+
+    #[tokio::test]
+    async fn test_tpe_with_nan_scores_and_state_keeps_them() {
+        // Only points with a = 0 score, so the model has fewer than two trials to split.
+        let sparse: BacktestFn = Arc::new(|p: &ParamSet, _: &[Bar]| BacktestReport {
+            metrics: PerformanceMetrics {
+                sharpe: if p["a"] == 0.0 { 1.0 } else { f64::NAN },
+                ..Default::default()
+            },
+            final_equity: f64::INFINITY,
+            ..Default::default()
+        });
+        let method = SearchMethod::Tpe { startup: 2, gamma: 0.25, candidates: 16 };
+        let state = search(Arc::new(Vec::new()), &space(), method, &config(40), sparse, SearchState::new(5)).await;
+        assert_eq!(state.trials.len(), 40);
+
+        let path = std::env::temp_dir().join(format!("quantx-search-nan-{}.json", std::process::id()));
+        state.save(path.to_str().unwrap()).unwrap();
+        let loaded = SearchState::load(path.to_str().unwrap()).unwrap();
+        let nan = |s: &SearchState| s.trials.iter().filter(|t| t.score.is_nan()).count();
+        assert!(nan(&state) > 0);
+        assert_eq!(nan(&loaded), nan(&state));
+        assert!(loaded.trials.iter().all(|t| t.final_equity == f64::INFINITY && t.metrics.sharpe.is_nan() == t.score.is_nan()));
+    }
+}
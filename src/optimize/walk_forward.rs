@@ -0,0 +1,285 @@
+use std::error::Error;
+use std::ops::Range;
+use std::sync::Arc;
+
+use csv::Writer;
+
+use crate::backtest::report::{PerformanceMetrics, mean_std, returns};
+use crate::data::bar::Bar;
+use crate::optimize::grid::grid_search;
+use crate::optimize::{BacktestFn, Objective, ParamSet, ParamSpace};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowMode {
+    /// In-sample window of fixed length slides forward with each step.
+    Rolling,
+    /// In-sample window always starts at the first bar and grows.
+    Anchored,
+}
+
+#[derive(Debug, Clone)]
+pub struct WalkForwardConfig {
+    /// In-sample length, in bars.
+    pub in_sample: usize,
+    /// Out-of-sample length, in bars; also the step between windows.
+    pub out_of_sample: usize,
+    pub mode: WindowMode,
+    pub objective: Objective,
+    /// Bars before each out-of-sample window fed to the strategy for indicator warm-up.
+    /// Only the out-of-sample part of that run is scored.
+    pub warmup: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct WindowResult {
+    pub in_sample: Range<usize>,
+    pub out_of_sample: Range<usize>,
+    pub params: ParamSet,
+    pub in_sample_score: f64,
+    pub out_of_sample_score: f64,
+    pub out_of_sample_metrics: PerformanceMetrics,
+}
+
+#[derive(Debug, Clone)]
+pub struct ParamStability {
+    pub name: String,
+    pub mean: f64,
+    pub std: f64,
+    pub min: f64,
+    pub max: f64,
+    /// Windows whose chosen value differs from the previous window's.
+    pub changes: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct WalkForwardResult {
+    pub windows: Vec<WindowResult>,
+    /// Out-of-sample equity of every window, compounded into one curve.
+    pub equity_curve: Vec<f64>,
+    pub timestamps: Vec<String>,
+    pub metrics: PerformanceMetrics,
+    pub stability: Vec<ParamStability>,
+}
+
+/// `(in_sample, out_of_sample)` index ranges over `n_bars`. Trailing bars that
+/// don't fill a whole out-of-sample window are left out.
+pub fn windows(n_bars: usize, config: &WalkForwardConfig) -> Vec<(Range<usize>, Range<usize>)> {
+    let mut out = Vec::new();
+    if config.in_sample == 0 || config.out_of_sample == 0 {
+        return out;
+    }
+    let mut is_end = config.in_sample;
+    while is_end + config.out_of_sample <= n_bars {
+        let is_start = match config.mode {
+            WindowMode::Rolling => is_end - config.in_sample,
+            WindowMode::Anchored => 0,
+        };
+        out.push((is_start..is_end, is_end..is_end + config.out_of_sample));
+        is_end += config.out_of_sample;
+    }
+    out
+}
+
+/// Optimizes on each in-sample window, then trades the following out-of-sample
+/// window with the winning parameters and stitches those runs together.
+pub async fn walk_forward(
+    bars: Arc<Vec<Bar>>,
+    space: &ParamSpace,
+    config: &WalkForwardConfig,
+    backtest: BacktestFn,
+    periods_per_year: f64,
+) -> Result<WalkForwardResult, Box<dyn Error + Send + Sync>> {
+    let plan = windows(bars.len(), config);
+    if plan.is_empty() {
+        return Err(format!(
+            "{} bars can't fit a {}+{} bar walk-forward window",
+            bars.len(),
+            config.in_sample,
+            config.out_of_sample
+        )
+        .into());
+    }
+
+    let mut results = Vec::with_capacity(plan.len());
+    let mut equity_curve: Vec<f64> = Vec::new();
+    let mut timestamps = Vec::new();
+
+    for (is_range, oos_range) in plan {
+        let is_bars = Arc::new(bars[is_range.clone()].to_vec());
+        let ranked = grid_search(is_bars, space, config.objective, Arc::clone(&backtest)).await;
+        let Some(best) = ranked.into_iter().next() else {
+            return Err("parameter space has no valid combinations".into());
+        };
+
+        let run_start = oos_range.start.saturating_sub(config.warmup);
+        let report = backtest(&best.params, &bars[run_start..oos_range.end]);
+        // Last warm-up point (or the starting cash) anchors the first out-of-sample return.
+        let skip = oos_range.start - run_start;
+        let anchor = if skip == 0 {
+            report.starting_cash
+        } else {
+            report.equity_curve.get(skip - 1).copied().unwrap_or(report.starting_cash)
+        };
+        let mut oos_equity = vec![anchor];
+        oos_equity.extend(report.equity_curve.iter().skip(skip));
+        let oos_metrics = PerformanceMetrics::from_equity(&oos_equity, periods_per_year);
+
+        if equity_curve.is_empty() {
+            equity_curve.push(report.starting_cash);
+        }
+        for r in returns(&oos_equity) {
+            let last = *equity_curve.last().unwrap();
+            equity_curve.push(last * (1.0 + r));
+        }
+        timestamps.extend(bars[oos_range.clone()].iter().map(|b| b.timestamp.clone()));
+
+        results.push(WindowResult {
+            in_sample: is_range,
+            out_of_sample: oos_range,
+            in_sample_score: best.score,
+            out_of_sample_score: config.objective.score(&oos_metrics),
+            out_of_sample_metrics: oos_metrics,
+            params: best.params,
+        });
+    }
+
+    let metrics = PerformanceMetrics::from_equity(&equity_curve, periods_per_year);
+    // Drop the seed point so the curve lines up with `timestamps`.
+    equity_curve.remove(0);
+    Ok(WalkForwardResult {
+        stability: param_stability(space, &results),
+        windows: results,
+        equity_curve,
+        timestamps,
+        metrics,
+    })
+}
+
+fn param_stability(space: &ParamSpace, windows: &[WindowResult]) -> Vec<ParamStability> {
+    space
+        .names()
+        .into_iter()
+        .map(|name| {
+            let values: Vec<f64> = windows.iter().filter_map(|w| w.params.get(name).copied()).collect();
+            let (mean, std) = mean_std(&values);
+            ParamStability {
+                name: name.to_string(),
+                mean,
+                std,
+                min: values.iter().copied().fold(f64::INFINITY, f64::min),
+                max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+                changes: values.windows(2).filter(|w| w[0] != w[1]).count(),
+            }
+        })
+        .collect()
+}
+
+impl WalkForwardResult {
+    /// One row per window: ranges, chosen parameters and in/out-of-sample scores.
+    pub fn write_windows_csv(&self, path: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut wtr = Writer::from_path(path)?;
+        let names: Vec<String> = self
+            .windows
+            .first()
+            .map(|w| w.params.keys().cloned().collect())
+            .unwrap_or_default();
+        let mut header = vec!["is_start", "is_end", "oos_start", "oos_end"]
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        header.extend(names.iter().cloned());
+        header.extend(["is_score", "oos_score", "oos_return", "oos_max_drawdown"].map(String::from));
+        wtr.write_record(&header)?;
+        for w in &self.windows {
+            let mut row = vec![
+                w.in_sample.start.to_string(),
+                w.in_sample.end.to_string(),
+                w.out_of_sample.start.to_string(),
+                w.out_of_sample.end.to_string(),
+            ];
+            row.extend(names.iter().map(|n| w.params.get(n).map_or(String::new(), |v| v.to_string())));
+            row.extend([
+                w.in_sample_score.to_string(),
+                w.out_of_sample_score.to_string(),
+                w.out_of_sample_metrics.total_return.to_string(),
+                w.out_of_sample_metrics.max_drawdown.to_string(),
+            ]);
+            wtr.write_record(&row)?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    pub fn write_equity_csv(&self, path: &str) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let mut wtr = Writer::from_path(path)?;
+        wtr.write_record(["timestamp", "equity"])?;
+        for (ts, equity) in self.timestamps.iter().zip(&self.equity_curve) {
+            wtr.write_record([ts.as_str(), &equity.to_string()])?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backtest::report::BacktestReport;
+
+    fn config(mode: WindowMode) -> WalkForwardConfig {
+        WalkForwardConfig {
+            in_sample: 4,
+            out_of_sample: 2,
+            mode,
+            objective: Objective::TotalReturn,
+            warmup: 1,
+        }
+    }
+
+    #[test]
+    fn test_rolling_and_anchored_windows() {
+        let rolling = windows(11, &config(WindowMode::Rolling));
+        assert_eq!(rolling, vec![(0..4, 4..6), (2..6, 6..8), (4..8, 8..10)]);
+        let anchored = windows(11, &config(WindowMode::Anchored));
+        assert_eq!(anchored[2], (0..8, 8..10));
+    }
+
+    #[tokio::test]
+    async fn test_walk_forward_stitches_out_of_sample() {
+        let bars: Vec<Bar> = (0..10)
+            .map(|i| Bar {
+                timestamp: format!("2025-01-01T{:02}:00:00+00:00", i),
+                open: 100.0,
+                high: 100.0,
+                low: 100.0,
+                close: 100.0,
+                volume: 1.0,
+            })
+            .collect();
+        // Equity grows by `g` per bar, so the largest `g` always wins in-sample.
+        let backtest: BacktestFn = Arc::new(|params: &ParamSet, bars: &[Bar]| {
+            let g = params["g"];
+            let equity_curve: Vec<f64> = (1..=bars.len()).map(|i| 100.0 + g * i as f64).collect();
+            let mut curve = vec![100.0];
+            curve.extend(&equity_curve);
+            BacktestReport {
+                starting_cash: 100.0,
+                final_equity: *equity_curve.last().unwrap(),
+                metrics: PerformanceMetrics::from_equity(&curve, 1.0),
+                equity_curve,
+                ..Default::default()
+            }
+        });
+        let space = ParamSpace::new().list("g", &[0.0, 1.0]);
+        let result = walk_forward(Arc::new(bars), &space, &config(WindowMode::Rolling), backtest, 1.0)
+            .await
+            .unwrap();
+
+        assert_eq!(result.windows.len(), 3);
+        assert_eq!(result.equity_curve.len(), 6);
+        assert_eq!(result.timestamps[0], "2025-01-01T04:00:00+00:00");
+        assert!(result.equity_curve.windows(2).all(|w| w[1] > w[0]));
+        assert_eq!(result.stability[0].mean, 1.0);
+        assert_eq!(result.stability[0].changes, 0);
+    }
+}
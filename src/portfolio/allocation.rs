@@ -0,0 +1,67 @@
+use crate::backtest::report::mean_std;
+use crate::portfolio::optimization::{covariance, risk_parity};
+
+/// How capital is split across strategies (or assets).
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum AllocationScheme {
+    #[default]
+    Equal,
+    /// Normalized to sum to 1; missing entries count as 0.
+    Fixed(Vec<f64>),
+    /// Weights proportional to 1 / volatility.
+    InverseVolatility,
+    /// Equal contribution to total variance, using the full covariance.
+    RiskParity,
+}
+
+impl AllocationScheme {
+    /// Weights for `returns.len()` series of per-period returns over the same periods.
+    /// Volatility-based schemes fall back to equal weights until every series has at least
+    /// two returns and non-zero volatility.
+    pub fn weights(&self, returns: &[Vec<f64>]) -> Vec<f64> {
+        let n = returns.len();
+        if n == 0 {
+            return Vec::new();
+        }
+        let equal = vec![1.0 / n as f64; n];
+        let weights = match self {
+            AllocationScheme::Equal => return equal,
+            AllocationScheme::Fixed(w) => (0..n).map(|i| w.get(i).copied().unwrap_or(0.0).max(0.0)).collect(),
+            AllocationScheme::InverseVolatility | AllocationScheme::RiskParity => {
+                let vols: Vec<f64> = returns.iter().map(|r| mean_std(r).1).collect();
+                if returns.iter().any(|r| r.len() < 2) || vols.iter().any(|v| !(v.is_finite() && *v > 0.0)) {
+                    return equal;
+                }
+                if *self == AllocationScheme::InverseVolatility {
+                    vols.iter().map(|v| 1.0 / v).collect()
+                } else {
+                    risk_parity(&covariance(returns))
+                }
+            }
+        };
+        normalize(weights).unwrap_or(equal)
+    }
+}
+
+fn normalize(weights: Vec<f64>) -> Option<Vec<f64>> {
+    let total: f64 = weights.iter().sum();
+    (total.is_finite() && total > 0.0).then(|| weights.into_iter().map(|w| w / total).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schemes() {
+        let calm = vec![0.01, -0.01, 0.01, -0.01];
+        let wild = vec![0.03, -0.03, 0.03, -0.03];
+        let returns = vec![calm.clone(), wild.clone()];
+        assert_eq!(AllocationScheme::Equal.weights(&returns), vec![0.5, 0.5]);
+        assert_eq!(AllocationScheme::Fixed(vec![3.0, 1.0]).weights(&returns), vec![0.75, 0.25]);
+        let iv = AllocationScheme::InverseVolatility.weights(&returns);
+        assert!((iv[0] - 0.75).abs() < 1e-12);
+        // Not enough history yet.
+        assert_eq!(AllocationScheme::RiskParity.weights(&[vec![0.1], vec![0.2]]), vec![0.5, 0.5]);
+    }
+}
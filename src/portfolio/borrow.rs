@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+
+use crate::data::feed::Symbol;
+use crate::portfolio::Portfolio;
+
+/// Annualized borrow rates. A symbol's time series takes precedence over its fixed rate,
+/// which takes precedence over `default_rate`. `cash_rate` applies to negative cash,
+/// i.e. quote currency borrowed to buy on margin.
+#[derive(Debug, Clone, Default)]
+pub struct BorrowRates {
+    pub default_rate: f64,
+    pub cash_rate: f64,
+    rates: HashMap<Symbol, f64>,
+    series: HashMap<Symbol, Vec<(DateTime<Utc>, f64)>>,
+}
+
+impl BorrowRates {
+    pub fn new(default_rate: f64) -> Self {
+        Self { default_rate, ..Default::default() }
+    }
+
+    pub fn with_rate(mut self, symbol: &str, annual_rate: f64) -> Self {
+        self.rates.insert(symbol.to_string(), annual_rate);
+        self
+    }
+
+    /// Rates that change over time; each applies from its timestamp until the next.
+    pub fn with_series(mut self, symbol: &str, mut points: Vec<(DateTime<Utc>, f64)>) -> Self {
+        points.sort_by_key(|(t, _)| *t);
+        self.series.insert(symbol.to_string(), points);
+        self
+    }
+
+    pub fn with_cash_rate(mut self, annual_rate: f64) -> Self {
+        self.cash_rate = annual_rate;
+        self
+    }
+
+    /// Annual rate for borrowing `symbol` at `at`.
+    pub fn rate(&self, symbol: &str, at: DateTime<Utc>) -> f64 {
+        let from_series = self.series.get(symbol).and_then(|points| {
+            let idx = points.partition_point(|(t, _)| *t <= at);
+            idx.checked_sub(1).map(|i| points[i].1)
+        });
+        from_series
+            .or_else(|| self.rates.get(symbol).copied())
+            .unwrap_or(self.default_rate)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AccrualPeriod {
+    #[default]
+    Hourly,
+    Daily,
+}
+
+impl AccrualPeriod {
+    fn seconds(&self) -> i64 {
+        match self {
+            AccrualPeriod::Hourly => 3_600,
+            AccrualPeriod::Daily => 86_400,
+        }
+    }
+
+    fn per_year(&self) -> f64 {
+        365.0 * 86_400.0 / self.seconds() as f64
+    }
+}
+
+/// Charges interest on borrowed assets at every hour or day boundary, the way spot
+/// margin venues do: shorts pay on the marked value of the borrowed units, and negative
+/// cash pays the cash rate. Charges are taken from cash and tallied in
+/// `Portfolio::borrow_cost`.
+#[derive(Debug, Clone)]
+pub struct BorrowAccrual {
+    pub rates: BorrowRates,
+    pub period: AccrualPeriod,
+    charge_cash: bool,
+    last: Option<DateTime<Utc>>,
+}
+
+impl BorrowAccrual {
+    pub fn new(rates: BorrowRates, period: AccrualPeriod) -> Self {
+        Self { rates, period, charge_cash: true, last: None }
+    }
+
+    /// Leaves negative cash alone, for margin accounts where it is futures notional
+    /// rather than a loan.
+    pub fn without_cash(mut self) -> Self {
+        self.charge_cash = false;
+        self
+    }
+
+    /// Advances the clock to `now`, charging once per period boundary crossed since the
+    /// previous call for what is borrowed now. Returns the interest charged.
+    pub fn accrue(&mut self, portfolio: &mut Portfolio, now: DateTime<Utc>, prices: &HashMap<Symbol, f64>) -> f64 {
+        let Some(last) = self.last.replace(now) else {
+            return 0.0;
+        };
+        let secs = self.period.seconds();
+        let (first, end) = (last.timestamp().div_euclid(secs) + 1, now.timestamp().div_euclid(secs));
+        let mut interest = 0.0;
+        for boundary in first..=end {
+            let Some(at) = DateTime::from_timestamp(boundary * secs, 0) else {
+                continue;
+            };
+            for (symbol, pos) in portfolio.positions.iter().filter(|(_, p)| p.quantity < 0.0) {
+                let price = prices.get(symbol).copied().unwrap_or(pos.avg_price);
+                interest += -pos.quantity * price * self.rates.rate(symbol, at) / self.period.per_year();
+            }
+            if self.charge_cash && portfolio.cash < 0.0 {
+                interest += -portfolio.cash * self.rates.cash_rate / self.period.per_year();
+            }
+        }
+        portfolio.cash -= interest;
+        portfolio.borrow_cost += interest;
+        interest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::order::OrderSide;
+    use crate::portfolio::{Fill, FillKind};
+
+    fn at(h: u32, m: u32) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(&format!("2024-01-01T{:02}:{:02}:00Z", h, m)).unwrap().to_utc()
+    }
+
+    #[test]
+    fn test_hourly_interest_on_shorts_and_negative_cash() {
+        let mut pf = Portfolio::new(0.0);
+        let short = |symbol: &str, price: f64| Fill {
+            timestamp: String::new(),
+            symbol: symbol.into(),
+            side: OrderSide::Sell,
+            quantity: 10.0,
+            price,
+            fee: 0.0,
+            kind: FillKind::Trade,
+        };
+        pf.apply_fill(short("BTC", 100.0));
+        pf.apply_fill(short("ETH", 50.0));
+        let rates = BorrowRates::new(0.0)
+            .with_rate("BTC", 0.0876)
+            .with_series("ETH", vec![(at(1, 0), 0.0), (at(2, 0), 0.876)]);
+        let mut accrual = BorrowAccrual::new(rates, AccrualPeriod::Hourly);
+        let prices = HashMap::from([("BTC".to_string(), 100.0), ("ETH".to_string(), 50.0)]);
+
+        assert_eq!(accrual.accrue(&mut pf, at(0, 30), &prices), 0.0);
+        // Boundaries at 01:00 and 02:00: BTC 1000 * 0.0876 / 8760 = 0.01 each; ETH only
+        // pays from 02:00, 500 * 0.876 / 8760 = 0.05.
+        let charged = accrual.accrue(&mut pf, at(2, 15), &prices);
+        assert!((charged - 0.07).abs() < 1e-12);
+        assert!((pf.borrow_cost - 0.07).abs() < 1e-12);
+        assert_eq!(accrual.accrue(&mut pf, at(2, 45), &prices), 0.0);
+
+        let mut levered = Portfolio::new(-8_760.0);
+        let mut accrual = BorrowAccrual::new(BorrowRates::new(0.0).with_cash_rate(0.365), AccrualPeriod::Daily);
+        accrual.accrue(&mut levered, at(0, 0), &HashMap::new());
+        let next_day = at(0, 0) + chrono::Duration::days(1);
+        assert!((accrual.accrue(&mut levered, next_day, &HashMap::new()) - 8.76).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,296 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::data::feed::{FeedSlice, Symbol};
+use crate::data::order::OrderSide;
+use crate::portfolio::{Fill, Portfolio};
+
+pub type Currency = String;
+
+/// What a symbol trades: `base` priced in `quote` (BTCUSDT = BTC in USDT, USDINR = USD in INR).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instrument {
+    pub base: Currency,
+    pub quote: Currency,
+    /// Both legs settle in cash, as on a spot exchange: buying credits `base` cash and
+    /// debits `quote` cash. Otherwise the symbol is held as a position in the quote book.
+    pub spot: bool,
+}
+
+/// Latest conversion rates. Missing pairs are inverted or crossed through one
+/// intermediate currency (ETH → BTC → USDT).
+#[derive(Debug, Clone, Default)]
+pub struct FxRates {
+    rates: HashMap<(Currency, Currency), f64>,
+}
+
+impl FxRates {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// One unit of `from` is worth `rate` units of `to`.
+    pub fn set(&mut self, from: &str, to: &str, rate: f64) {
+        if rate.is_finite() && rate > 0.0 {
+            self.rates.insert((from.to_string(), to.to_string()), rate);
+        }
+    }
+
+    fn direct(&self, from: &str, to: &str) -> Option<f64> {
+        if from == to {
+            return Some(1.0);
+        }
+        let key = |a: &str, b: &str| (a.to_string(), b.to_string());
+        self.rates
+            .get(&key(from, to))
+            .copied()
+            .or_else(|| self.rates.get(&key(to, from)).map(|r| 1.0 / r))
+    }
+
+    pub fn rate(&self, from: &str, to: &str) -> Option<f64> {
+        self.direct(from, to).or_else(|| {
+            let mut via: Vec<&Currency> = self.rates.keys().flat_map(|(a, b)| [a, b]).collect();
+            via.sort();
+            via.dedup();
+            via.into_iter()
+                .find_map(|mid| Some(self.direct(from, mid)? * self.direct(mid, to)?))
+        })
+    }
+
+    pub fn convert(&self, amount: f64, from: &str, to: &str) -> Option<f64> {
+        self.rate(from, to).map(|r| amount * r)
+    }
+}
+
+/// Books per currency, valued together in `base`. Fills settle in their quote
+/// currency's cash and realized PnL is attributed to that currency. Symbols registered
+/// with `with_pair` also settle the base leg, so ETH bought on ETHBTC is ETH cash that
+/// can be sold on ETHUSDT; those registered with `with_instrument` are held as positions
+/// in the quote book. Move cash between currencies with `exchange`.
+#[derive(Debug, Clone)]
+pub struct MultiCurrencyPortfolio {
+    pub base: Currency,
+    pub fx: FxRates,
+    books: BTreeMap<Currency, Portfolio>,
+    instruments: HashMap<Symbol, Instrument>,
+    /// Cost basis of assets bought on spot pairs, by base asset and in the account's
+    /// `base` currency, so a sale on any pair realizes against it; its cash is meaningless.
+    spot_costs: Portfolio,
+}
+
+impl MultiCurrencyPortfolio {
+    pub fn new(base: &str) -> Self {
+        Self {
+            base: base.to_string(),
+            fx: FxRates::new(),
+            books: BTreeMap::new(),
+            instruments: HashMap::new(),
+            spot_costs: Portfolio::new(0.0),
+        }
+    }
+
+    /// Registers what `symbol` trades. Bars of registered symbols also feed `fx`, so
+    /// FX pairs like USDINR can be registered purely as rate sources.
+    pub fn with_instrument(mut self, symbol: &str, base: &str, quote: &str) -> Self {
+        let instrument = Instrument { base: base.to_string(), quote: quote.to_string(), spot: false };
+        self.instruments.insert(symbol.to_string(), instrument);
+        self
+    }
+
+    /// Registers a spot pair whose fills move cash in both currencies.
+    pub fn with_pair(mut self, symbol: &str, base: &str, quote: &str) -> Self {
+        let instrument = Instrument { base: base.to_string(), quote: quote.to_string(), spot: true };
+        self.instruments.insert(symbol.to_string(), instrument);
+        self
+    }
+
+    pub fn instrument(&self, symbol: &str) -> Option<&Instrument> {
+        self.instruments.get(symbol)
+    }
+
+    /// Quote currency of `symbol`; unregistered symbols are assumed to trade in `base`.
+    pub fn currency_of(&self, symbol: &str) -> &str {
+        self.instruments.get(symbol).map_or(&self.base, |i| &i.quote)
+    }
+
+    pub fn book(&self, currency: &str) -> Option<&Portfolio> {
+        self.books.get(currency)
+    }
+
+    fn book_mut(&mut self, currency: &str) -> &mut Portfolio {
+        self.books.entry(currency.to_string()).or_insert_with(|| Portfolio::new(0.0))
+    }
+
+    pub fn deposit(&mut self, currency: &str, amount: f64) {
+        self.book_mut(currency).cash += amount;
+    }
+
+    /// Converts `amount` of `from` cash into `to` at the current rate less `fee_rate`.
+    /// Returns the amount received, or `None` without a rate.
+    pub fn exchange(&mut self, from: &str, to: &str, amount: f64, fee_rate: f64) -> Option<f64> {
+        let received = self.fx.convert(amount, from, to)? * (1.0 - fee_rate);
+        self.book_mut(from).cash -= amount;
+        self.book_mut(to).cash += received;
+        Some(received)
+    }
+
+    /// Updates rates from the slice's registered symbols.
+    pub fn on_slice(&mut self, slice: &FeedSlice) {
+        for (symbol, bar) in &slice.bars {
+            if let Some(inst) = self.instruments.get(symbol) {
+                self.fx.set(&inst.base, &inst.quote, bar.close);
+            }
+        }
+    }
+
+    /// Books `fill` in its symbol's quote currency, and for spot pairs the base leg in
+    /// the base currency; returns the realized PnL in the quote currency. Spot sales
+    /// realize against the asset's cost wherever it was bought, converted through `fx`
+    /// at the fill; without a rate the fill leaves the cost basis' price unchanged, and
+    /// units with no recorded cost (e.g. deposits) realize nothing.
+    pub fn apply_fill(&mut self, fill: Fill) -> f64 {
+        let currency = self.currency_of(&fill.symbol).to_string();
+        let Some(base) = self.instruments.get(&fill.symbol).filter(|i| i.spot).map(|i| i.base.clone()) else {
+            return self.book_mut(&currency).apply_fill(fill);
+        };
+        let signed = match fill.side {
+            OrderSide::Buy => fill.quantity,
+            OrderSide::Sell => -fill.quantity,
+        };
+        let held = self.spot_costs.position(&base);
+        let quantity = match fill.side {
+            OrderSide::Buy => fill.quantity,
+            OrderSide::Sell => fill.quantity.min(held.max(0.0)),
+        };
+        let mut realized = 0.0;
+        if quantity > 0.0 {
+            let at_cost = self.spot_costs.positions.get(&base).map_or(0.0, |p| p.avg_price);
+            let price = self.fx.convert(fill.price, &currency, &self.base).unwrap_or(at_cost);
+            let cost_fill = Fill { symbol: base.clone(), quantity, price, fee: 0.0, ..fill.clone() };
+            let in_base = self.spot_costs.apply_fill(cost_fill);
+            realized = self.fx.convert(in_base, &self.base, &currency).unwrap_or(0.0);
+        }
+        let book = self.book_mut(&currency);
+        book.cash -= signed * fill.price + fill.fee;
+        book.fees_paid += fill.fee;
+        book.realized_pnl += realized;
+        book.blotter.push(fill);
+        self.book_mut(&base).cash += signed;
+        realized
+    }
+
+    pub fn cash_balances(&self) -> BTreeMap<Currency, f64> {
+        self.books.iter().map(|(c, b)| (c.clone(), b.cash)).collect()
+    }
+
+    /// Open positions across the books; spot holdings are cash, not positions.
+    pub fn quantities(&self) -> HashMap<Symbol, f64> {
+        self.books.values().flat_map(Portfolio::quantities).collect()
+    }
+
+    pub fn realized_pnl_by_currency(&self) -> BTreeMap<Currency, f64> {
+        self.books.iter().map(|(c, b)| (c.clone(), b.realized_pnl)).collect()
+    }
+
+    /// Each currency's equity (cash plus positions marked at `prices`) in that currency.
+    pub fn equity_by_currency(&self, prices: &HashMap<Symbol, f64>) -> BTreeMap<Currency, f64> {
+        self.books.iter().map(|(c, b)| (c.clone(), b.equity(prices))).collect()
+    }
+
+    /// Total equity in the base currency, or `None` if some currency can't be converted.
+    pub fn equity(&self, prices: &HashMap<Symbol, f64>) -> Option<f64> {
+        self.equity_by_currency(prices)
+            .iter()
+            .map(|(c, e)| self.fx.convert(*e, c, &self.base))
+            .sum()
+    }
+
+    /// Realized PnL across currencies, each converted at the current rate.
+    pub fn realized_pnl(&self) -> Option<f64> {
+        self.realized_pnl_by_currency()
+            .iter()
+            .map(|(c, p)| self.fx.convert(*p, c, &self.base))
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::bar::Bar;
+    use crate::portfolio::FillKind;
+
+    fn fill(symbol: &str, side: OrderSide, quantity: f64, price: f64) -> Fill {
+        Fill { timestamp: "t".into(), symbol: symbol.into(), side, quantity, price, fee: 0.0, kind: FillKind::Trade }
+    }
+
+    #[test]
+    fn test_cross_rates() {
+        let mut fx = FxRates::new();
+        fx.set("BTC", "USDT", 50_000.0);
+        fx.set("ETH", "BTC", 0.05);
+        fx.set("USD", "INR", 80.0);
+        assert_eq!(fx.rate("USDT", "BTC"), Some(1.0 / 50_000.0));
+        assert_eq!(fx.rate("ETH", "USDT"), Some(2_500.0));
+        assert_eq!(fx.rate("INR", "INR"), Some(1.0));
+        assert_eq!(fx.rate("ETH", "INR"), None);
+    }
+
+    #[test]
+    fn test_books_per_currency_valued_in_base() {
+        let mut pf = MultiCurrencyPortfolio::new("USDT")
+            .with_instrument("BTCUSDT", "BTC", "USDT")
+            .with_instrument("ETHBTC", "ETH", "BTC")
+            .with_instrument("INFY", "INFY", "INR")
+            .with_instrument("USDTINR", "USDT", "INR");
+        let bar = |close: f64| Bar { timestamp: "t".into(), open: close, high: close, low: close, close, volume: 1.0 };
+        let slice = FeedSlice {
+            timestamp: chrono::Utc::now(),
+            bars: HashMap::from([
+                ("BTCUSDT".to_string(), bar(50_000.0)),
+                ("ETHBTC".to_string(), bar(0.05)),
+                ("USDTINR".to_string(), bar(80.0)),
+            ]),
+        };
+        pf.on_slice(&slice);
+        pf.deposit("USDT", 100_000.0);
+        pf.deposit("INR", 80_000.0);
+        assert_eq!(pf.exchange("USDT", "BTC", 50_000.0, 0.0), Some(1.0));
+
+        pf.apply_fill(fill("ETHBTC", OrderSide::Buy, 10.0, 0.05));
+        pf.apply_fill(fill("INFY", OrderSide::Buy, 50.0, 1_600.0));
+        pf.apply_fill(fill("ETHBTC", OrderSide::Sell, 10.0, 0.06));
+        assert_eq!(pf.cash_balances()["BTC"], 1.1);
+        assert!((pf.realized_pnl_by_currency()["BTC"] - 0.1).abs() < 1e-12);
+
+        let prices = HashMap::from([("INFY".to_string(), 1_760.0)]);
+        // 50k USDT + 1.1 BTC (55k) + 88k INR in positions (1.1k USDT).
+        let equity = pf.equity(&prices).unwrap();
+        assert!((equity - 106_100.0).abs() < 1e-6);
+        assert!((pf.realized_pnl().unwrap() - 5_000.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_spot_pairs_settle_both_legs() {
+        let mut pf = MultiCurrencyPortfolio::new("USDT")
+            .with_pair("ETHBTC", "ETH", "BTC")
+            .with_pair("ETHUSDT", "ETH", "USDT");
+        pf.fx.set("BTC", "USDT", 50_000.0);
+        pf.fx.set("ETH", "USDT", 2_500.0);
+        pf.deposit("BTC", 1.0);
+        pf.apply_fill(fill("ETHBTC", OrderSide::Buy, 10.0, 0.05));
+        assert_eq!((pf.cash_balances()["BTC"], pf.cash_balances()["ETH"]), (0.5, 10.0));
+        assert!((pf.apply_fill(fill("ETHBTC", OrderSide::Sell, 4.0, 0.06)) - 0.04).abs() < 1e-12);
+        assert!((pf.realized_pnl_by_currency()["BTC"] - 0.04).abs() < 1e-12);
+
+        // The rest of the ETH bought for BTC is sold for USDT, against its 2.5k cost.
+        pf.fx.set("ETH", "USDT", 3_000.0);
+        assert!((pf.apply_fill(fill("ETHUSDT", OrderSide::Sell, 6.0, 3_000.0)) - 3_000.0).abs() < 1e-9);
+        assert!((pf.realized_pnl_by_currency()["USDT"] - 3_000.0).abs() < 1e-9);
+        assert_eq!(pf.cash_balances()["ETH"], 0.0);
+        assert_eq!(pf.cash_balances()["USDT"], 18_000.0);
+        assert!(pf.book("USDT").unwrap().positions.is_empty());
+        assert!(pf.spot_costs.positions.is_empty());
+        // 0.74 BTC at 50k plus 18k USDT.
+        assert!((pf.equity(&HashMap::new()).unwrap() - 55_000.0).abs() < 1e-6);
+    }
+}
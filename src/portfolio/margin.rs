@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+
+use crate::broker::OrderRequest;
+use crate::data::bar::Bar;
+use crate::data::feed::Symbol;
+use crate::data::order::OrderSide;
+use crate::portfolio::{Fill, FillKind, Portfolio};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MarginMode {
+    /// All positions share the account's equity as collateral.
+    #[default]
+    Cross,
+    /// Each position is backed only by the margin posted when it was opened.
+    Isolated,
+}
+
+/// Linear (USDT-margined style) futures margin rules.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MarginConfig {
+    pub mode: MarginMode,
+    /// Initial margin is notional / leverage.
+    pub leverage: f64,
+    /// Maintenance margin as a fraction of notional.
+    pub maintenance_rate: f64,
+    /// Charged on the notional of a forced liquidation.
+    pub liquidation_fee_rate: f64,
+    /// A margin call is raised once maintenance margin reaches this fraction of the
+    /// margin balance; liquidation happens at 1.0.
+    pub margin_call_ratio: f64,
+}
+
+impl Default for MarginConfig {
+    fn default() -> Self {
+        Self {
+            mode: MarginMode::Cross,
+            leverage: 10.0,
+            maintenance_rate: 0.005,
+            liquidation_fee_rate: 0.005,
+            margin_call_ratio: 0.8,
+        }
+    }
+}
+
+impl MarginConfig {
+    pub fn initial_rate(&self) -> f64 {
+        1.0 / self.leverage.max(1.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("needs {required:.2} initial margin, {available:.2} available")]
+pub struct InsufficientMargin {
+    pub required: f64,
+    pub available: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MarginEvent {
+    MarginCall { timestamp: String, margin_ratio: f64 },
+    Liquidation { timestamp: String, symbol: Symbol, quantity: f64, price: f64, fee: f64 },
+}
+
+/// Margin accounting over a `Portfolio`: pre-trade initial margin checks, maintenance
+/// monitoring and forced liquidation. Liquidations are booked as `FillKind::Liquidation`.
+#[derive(Debug, Clone, Default)]
+pub struct MarginManager {
+    pub config: MarginConfig,
+    /// Margin posted per position in isolated mode.
+    isolated: HashMap<Symbol, f64>,
+    in_call: bool,
+    events: Vec<MarginEvent>,
+}
+
+fn mark(portfolio: &Portfolio, symbol: &str, prices: &HashMap<Symbol, f64>) -> f64 {
+    prices
+        .get(symbol)
+        .copied()
+        .unwrap_or_else(|| portfolio.positions.get(symbol).map_or(0.0, |p| p.avg_price))
+}
+
+impl MarginManager {
+    pub fn new(config: MarginConfig) -> Self {
+        Self { config, ..Default::default() }
+    }
+
+    pub fn events(&self) -> &[MarginEvent] {
+        &self.events
+    }
+
+    pub fn take_events(&mut self) -> Vec<MarginEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub fn isolated_margin(&self, symbol: &str) -> f64 {
+        self.isolated.get(symbol).copied().unwrap_or(0.0)
+    }
+
+    pub fn initial_margin(&self, portfolio: &Portfolio, prices: &HashMap<Symbol, f64>) -> f64 {
+        self.notional(portfolio, prices) * self.config.initial_rate()
+    }
+
+    pub fn maintenance_margin(&self, portfolio: &Portfolio, prices: &HashMap<Symbol, f64>) -> f64 {
+        self.notional(portfolio, prices) * self.config.maintenance_rate
+    }
+
+    fn notional(&self, portfolio: &Portfolio, prices: &HashMap<Symbol, f64>) -> f64 {
+        portfolio
+            .positions
+            .iter()
+            .map(|(s, p)| p.quantity.abs() * mark(portfolio, s, prices))
+            .sum()
+    }
+
+    /// Collateral free for new positions: equity less initial margin in cross mode; the
+    /// wallet (equity before unrealized PnL) less posted margin in isolated mode.
+    pub fn available_margin(&self, portfolio: &Portfolio, prices: &HashMap<Symbol, f64>) -> f64 {
+        let equity = portfolio.equity(prices);
+        match self.config.mode {
+            MarginMode::Cross => equity - self.initial_margin(portfolio, prices),
+            MarginMode::Isolated => {
+                let unrealized: f64 = portfolio
+                    .positions
+                    .iter()
+                    .map(|(s, p)| p.quantity * (mark(portfolio, s, prices) - p.avg_price))
+                    .sum();
+                equity - unrealized - self.isolated.values().sum::<f64>()
+            }
+        }
+    }
+
+    /// Checks the initial margin for the part of an order that adds exposure, plus the
+    /// `fee` it will pay.
+    pub fn check_order(
+        &self,
+        portfolio: &Portfolio,
+        request: &OrderRequest,
+        price: f64,
+        fee: f64,
+        prices: &HashMap<Symbol, f64>,
+    ) -> Result<(), InsufficientMargin> {
+        let position = portfolio.position(&request.symbol);
+        let signed = if request.side == OrderSide::Buy { request.quantity } else { -request.quantity };
+        let added = ((position + signed).abs() - position.abs()).max(0.0);
+        let required = added * price * self.config.initial_rate() + fee;
+        let available = self.available_margin(portfolio, prices);
+        if required > available + 1e-9 {
+            return Err(InsufficientMargin { required, available });
+        }
+        Ok(())
+    }
+
+    /// Books `fill` and, in isolated mode, adjusts the margin posted for its position.
+    pub fn apply_fill(&mut self, portfolio: &mut Portfolio, fill: Fill) -> f64 {
+        let before = portfolio.position(&fill.symbol);
+        let (symbol, price) = (fill.symbol.clone(), fill.price);
+        let realized = portfolio.apply_fill(fill);
+        let after = portfolio.position(&symbol);
+        if self.config.mode == MarginMode::Isolated {
+            let posted = self.isolated_margin(&symbol);
+            let margin = if after == 0.0 {
+                0.0
+            } else if before * after < 0.0 || before == 0.0 {
+                after.abs() * price * self.config.initial_rate()
+            } else if after.abs() > before.abs() {
+                posted + (after.abs() - before.abs()) * price * self.config.initial_rate()
+            } else {
+                posted * after.abs() / before.abs()
+            };
+            if margin > 0.0 {
+                self.isolated.insert(symbol, margin);
+            } else {
+                self.isolated.remove(&symbol);
+            }
+        }
+        realized
+    }
+
+    /// Mark price of `symbol` at which its position would be liquidated, with every other
+    /// position held at `prices`. `None` when flat or when no positive price liquidates it.
+    pub fn liquidation_price(&self, portfolio: &Portfolio, symbol: &str, prices: &HashMap<Symbol, f64>) -> Option<f64> {
+        let pos = portfolio.positions.get(symbol)?;
+        let q = pos.quantity;
+        let mmr = self.config.maintenance_rate;
+        let price = match self.config.mode {
+            MarginMode::Isolated => {
+                // margin + q (P - entry) = mmr |q| P
+                let margin = self.isolated_margin(symbol);
+                (q * pos.avg_price - margin) / (q - mmr * q.abs())
+            }
+            MarginMode::Cross => {
+                // equity + q (P - P0) = mmr |q| P + maintenance on everything else
+                let p0 = mark(portfolio, symbol, prices);
+                let others = self.maintenance_margin(portfolio, prices) - mmr * q.abs() * p0;
+                let equity = portfolio.equity(prices);
+                (equity - q * p0 - others) / (mmr * q.abs() - q)
+            }
+        };
+        (price.is_finite() && price > 0.0).then_some(price)
+    }
+
+    /// Checks `symbol`'s position against `bar`: if the adverse extreme reaches the
+    /// liquidation price, returns the fill closing it there (or at the open on a gap)
+    /// with the liquidation fee, for the caller to book through `apply_fill`. Otherwise
+    /// raises a margin call once the maintenance ratio at the close passes
+    /// `margin_call_ratio`. `prices` should hold the close.
+    pub fn on_bar(
+        &mut self,
+        portfolio: &Portfolio,
+        symbol: &str,
+        bar: &Bar,
+        prices: &HashMap<Symbol, f64>,
+    ) -> Option<Fill> {
+        let q = portfolio.position(symbol);
+        if q != 0.0
+            && let Some(liq) = self.liquidation_price(portfolio, symbol, prices)
+        {
+            let long = q > 0.0;
+            let hit = if long { bar.low <= liq } else { bar.high >= liq };
+            if hit {
+                let price = if long { bar.open.min(liq) } else { bar.open.max(liq) };
+                let fee = q.abs() * price * self.config.liquidation_fee_rate;
+                let fill = Fill {
+                    timestamp: bar.timestamp.clone(),
+                    symbol: symbol.to_string(),
+                    side: if long { OrderSide::Sell } else { OrderSide::Buy },
+                    quantity: q.abs(),
+                    price,
+                    fee,
+                    kind: FillKind::Liquidation,
+                };
+                self.events.push(MarginEvent::Liquidation {
+                    timestamp: bar.timestamp.clone(),
+                    symbol: symbol.to_string(),
+                    quantity: q.abs(),
+                    price,
+                    fee,
+                });
+                return Some(fill);
+            }
+        }
+
+        let balance = portfolio.equity(prices);
+        let maintenance = self.maintenance_margin(portfolio, prices);
+        let ratio = if balance > 0.0 { maintenance / balance } else if maintenance > 0.0 { f64::INFINITY } else { 0.0 };
+        let called = maintenance > 0.0 && ratio >= self.config.margin_call_ratio;
+        if called && !self.in_call {
+            self.events.push(MarginEvent::MarginCall { timestamp: bar.timestamp.clone(), margin_ratio: ratio });
+        }
+        self.in_call = called;
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(side: OrderSide, quantity: f64, price: f64) -> Fill {
+        Fill { timestamp: "t".into(), symbol: "BTC".into(), side, quantity, price, fee: 0.0, kind: FillKind::Trade }
+    }
+
+    fn bar(open: f64, high: f64, low: f64, close: f64) -> Bar {
+        Bar { timestamp: "t1".into(), open, high, low, close, volume: 1.0 }
+    }
+
+    #[test]
+    fn test_isolated_liquidation_price_and_fill() {
+        let config = MarginConfig { mode: MarginMode::Isolated, leverage: 10.0, maintenance_rate: 0.01, ..Default::default() };
+        let mut margin = MarginManager::new(config);
+        let mut pf = Portfolio::new(1_000.0);
+        let prices = HashMap::from([("BTC".to_string(), 100.0)]);
+        assert!(margin.check_order(&pf, &OrderRequest::market("BTC", OrderSide::Buy, 101.0), 100.0, 0.0, &prices).is_err());
+        // All the margin, but not the fee on top.
+        let buy = OrderRequest::market("BTC", OrderSide::Buy, 100.0);
+        assert!(margin.check_order(&pf, &buy, 100.0, 0.0, &prices).is_ok());
+        assert!(margin.check_order(&pf, &buy, 100.0, 1.0, &prices).is_err());
+        margin.apply_fill(&mut pf, fill(OrderSide::Buy, 50.0, 100.0));
+        assert_eq!(margin.isolated_margin("BTC"), 500.0);
+        // (5000 - 500) / (50 * 0.99)
+        let liq = margin.liquidation_price(&pf, "BTC", &prices).unwrap();
+        assert!((liq - 90.909_090_9).abs() < 1e-6);
+
+        assert!(margin.on_bar(&pf, "BTC", &bar(100.0, 101.0, 92.0, 95.0), &prices).is_none());
+        let prices = HashMap::from([("BTC".to_string(), 88.0)]);
+        let liquidation = margin.on_bar(&pf, "BTC", &bar(95.0, 95.0, 85.0, 88.0), &prices).unwrap();
+        assert_eq!(liquidation.kind, FillKind::Liquidation);
+        assert!((liquidation.price - liq).abs() < 1e-9);
+        margin.apply_fill(&mut pf, liquidation);
+        assert_eq!(pf.position("BTC"), 0.0);
+        assert_eq!(margin.isolated_margin("BTC"), 0.0);
+        assert!(matches!(pf.blotter.last().unwrap().kind, FillKind::Liquidation));
+        assert!(matches!(margin.events()[0], MarginEvent::Liquidation { .. }));
+    }
+
+    #[test]
+    fn test_cross_margin_call_then_gap_liquidation() {
+        let config = MarginConfig { leverage: 20.0, maintenance_rate: 0.05, ..Default::default() };
+        let mut margin = MarginManager::new(config);
+        let mut pf = Portfolio::new(1_000.0);
+        margin.apply_fill(&mut pf, fill(OrderSide::Sell, 100.0, 100.0));
+        let prices = HashMap::from([("BTC".to_string(), 100.0)]);
+        // Short 100 with 1000 equity: 1000 - 100 (P - 100) = 5 P  =>  P = 11000 / 105.
+        let liq = margin.liquidation_price(&pf, "BTC", &prices).unwrap();
+        assert!((liq - 11_000.0 / 105.0).abs() < 1e-9);
+
+        // At 104 maintenance is 520 against 600 equity.
+        let prices = HashMap::from([("BTC".to_string(), 104.0)]);
+        assert!(margin.on_bar(&pf, "BTC", &bar(101.0, 104.5, 100.0, 104.0), &prices).is_none());
+        assert!(matches!(margin.events()[0], MarginEvent::MarginCall { .. }));
+
+        let prices = HashMap::from([("BTC".to_string(), 110.0)]);
+        let liquidation = margin.on_bar(&pf, "BTC", &bar(108.0, 111.0, 107.0, 110.0), &prices).unwrap();
+        assert_eq!((liquidation.side, liquidation.price), (OrderSide::Buy, 108.0));
+        margin.apply_fill(&mut pf, liquidation);
+        assert_eq!(pf.position("BTC"), 0.0);
+    }
+}
@@ -0,0 +1,272 @@
+pub mod allocation;
+pub mod borrow;
+pub mod currency;
+pub mod margin;
+pub mod optimization;
+pub mod rebalance;
+
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+
+use crate::data::continuous::RollEvent;
+use crate::data::corporate_actions::{ActionKind, AdjustmentMode, CorporateActions};
+use crate::data::feed::Symbol;
+use crate::data::order::OrderSide;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Position {
+    /// Signed: negative for shorts.
+    pub quantity: f64,
+    pub avg_price: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FillKind {
+    #[default]
+    Trade,
+    /// One leg of a futures roll from an expiring contract into the next.
+    Roll,
+    /// Forced close when margin fell to the maintenance requirement.
+    Liquidation,
+}
+
+/// One executed trade, as recorded in the blotter.
+#[derive(Debug, Clone)]
+pub struct Fill {
+    pub timestamp: String,
+    pub symbol: Symbol,
+    pub side: OrderSide,
+    pub quantity: f64,
+    pub price: f64,
+    pub fee: f64,
+    pub kind: FillKind,
+}
+
+/// Cash, positions and the trade blotter for one account.
+#[derive(Debug, Clone)]
+pub struct Portfolio {
+    pub cash: f64,
+    pub positions: HashMap<Symbol, Position>,
+    pub realized_pnl: f64,
+    pub fees_paid: f64,
+    pub dividends: f64,
+    /// Interest paid on borrowed assets and negative cash, kept apart from trading fees.
+    pub borrow_cost: f64,
+    pub blotter: Vec<Fill>,
+}
+
+impl Portfolio {
+    pub fn new(cash: f64) -> Self {
+        Self {
+            cash,
+            positions: HashMap::new(),
+            realized_pnl: 0.0,
+            fees_paid: 0.0,
+            dividends: 0.0,
+            borrow_cost: 0.0,
+            blotter: Vec::new(),
+        }
+    }
+
+    pub fn position(&self, symbol: &str) -> f64 {
+        self.positions.get(symbol).map_or(0.0, |p| p.quantity)
+    }
+
+    /// Signed quantity of every open position.
+    pub fn quantities(&self) -> HashMap<Symbol, f64> {
+        self.positions.iter().map(|(s, p)| (s.clone(), p.quantity)).collect()
+    }
+
+    /// Books a fill against cash and the symbol's position. Returns the PnL realized
+    /// by any part of the fill that reduced an existing position (before fees).
+    pub fn apply_fill(&mut self, fill: Fill) -> f64 {
+        let signed_qty = match fill.side {
+            OrderSide::Buy => fill.quantity,
+            OrderSide::Sell => -fill.quantity,
+        };
+        self.cash -= signed_qty * fill.price + fill.fee;
+        self.fees_paid += fill.fee;
+
+        let pos = self.positions.entry(fill.symbol.clone()).or_default();
+        let mut realized = 0.0;
+        if pos.quantity != 0.0 && pos.quantity.signum() != signed_qty.signum() {
+            let closed = signed_qty.abs().min(pos.quantity.abs());
+            realized = (fill.price - pos.avg_price) * closed * pos.quantity.signum();
+        }
+
+        let new_qty = pos.quantity + signed_qty;
+        if new_qty.abs() < 1e-12 {
+            pos.quantity = 0.0;
+            pos.avg_price = 0.0;
+        } else if pos.quantity == 0.0 || pos.quantity.signum() != new_qty.signum() {
+            // Opened fresh, or flipped through zero: the remainder is a new position at the fill price.
+            pos.quantity = new_qty;
+            pos.avg_price = fill.price;
+        } else if new_qty.abs() > pos.quantity.abs() {
+            pos.avg_price = (pos.avg_price * pos.quantity + fill.price * signed_qty) / new_qty;
+            pos.quantity = new_qty;
+        } else {
+            pos.quantity = new_qty;
+        }
+        if pos.quantity == 0.0 {
+            self.positions.remove(&fill.symbol);
+        }
+
+        self.realized_pnl += realized;
+        self.blotter.push(fill);
+        realized
+    }
+
+    /// Cash plus positions marked at `prices`; symbols without a price are marked at cost.
+    pub fn equity(&self, prices: &HashMap<Symbol, f64>) -> f64 {
+        self.cash
+            + self
+                .positions
+                .iter()
+                .map(|(symbol, p)| p.quantity * prices.get(symbol).copied().unwrap_or(p.avg_price))
+                .sum::<f64>()
+    }
+
+    /// Moves any position in `roll.from` into `roll.to` with two explicit `FillKind::Roll`
+    /// fills at the roll prices, each charged `fee_rate` of its notional. Returns the fees paid.
+    pub fn roll(&mut self, roll: &RollEvent, fee_rate: f64) -> f64 {
+        let mut fees = 0.0;
+        for mut fill in self.roll_fills(roll) {
+            fill.fee = fill.quantity * fill.price * fee_rate;
+            fees += fill.fee;
+            self.apply_fill(fill);
+        }
+        fees
+    }
+
+    /// The closing and opening legs that roll the position in `roll.from`, before fees;
+    /// empty when there is nothing to roll.
+    pub fn roll_fills(&self, roll: &RollEvent) -> Vec<Fill> {
+        let qty = self.position(&roll.from);
+        if qty == 0.0 {
+            return Vec::new();
+        }
+        let (close_side, open_side) = if qty > 0.0 {
+            (OrderSide::Sell, OrderSide::Buy)
+        } else {
+            (OrderSide::Buy, OrderSide::Sell)
+        };
+        [
+            (roll.from.clone(), close_side, roll.from_price),
+            (roll.to.clone(), open_side, roll.to_price),
+        ]
+        .into_iter()
+        .map(|(symbol, side, price)| Fill {
+            timestamp: roll.timestamp.clone(),
+            symbol,
+            side,
+            quantity: qty.abs(),
+            price,
+            fee: 0.0,
+            kind: FillKind::Roll,
+        })
+        .collect()
+    }
+
+    /// Applies actions going ex on `date` to positions held coming into that date; call
+    /// once per date, before that date's fills. `adjustment` is how the traded series was
+    /// adjusted, `None` for raw prices. On raw prices splits and bonus issues scale the
+    /// quantity and average price, and dividends are paid as declared. On price-only
+    /// series positions are already in adjusted shares, so each dividend is divided by the
+    /// splits that follow it. Total-return series already include dividends and nothing is
+    /// paid. Shorts are charged dividends. Returns the dividends paid.
+    pub fn apply_corporate_actions(
+        &mut self,
+        actions: &CorporateActions,
+        date: NaiveDate,
+        adjustment: Option<AdjustmentMode>,
+    ) -> f64 {
+        let mut total = 0.0;
+        for action in actions.on(date) {
+            match (action.kind, adjustment) {
+                (ActionKind::Split(shares) | ActionKind::Bonus(shares), None) => {
+                    if let Some(pos) = self.positions.get_mut(&action.symbol) {
+                        pos.quantity *= shares;
+                        pos.avg_price /= shares;
+                    }
+                }
+                (ActionKind::Dividend(per_share), None) => total += self.position(&action.symbol) * per_share,
+                (ActionKind::Dividend(per_share), Some(AdjustmentMode::PriceOnly)) => {
+                    let later = actions.share_factor_after(&action.symbol, date);
+                    total += self.position(&action.symbol) * per_share / later;
+                }
+                _ => {}
+            }
+        }
+        self.cash += total;
+        self.dividends += total;
+        total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fill(side: OrderSide, quantity: f64, price: f64) -> Fill {
+        Fill {
+            timestamp: "2025-01-01T00:00:00+00:00".to_string(),
+            symbol: "BTCUSDT".to_string(),
+            side,
+            quantity,
+            price,
+            fee: 1.0,
+            kind: FillKind::Trade,
+        }
+    }
+
+    #[test]
+    fn test_round_trip_realizes_pnl() {
+        let mut pf = Portfolio::new(1_000.0);
+        pf.apply_fill(fill(OrderSide::Buy, 2.0, 100.0));
+        pf.apply_fill(fill(OrderSide::Buy, 2.0, 120.0));
+        assert_eq!(pf.positions["BTCUSDT"].avg_price, 110.0);
+
+        let realized = pf.apply_fill(fill(OrderSide::Sell, 4.0, 130.0));
+        assert_eq!(realized, 80.0);
+        assert_eq!(pf.position("BTCUSDT"), 0.0);
+        assert_eq!(pf.cash, 1_000.0 + 80.0 - 3.0);
+        assert_eq!(pf.blotter.len(), 3);
+    }
+
+    #[test]
+    fn test_flip_long_to_short() {
+        let mut pf = Portfolio::new(1_000.0);
+        pf.apply_fill(fill(OrderSide::Buy, 1.0, 100.0));
+        let realized = pf.apply_fill(fill(OrderSide::Sell, 3.0, 90.0));
+        assert_eq!(realized, -10.0);
+        assert_eq!(pf.positions["BTCUSDT"], Position { quantity: -2.0, avg_price: 90.0 });
+
+        let prices = HashMap::from([("BTCUSDT".to_string(), 80.0)]);
+        assert_eq!(pf.equity(&prices), pf.cash - 160.0);
+    }
+
+    #[test]
+    fn test_roll_books_two_legs() {
+        let mut pf = Portfolio::new(10_000.0);
+        let mut f = fill(OrderSide::Sell, 2.0, 100.0);
+        f.symbol = "BTCUSD_250328".to_string();
+        pf.apply_fill(f);
+
+        let roll = RollEvent {
+            index: 10,
+            timestamp: "2025-03-21T00:00:00+00:00".to_string(),
+            from: "BTCUSD_250328".to_string(),
+            to: "BTCUSD_250627".to_string(),
+            from_price: 90.0,
+            to_price: 95.0,
+        };
+        let fees = pf.roll(&roll, 0.001);
+        assert!((fees - 0.37).abs() < 1e-9);
+        assert_eq!(pf.position("BTCUSD_250328"), 0.0);
+        assert_eq!(pf.position("BTCUSD_250627"), -2.0);
+        assert_eq!(pf.realized_pnl, 20.0);
+        assert!(pf.blotter[1..].iter().all(|f| f.kind == FillKind::Roll));
+    }
+}
@@ -0,0 +1,445 @@
+use std::collections::HashMap;
+use std::error::Error;
+
+use crate::data::bar::Bar;
+use crate::data::feed::{MissingBarPolicy, MultiSymbolFeed, Symbol};
+use crate::portfolio::rebalance::Target;
+
+type OptResult<T> = Result<T, Box<dyn Error + Send + Sync>>;
+
+/// Aligned per-period simple returns, one series per symbol.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReturnMatrix {
+    pub symbols: Vec<Symbol>,
+    /// `returns[i][t]` is symbol `i`'s return over period `t`.
+    pub returns: Vec<Vec<f64>>,
+}
+
+impl ReturnMatrix {
+    /// Close-to-close returns over the timestamps every symbol has a bar for.
+    pub fn from_bars(series: HashMap<Symbol, Vec<Bar>>) -> OptResult<Self> {
+        let feed = MultiSymbolFeed::from_series(series, MissingBarPolicy::Wait)?;
+        let symbols = feed.symbols().to_vec();
+        let mut returns = vec![Vec::new(); symbols.len()];
+        let mut prev: Option<Vec<f64>> = None;
+        for slice in feed {
+            let closes: Vec<f64> = symbols.iter().map(|s| slice.bars[s].close).collect();
+            if let Some(prev) = &prev {
+                for (i, (c, p)) in closes.iter().zip(prev).enumerate() {
+                    returns[i].push(if *p != 0.0 { c / p - 1.0 } else { 0.0 });
+                }
+            }
+            prev = Some(closes);
+        }
+        Ok(Self { symbols, returns })
+    }
+
+    pub fn len(&self) -> usize {
+        self.returns.first().map_or(0, Vec::len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Per-period mean return of each symbol.
+    pub fn means(&self) -> Vec<f64> {
+        self.returns.iter().map(|r| r.iter().sum::<f64>() / r.len().max(1) as f64).collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CovarianceEstimator {
+    Sample,
+    /// RiskMetrics-style exponentially weighted (zero-mean) covariance; `lambda` near 1
+    /// remembers longer (0.94 is the classic daily value).
+    Ewma { lambda: f64 },
+    /// Ledoit-Wolf shrinkage of the sample covariance towards a scaled identity, with
+    /// the optimal intensity estimated from the data.
+    LedoitWolf,
+}
+
+impl CovarianceEstimator {
+    pub fn estimate(&self, returns: &[Vec<f64>]) -> Vec<Vec<f64>> {
+        match *self {
+            CovarianceEstimator::Sample => covariance(returns),
+            CovarianceEstimator::Ewma { lambda } => ewma_covariance(returns, lambda),
+            CovarianceEstimator::LedoitWolf => ledoit_wolf(returns).0,
+        }
+    }
+}
+
+fn common_tails(returns: &[Vec<f64>]) -> (Vec<&[f64]>, usize) {
+    let len = returns.iter().map(Vec::len).min().unwrap_or(0);
+    (returns.iter().map(|r| &r[r.len() - len..]).collect(), len)
+}
+
+/// Sample covariance over the common (trailing) length of the series.
+pub fn covariance(returns: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    let (tails, len) = common_tails(returns);
+    let means: Vec<f64> = tails.iter().map(|r| r.iter().sum::<f64>() / len.max(1) as f64).collect();
+    let denom = len.saturating_sub(1).max(1) as f64;
+    (0..tails.len())
+        .map(|i| {
+            (0..tails.len())
+                .map(|j| (0..len).map(|t| (tails[i][t] - means[i]) * (tails[j][t] - means[j])).sum::<f64>() / denom)
+                .collect()
+        })
+        .collect()
+}
+
+pub fn ewma_covariance(returns: &[Vec<f64>], lambda: f64) -> Vec<Vec<f64>> {
+    let (tails, len) = common_tails(returns);
+    let weights: Vec<f64> = (0..len).map(|t| lambda.powi((len - 1 - t) as i32)).collect();
+    let total: f64 = weights.iter().sum::<f64>().max(f64::MIN_POSITIVE);
+    (0..tails.len())
+        .map(|i| {
+            (0..tails.len())
+                .map(|j| (0..len).map(|t| weights[t] * tails[i][t] * tails[j][t]).sum::<f64>() / total)
+                .collect()
+        })
+        .collect()
+}
+
+/// Ledoit & Wolf (2004) shrinkage towards `mean variance × I`. Returns the estimate and
+/// the shrinkage intensity in `[0, 1]`.
+pub fn ledoit_wolf(returns: &[Vec<f64>]) -> (Vec<Vec<f64>>, f64) {
+    let (tails, len) = common_tails(returns);
+    let n = tails.len();
+    if n == 0 || len == 0 {
+        return (vec![vec![0.0; n]; n], 0.0);
+    }
+    let t_len = len as f64;
+    let x: Vec<Vec<f64>> = tails
+        .iter()
+        .map(|r| {
+            let mean = r.iter().sum::<f64>() / t_len;
+            r.iter().map(|v| v - mean).collect()
+        })
+        .collect();
+    let s: Vec<Vec<f64>> = (0..n)
+        .map(|i| (0..n).map(|j| (0..len).map(|t| x[i][t] * x[j][t]).sum::<f64>() / t_len).collect())
+        .collect();
+    let mu = (0..n).map(|i| s[i][i]).sum::<f64>() / n as f64;
+    let identity_gap = |i: usize, j: usize| s[i][j] - if i == j { mu } else { 0.0 };
+    let d2 = (0..n).flat_map(|i| (0..n).map(move |j| (i, j))).map(|(i, j)| identity_gap(i, j).powi(2)).sum::<f64>() / n as f64;
+    let b2_bar = (0..len)
+        .map(|t| {
+            (0..n)
+                .flat_map(|i| (0..n).map(move |j| (i, j)))
+                .map(|(i, j)| (x[i][t] * x[j][t] - s[i][j]).powi(2))
+                .sum::<f64>()
+                / n as f64
+        })
+        .sum::<f64>()
+        / (t_len * t_len);
+    let shrinkage = if d2 > 0.0 { b2_bar.min(d2) / d2 } else { 0.0 };
+    let sigma = (0..n)
+        .map(|i| {
+            (0..n)
+                .map(|j| (1.0 - shrinkage) * s[i][j] + if i == j { shrinkage * mu } else { 0.0 })
+                .collect()
+        })
+        .collect();
+    (sigma, shrinkage)
+}
+
+/// Bounds applied to every weight; weights always sum to 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Default for Bounds {
+    /// Long only.
+    fn default() -> Self {
+        Self { min: 0.0, max: 1.0 }
+    }
+}
+
+fn mat_vec(m: &[Vec<f64>], v: &[f64]) -> Vec<f64> {
+    m.iter().map(|row| row.iter().zip(v).map(|(a, b)| a * b).sum()).collect()
+}
+
+/// Euclidean projection of `v` onto `{ Σw = 1, min ≤ w ≤ max }`, by bisection on the shift.
+fn project(v: &[f64], bounds: &Bounds) -> Vec<f64> {
+    let shifted = |nu: f64| -> Vec<f64> { v.iter().map(|x| (x - nu).clamp(bounds.min, bounds.max)).collect() };
+    let (mut lo, mut hi) = (
+        v.iter().fold(f64::INFINITY, |a, x| a.min(*x)) - bounds.max,
+        v.iter().fold(f64::NEG_INFINITY, |a, x| a.max(*x)) - bounds.min,
+    );
+    for _ in 0..200 {
+        let mid = 0.5 * (lo + hi);
+        if shifted(mid).iter().sum::<f64>() > 1.0 {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    shifted(0.5 * (lo + hi))
+}
+
+/// Maximizes `μ'w - ½ γ w'Σw` subject to the bounds and full investment, by accelerated
+/// projected gradient. With `mu` all zero this is the minimum-variance portfolio.
+pub fn mean_variance(mu: &[f64], cov: &[Vec<f64>], risk_aversion: f64, bounds: &Bounds) -> OptResult<Vec<f64>> {
+    let n = cov.len();
+    if n == 0 || mu.len() != n {
+        return Err(format!("Expected {} expected returns, got {}", n, mu.len()).into());
+    }
+    if !(bounds.min.is_finite() && bounds.max.is_finite()) || bounds.min * n as f64 > 1.0 || bounds.max * (n as f64) < 1.0 {
+        return Err(format!("Weight bounds [{}, {}] can't sum to 1 over {} assets", bounds.min, bounds.max, n).into());
+    }
+    // Step from the largest eigenvalue of γΣ (power iteration).
+    let mut v = vec![1.0 / (n as f64).sqrt(); n];
+    let mut lipschitz = 0.0;
+    for _ in 0..100 {
+        let mv = mat_vec(cov, &v);
+        let norm = mv.iter().map(|x| x * x).sum::<f64>().sqrt();
+        if norm == 0.0 {
+            break;
+        }
+        lipschitz = norm * risk_aversion;
+        v = mv.into_iter().map(|x| x / norm).collect();
+    }
+    let step = 1.0 / lipschitz.max(1e-12) / 1.01;
+
+    let mut w = project(&vec![1.0 / n as f64; n], bounds);
+    let mut y = w.clone();
+    let mut momentum: f64 = 1.0;
+    for _ in 0..20_000 {
+        let grad: Vec<f64> = mat_vec(cov, &y).iter().zip(mu).map(|(sw, m)| risk_aversion * sw - m).collect();
+        let next = project(&y.iter().zip(&grad).map(|(yi, g)| yi - step * g).collect::<Vec<_>>(), bounds);
+        let next_momentum = 0.5 * (1.0 + (1.0 + 4.0 * momentum * momentum).sqrt());
+        let beta = (momentum - 1.0) / next_momentum;
+        y = next.iter().zip(&w).map(|(a, b)| a + beta * (a - b)).collect();
+        let change = next.iter().zip(&w).map(|(a, b)| (a - b).abs()).fold(0.0, f64::max);
+        w = next;
+        momentum = next_momentum;
+        if change < 1e-13 {
+            break;
+        }
+    }
+    Ok(w)
+}
+
+/// Global minimum-variance weights within `bounds`.
+pub fn min_variance(cov: &[Vec<f64>], bounds: &Bounds) -> OptResult<Vec<f64>> {
+    mean_variance(&vec![0.0; cov.len()], cov, 1.0, bounds)
+}
+
+/// Equal-risk-contribution weights (long only, summing to 1) by multiplicative updates
+/// that push each asset's share of variance, `w_i (Σw)_i / w'Σw`, towards 1/n.
+pub fn risk_parity(cov: &[Vec<f64>]) -> Vec<f64> {
+    let n = cov.len();
+    if n == 0 {
+        return Vec::new();
+    }
+    let mut w: Vec<f64> = (0..n).map(|i| 1.0 / cov[i][i].max(1e-18).sqrt()).collect();
+    let total: f64 = w.iter().sum();
+    w.iter_mut().for_each(|x| *x /= total);
+    for _ in 0..500 {
+        let sigma_w = mat_vec(cov, &w);
+        let variance: f64 = (0..n).map(|i| w[i] * sigma_w[i]).sum();
+        if variance <= 0.0 {
+            break;
+        }
+        let mut max_gap: f64 = 0.0;
+        for i in 0..n {
+            let contribution = w[i] * sigma_w[i] / variance;
+            max_gap = max_gap.max((contribution - 1.0 / n as f64).abs());
+            if contribution > 0.0 {
+                w[i] *= (1.0 / (n as f64 * contribution)).sqrt();
+            }
+        }
+        let total: f64 = w.iter().sum();
+        w.iter_mut().for_each(|x| *x /= total);
+        if max_gap < 1e-10 {
+            break;
+        }
+    }
+    w
+}
+
+/// Hierarchical risk parity (López de Prado): single-linkage clustering on correlation
+/// distance, quasi-diagonal ordering, then recursive bisection by inverse cluster variance.
+pub fn hierarchical_risk_parity(cov: &[Vec<f64>]) -> Vec<f64> {
+    let n = cov.len();
+    if n == 0 {
+        return Vec::new();
+    }
+    let vol: Vec<f64> = (0..n).map(|i| cov[i][i].max(0.0).sqrt()).collect();
+    let dist = |i: usize, j: usize| {
+        let corr = if vol[i] > 0.0 && vol[j] > 0.0 { cov[i][j] / (vol[i] * vol[j]) } else { 0.0 };
+        (0.5 * (1.0 - corr.clamp(-1.0, 1.0))).sqrt()
+    };
+
+    // Merging clusters by concatenating their leaf lists yields the quasi-diagonal order.
+    let mut clusters: Vec<Vec<usize>> = (0..n).map(|i| vec![i]).collect();
+    while clusters.len() > 1 {
+        let mut best = (0, 1, f64::INFINITY);
+        for a in 0..clusters.len() {
+            for b in a + 1..clusters.len() {
+                let d = clusters[a]
+                    .iter()
+                    .flat_map(|&i| clusters[b].iter().map(move |&j| (i, j)))
+                    .map(|(i, j)| dist(i, j))
+                    .fold(f64::INFINITY, f64::min);
+                if d < best.2 {
+                    best = (a, b, d);
+                }
+            }
+        }
+        let merged = clusters.remove(best.1);
+        clusters[best.0].extend(merged);
+    }
+    let order = clusters.pop().unwrap_or_default();
+
+    let cluster_variance = |items: &[usize]| {
+        let ivp: Vec<f64> = items.iter().map(|&i| 1.0 / cov[i][i].max(1e-18)).collect();
+        let total: f64 = ivp.iter().sum();
+        let w: Vec<f64> = ivp.iter().map(|x| x / total).collect();
+        items
+            .iter()
+            .enumerate()
+            .flat_map(|(a, &i)| items.iter().enumerate().map(move |(b, &j)| (a, b, i, j)))
+            .map(|(a, b, i, j)| w[a] * w[b] * cov[i][j])
+            .sum::<f64>()
+    };
+    let mut weights = vec![1.0; n];
+    let mut stack = vec![order];
+    while let Some(items) = stack.pop() {
+        if items.len() < 2 {
+            continue;
+        }
+        let (left, right) = items.split_at(items.len() / 2);
+        let (vl, vr) = (cluster_variance(left), cluster_variance(right));
+        let alpha = if vl + vr > 0.0 { 1.0 - vl / (vl + vr) } else { 0.5 };
+        left.iter().for_each(|&i| weights[i] *= alpha);
+        right.iter().for_each(|&i| weights[i] *= 1.0 - alpha);
+        stack.push(left.to_vec());
+        stack.push(right.to_vec());
+    }
+    weights
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Optimizer {
+    /// Uses the sample mean returns as expected returns.
+    MeanVariance { risk_aversion: f64 },
+    MinVariance,
+    EqualRiskContribution,
+    HierarchicalRiskParity,
+}
+
+/// Estimator, optimizer and constraints in one, producing weights by symbol.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PortfolioOptimizer {
+    pub estimator: CovarianceEstimator,
+    pub optimizer: Optimizer,
+    /// Used by the mean-variance and minimum-variance optimizers.
+    pub bounds: Bounds,
+}
+
+impl PortfolioOptimizer {
+    pub fn new(estimator: CovarianceEstimator, optimizer: Optimizer) -> Self {
+        Self { estimator, optimizer, bounds: Bounds::default() }
+    }
+
+    pub fn with_bounds(mut self, bounds: Bounds) -> Self {
+        self.bounds = bounds;
+        self
+    }
+
+    pub fn weights(&self, data: &ReturnMatrix) -> OptResult<HashMap<Symbol, f64>> {
+        if data.len() < 2 {
+            return Err(format!("Need at least 2 return periods, got {}", data.len()).into());
+        }
+        let cov = self.estimator.estimate(&data.returns);
+        let weights = match self.optimizer {
+            Optimizer::MeanVariance { risk_aversion } => mean_variance(&data.means(), &cov, risk_aversion, &self.bounds)?,
+            Optimizer::MinVariance => min_variance(&cov, &self.bounds)?,
+            Optimizer::EqualRiskContribution => risk_parity(&cov),
+            Optimizer::HierarchicalRiskParity => hierarchical_risk_parity(&cov),
+        };
+        Ok(data.symbols.iter().cloned().zip(weights).collect())
+    }
+
+    /// Weights as rebalancing targets (see `rebalance::rebalance_orders`).
+    pub fn targets(&self, data: &ReturnMatrix) -> OptResult<HashMap<Symbol, Target>> {
+        Ok(self.weights(data)?.into_iter().map(|(s, w)| (s, Target::Weight(w))).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COV: [[f64; 3]; 3] = [[0.04, 0.006, 0.0], [0.006, 0.01, 0.002], [0.0, 0.002, 0.0225]];
+
+    fn cov() -> Vec<Vec<f64>> {
+        COV.iter().map(|r| r.to_vec()).collect()
+    }
+
+    fn close(a: &[f64], b: &[f64], tol: f64) -> bool {
+        a.iter().zip(b).all(|(x, y)| (x - y).abs() < tol)
+    }
+
+    #[test]
+    fn test_min_variance_and_bounds() {
+        // Two uncorrelated assets: GMV weights are proportional to 1 / variance.
+        let cov2 = vec![vec![0.04, 0.0], vec![0.0, 0.01]];
+        let w = min_variance(&cov2, &Bounds::default()).unwrap();
+        assert!(close(&w, &[0.2, 0.8], 1e-9));
+        let capped = min_variance(&cov2, &Bounds { min: 0.0, max: 0.6 }).unwrap();
+        assert!(close(&capped, &[0.4, 0.6], 1e-9));
+        // Negative correlation with a loose lower bound lets one leg go short.
+        let hedged = vec![vec![0.04, 0.018], vec![0.018, 0.01]];
+        let w = min_variance(&hedged, &Bounds { min: -1.0, max: 2.0 }).unwrap();
+        assert!(w[0] < 0.0 && (w.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        assert!(min_variance(&cov2, &Bounds { min: 0.0, max: 0.4 }).is_err());
+
+        // Expected returns tilt mean-variance towards the first asset.
+        let mv = mean_variance(&[0.01, 0.0], &cov2, 1.0, &Bounds::default()).unwrap();
+        assert!(mv[0] > 0.2);
+    }
+
+    #[test]
+    fn test_risk_parity_and_hrp() {
+        let w = risk_parity(&cov());
+        let sigma_w = mat_vec(&cov(), &w);
+        let contributions: Vec<f64> = (0..3).map(|i| w[i] * sigma_w[i]).collect();
+        assert!((w.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+        assert!(contributions.iter().all(|c| (c - contributions[0]).abs() < 1e-9));
+
+        let hrp = hierarchical_risk_parity(&cov());
+        assert!((hrp.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+        assert!(hrp[1] > hrp[2] && hrp[2] > hrp[0]);
+        // Two uncorrelated assets: HRP is inverse variance.
+        let diag = vec![vec![0.01, 0.0], vec![0.0, 0.04]];
+        assert!(close(&hierarchical_risk_parity(&diag), &[0.8, 0.2], 1e-12));
+    }
+
+    #[test]
+    fn test_covariance_estimators() {
+        let returns = vec![
+            vec![0.01, -0.02, 0.015, 0.003, -0.01, 0.02],
+            vec![0.008, -0.01, 0.01, 0.0, -0.004, 0.012],
+        ];
+        let sample = covariance(&returns);
+        assert!(sample[0][0] > sample[1][1] && sample[0][1] > 0.0);
+        let (lw, shrinkage) = ledoit_wolf(&returns);
+        assert!((0.0..=1.0).contains(&shrinkage));
+        assert!((lw[0][1] - lw[1][0]).abs() < 1e-18);
+        // Shrinking towards the identity lowers the off-diagonal term.
+        assert!(lw[0][1].abs() <= sample[0][1].abs());
+        let recent = ewma_covariance(&returns, 0.5);
+        let flat = ewma_covariance(&returns, 1.0);
+        assert!(recent[0][0] > flat[0][0]);
+
+        let data = ReturnMatrix { symbols: vec!["A".into(), "B".into()], returns };
+        let opt = PortfolioOptimizer::new(CovarianceEstimator::LedoitWolf, Optimizer::MinVariance);
+        let targets = opt.targets(&data).unwrap();
+        assert!(matches!(targets["B"], Target::Weight(w) if w > 0.5));
+    }
+}
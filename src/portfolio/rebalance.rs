@@ -0,0 +1,127 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::broker::OrderRequest;
+use crate::data::feed::Symbol;
+use crate::data::order::OrderSide;
+
+/// Where a strategy wants to be in one symbol, rather than what to trade to get there.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    /// Signed number of units (negative = short).
+    Quantity(f64),
+    /// Signed fraction of equity (0.3 = 30% long).
+    Weight(f64),
+}
+
+impl Target {
+    /// Desired signed position at `price` for an account worth `equity`.
+    pub fn quantity(&self, price: f64, equity: f64) -> f64 {
+        match *self {
+            Target::Quantity(q) => q,
+            Target::Weight(_) if price.is_nan() || price <= 0.0 => 0.0,
+            Target::Weight(w) => w * equity / price,
+        }
+    }
+}
+
+/// Filters that keep rebalancing from churning on small differences.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RebalanceConfig {
+    /// Orders smaller than this many units are dropped.
+    pub min_quantity: f64,
+    /// Orders worth less than this are dropped.
+    pub min_notional: f64,
+    /// Skip a symbol while its position is within this fraction of equity of the target
+    /// (0.02 = 2 percentage points of weight).
+    pub threshold: f64,
+    /// Orders are rounded down to a multiple of this, when set.
+    pub lot_size: Option<f64>,
+}
+
+impl Default for RebalanceConfig {
+    fn default() -> Self {
+        Self {
+            min_quantity: 0.0,
+            min_notional: 0.0,
+            threshold: 0.0,
+            lot_size: None,
+        }
+    }
+}
+
+/// Market orders that move `positions` to `targets`. Symbols without a target are left
+/// alone; target `Quantity(0.0)` to exit. Symbols without a price are skipped. Sells
+/// come before buys so the cash they free up is available.
+pub fn rebalance_orders(
+    targets: &HashMap<Symbol, Target>,
+    positions: &HashMap<Symbol, f64>,
+    prices: &HashMap<Symbol, f64>,
+    equity: f64,
+    config: &RebalanceConfig,
+) -> Vec<OrderRequest> {
+    let targets: BTreeMap<_, _> = targets.iter().collect();
+    let mut orders = Vec::new();
+    for (symbol, target) in targets {
+        let Some(&price) = prices.get(symbol).filter(|p| **p > 0.0) else {
+            continue;
+        };
+        let current = positions.get(symbol).copied().unwrap_or(0.0);
+        let mut delta = target.quantity(price, equity) - current;
+        if equity > 0.0 && (delta * price).abs() / equity < config.threshold {
+            continue;
+        }
+        if let Some(lot) = config.lot_size.filter(|l| *l > 0.0) {
+            delta = (delta / lot).trunc() * lot;
+        }
+        let quantity = delta.abs();
+        if quantity <= 1e-12 || quantity < config.min_quantity || quantity * price < config.min_notional {
+            continue;
+        }
+        let side = if delta > 0.0 { OrderSide::Buy } else { OrderSide::Sell };
+        orders.push(OrderRequest::market(symbol, side, quantity));
+    }
+    orders.sort_by_key(|o| o.side == OrderSide::Buy);
+    orders
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map<T: Copy>(entries: &[(&str, T)]) -> HashMap<Symbol, T> {
+        entries.iter().map(|(s, v)| (s.to_string(), *v)).collect()
+    }
+
+    #[test]
+    fn test_rebalance_to_weights_and_quantities() {
+        let prices = map(&[("BTC", 100.0), ("ETH", 10.0), ("SOL", 5.0)]);
+        let positions = map(&[("BTC", 1.0), ("ETH", 20.0), ("XRP", 7.0)]);
+        let targets = map(&[
+            ("BTC", Target::Weight(0.5)),
+            ("ETH", Target::Quantity(0.0)),
+            ("SOL", Target::Weight(-0.1)),
+        ]);
+        let orders = rebalance_orders(&targets, &positions, &prices, 1_000.0, &RebalanceConfig::default());
+        assert_eq!(orders, vec![
+            OrderRequest::market("ETH", OrderSide::Sell, 20.0),
+            OrderRequest::market("SOL", OrderSide::Sell, 20.0),
+            OrderRequest::market("BTC", OrderSide::Buy, 4.0),
+        ]);
+    }
+
+    #[test]
+    fn test_thresholds_and_lots() {
+        let prices = map(&[("BTC", 100.0), ("ETH", 10.0)]);
+        let positions = map(&[("BTC", 2.9), ("ETH", 0.0)]);
+        let targets = map(&[("BTC", Target::Weight(0.3)), ("ETH", Target::Weight(0.057))]);
+        // BTC is one point of weight off, inside the 2% band.
+        let config = RebalanceConfig { threshold: 0.02, ..Default::default() };
+        assert_eq!(rebalance_orders(&targets, &positions, &prices, 1_000.0, &config), vec![
+            OrderRequest::market("ETH", OrderSide::Buy, 5.7),
+        ]);
+        let config = RebalanceConfig { lot_size: Some(1.0), min_notional: 20.0, ..Default::default() };
+        assert_eq!(rebalance_orders(&targets, &positions, &prices, 1_000.0, &config), vec![
+            OrderRequest::market("ETH", OrderSide::Buy, 5.0),
+        ]);
+    }
+}
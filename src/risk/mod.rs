@@ -0,0 +1,305 @@
+pub mod sizing;
+
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+
+use crate::broker::OrderRequest;
+use crate::data::feed::Symbol;
+use crate::data::order::OrderSide;
+
+/// Pre-trade limits. Unset limits are not enforced.
+#[derive(Debug, Clone, Default)]
+pub struct RiskLimits {
+    /// Largest absolute position per symbol, unless overridden in `position_limits`.
+    pub max_position: Option<f64>,
+    pub position_limits: HashMap<Symbol, f64>,
+    pub max_order_notional: Option<f64>,
+    pub max_open_orders: Option<usize>,
+    /// Loss from the day's opening equity, in account currency, that halts new risk for the day.
+    pub max_daily_loss: Option<f64>,
+    /// Drawdown from peak equity (0.2 = 20%) that flattens everything and halts for good.
+    pub max_drawdown: Option<f64>,
+}
+
+impl RiskLimits {
+    pub fn with_max_position(mut self, quantity: f64) -> Self {
+        self.max_position = Some(quantity);
+        self
+    }
+
+    pub fn with_position_limit(mut self, symbol: &str, quantity: f64) -> Self {
+        self.position_limits.insert(symbol.to_string(), quantity);
+        self
+    }
+
+    pub fn with_max_order_notional(mut self, notional: f64) -> Self {
+        self.max_order_notional = Some(notional);
+        self
+    }
+
+    pub fn with_max_open_orders(mut self, count: usize) -> Self {
+        self.max_open_orders = Some(count);
+        self
+    }
+
+    pub fn with_max_daily_loss(mut self, loss: f64) -> Self {
+        self.max_daily_loss = Some(loss);
+        self
+    }
+
+    pub fn with_max_drawdown(mut self, fraction: f64) -> Self {
+        self.max_drawdown = Some(fraction);
+        self
+    }
+
+    fn position_limit(&self, symbol: &str) -> Option<f64> {
+        self.position_limits.get(symbol).copied().or(self.max_position)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum RejectReason {
+    #[error("trading halted by the drawdown kill switch")]
+    Halted,
+    #[error("daily loss {loss:.2} reached the {limit:.2} limit")]
+    DailyLoss { loss: f64, limit: f64 },
+    #[error("{symbol} position would be {resulting} (limit {limit})")]
+    PositionLimit { symbol: Symbol, resulting: f64, limit: f64 },
+    #[error("order notional {notional:.2} exceeds {limit:.2}")]
+    OrderNotional { notional: f64, limit: f64 },
+    #[error("{open} orders already open (limit {limit})")]
+    OpenOrders { open: usize, limit: usize },
+    #[error("needs {required:.2} cash, {available:.2} available")]
+    InsufficientCash { required: f64, available: f64 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RiskEvent {
+    Rejected { timestamp: String, request: OrderRequest, reason: RejectReason },
+    /// New risk is blocked until the next day.
+    DailyLossBreached { timestamp: String, loss: f64 },
+    /// Positions are being flattened and trading is halted.
+    KillSwitch { timestamp: String, drawdown: f64, equity: f64 },
+}
+
+/// Sits between strategy signals and execution. Orders that only reduce a position
+/// skip every check, so exits are never blocked.
+#[derive(Debug, Clone, Default)]
+pub struct RiskManager {
+    pub limits: RiskLimits,
+    peak_equity: f64,
+    day: Option<NaiveDate>,
+    day_start_equity: f64,
+    last_equity: f64,
+    day_halted: bool,
+    killed: bool,
+    events: Vec<RiskEvent>,
+}
+
+impl RiskManager {
+    pub fn new(limits: RiskLimits) -> Self {
+        Self { limits, ..Default::default() }
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.killed
+    }
+
+    pub fn events(&self) -> &[RiskEvent] {
+        &self.events
+    }
+
+    /// Hands over events raised since the last call, e.g. to pass on to the strategy.
+    pub fn take_events(&mut self) -> Vec<RiskEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Checks `request` at `price` against the current `position` in its symbol and the
+    /// number of orders already working. Rejections are also recorded as events.
+    pub fn check(
+        &mut self,
+        request: &OrderRequest,
+        price: f64,
+        position: f64,
+        open_orders: usize,
+        timestamp: &str,
+    ) -> Result<(), RejectReason> {
+        let result = self.evaluate(request, price, position, open_orders);
+        if let Err(reason) = &result {
+            self.record_rejection(request, reason.clone(), timestamp);
+        }
+        result
+    }
+
+    fn evaluate(&self, request: &OrderRequest, price: f64, position: f64, open_orders: usize) -> Result<(), RejectReason> {
+        let signed = match request.side {
+            OrderSide::Buy => request.quantity,
+            OrderSide::Sell => -request.quantity,
+        };
+        let resulting = position + signed;
+        let reducing = resulting.abs() <= position.abs() && resulting * position >= 0.0;
+        if reducing {
+            // A position grown past the notional cap by price moves must still be closable.
+            return Ok(());
+        }
+
+        if self.killed {
+            return Err(RejectReason::Halted);
+        }
+        if self.day_halted {
+            return Err(RejectReason::DailyLoss {
+                loss: self.day_start_equity - self.last_equity,
+                limit: self.limits.max_daily_loss.unwrap_or_default(),
+            });
+        }
+        if let Some(limit) = self.limits.position_limit(&request.symbol)
+            && resulting.abs() > limit + 1e-12
+        {
+            return Err(RejectReason::PositionLimit { symbol: request.symbol.clone(), resulting, limit });
+        }
+        let notional = request.quantity * price;
+        if let Some(limit) = self.limits.max_order_notional
+            && notional > limit
+        {
+            return Err(RejectReason::OrderNotional { notional, limit });
+        }
+        if let Some(limit) = self.limits.max_open_orders
+            && open_orders >= limit
+        {
+            return Err(RejectReason::OpenOrders { open: open_orders, limit });
+        }
+        Ok(())
+    }
+
+    /// Records a rejection raised elsewhere (e.g. by the cash check) so it reaches the strategy.
+    pub fn record_rejection(&mut self, request: &OrderRequest, reason: RejectReason, timestamp: &str) {
+        self.events.push(RiskEvent::Rejected {
+            timestamp: timestamp.to_string(),
+            request: request.clone(),
+            reason,
+        });
+    }
+
+    /// Feeds the latest marked equity. Returns true the moment the drawdown kill switch
+    /// trips; the caller should then flatten (see `flatten_orders`) and cancel working orders.
+    pub fn on_equity(&mut self, equity: f64, date: NaiveDate, timestamp: &str) -> bool {
+        if self.day != Some(date) {
+            self.day = Some(date);
+            self.day_start_equity = equity;
+            self.day_halted = false;
+        }
+        self.peak_equity = self.peak_equity.max(equity);
+        self.last_equity = equity;
+
+        let loss = self.day_start_equity - equity;
+        if let Some(limit) = self.limits.max_daily_loss
+            && !self.day_halted
+            && loss >= limit
+        {
+            self.day_halted = true;
+            self.events.push(RiskEvent::DailyLossBreached { timestamp: timestamp.to_string(), loss });
+        }
+
+        if let Some(limit) = self.limits.max_drawdown
+            && !self.killed
+            && self.peak_equity > 0.0
+        {
+            let drawdown = 1.0 - equity / self.peak_equity;
+            if drawdown >= limit {
+                self.killed = true;
+                self.events.push(RiskEvent::KillSwitch { timestamp: timestamp.to_string(), drawdown, equity });
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Market orders that close every non-zero position.
+    pub fn flatten_orders(positions: &HashMap<Symbol, f64>) -> Vec<OrderRequest> {
+        let mut symbols: Vec<_> = positions.iter().filter(|(_, q)| q.abs() > 1e-12).collect();
+        symbols.sort_by(|a, b| a.0.cmp(b.0));
+        symbols
+            .into_iter()
+            .map(|(symbol, qty)| {
+                let side = if *qty > 0.0 { OrderSide::Sell } else { OrderSide::Buy };
+                OrderRequest::market(symbol, side, qty.abs())
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 1, d).unwrap()
+    }
+
+    #[test]
+    fn test_pre_trade_limits() {
+        let limits = RiskLimits::default()
+            .with_max_position(2.0)
+            .with_position_limit("ETH", 10.0)
+            .with_max_order_notional(1_000.0)
+            .with_max_open_orders(3);
+        let mut risk = RiskManager::new(limits);
+        let buy = OrderRequest::market("BTC", OrderSide::Buy, 1.0);
+
+        assert!(risk.check(&buy, 100.0, 1.0, 0, "t").is_ok());
+        assert!(matches!(risk.check(&buy, 100.0, 2.0, 0, "t"), Err(RejectReason::PositionLimit { .. })));
+        assert!(risk.check(&OrderRequest::market("ETH", OrderSide::Buy, 5.0), 100.0, 2.0, 0, "t").is_ok());
+        assert!(matches!(risk.check(&buy, 5_000.0, 0.0, 0, "t"), Err(RejectReason::OrderNotional { .. })));
+        assert!(matches!(risk.check(&buy, 100.0, 0.0, 3, "t"), Err(RejectReason::OpenOrders { .. })));
+        // Selling down from over the limit is still allowed.
+        assert!(risk.check(&OrderRequest::market("BTC", OrderSide::Sell, 1.0), 100.0, 5.0, 0, "t").is_ok());
+        assert_eq!(risk.take_events().len(), 3);
+        assert!(risk.events().is_empty());
+    }
+
+    #[test]
+    fn test_exit_passes_notional_and_open_order_caps() {
+        let limits = RiskLimits::default().with_max_order_notional(1_000.0).with_max_open_orders(1);
+        let mut risk = RiskManager::new(limits);
+        // Opened at 900 of notional, then the price doubles.
+        assert!(risk.check(&OrderRequest::market("BTC", OrderSide::Buy, 9.0), 100.0, 0.0, 0, "t1").is_ok());
+        let exit = OrderRequest::market("BTC", OrderSide::Sell, 9.0);
+        assert!(risk.check(&exit, 200.0, 9.0, 1, "t2").is_ok());
+        // Going past flat into a short is a new position and is checked.
+        let reverse = OrderRequest::market("BTC", OrderSide::Sell, 18.0);
+        assert!(matches!(risk.check(&reverse, 200.0, 9.0, 0, "t2"), Err(RejectReason::OrderNotional { .. })));
+    }
+
+    #[test]
+    fn test_daily_loss_halts_until_next_day() {
+        let mut risk = RiskManager::new(RiskLimits::default().with_max_daily_loss(100.0));
+        let buy = OrderRequest::market("BTC", OrderSide::Buy, 1.0);
+        risk.on_equity(1_000.0, day(1), "d1");
+        risk.on_equity(890.0, day(1), "d1");
+        assert!(matches!(risk.check(&buy, 10.0, 0.0, 0, "d1"), Err(RejectReason::DailyLoss { .. })));
+        assert!(risk.check(&OrderRequest::market("BTC", OrderSide::Sell, 1.0), 10.0, 1.0, 0, "d1").is_ok());
+        risk.on_equity(880.0, day(2), "d2");
+        assert!(risk.check(&buy, 10.0, 0.0, 0, "d2").is_ok());
+        assert!(matches!(risk.events()[0], RiskEvent::DailyLossBreached { .. }));
+    }
+
+    #[test]
+    fn test_kill_switch_flattens_and_halts() {
+        let mut risk = RiskManager::new(RiskLimits::default().with_max_drawdown(0.2));
+        assert!(!risk.on_equity(1_000.0, day(1), "t1"));
+        assert!(!risk.on_equity(1_200.0, day(2), "t2"));
+        assert!(risk.on_equity(950.0, day(3), "t3"));
+        assert!(!risk.on_equity(900.0, day(3), "t4"));
+        assert!(risk.is_halted());
+        let buy = OrderRequest::market("BTC", OrderSide::Buy, 1.0);
+        assert_eq!(risk.check(&buy, 10.0, 0.0, 0, "t5"), Err(RejectReason::Halted));
+
+        let positions = HashMap::from([("BTC".to_string(), 2.0), ("ETH".to_string(), -3.0), ("SOL".to_string(), 0.0)]);
+        let orders = RiskManager::flatten_orders(&positions);
+        assert_eq!(orders, vec![
+            OrderRequest::market("BTC", OrderSide::Sell, 2.0),
+            OrderRequest::market("ETH", OrderSide::Buy, 3.0),
+        ]);
+    }
+}
@@ -0,0 +1,211 @@
+use std::fmt::Debug;
+
+use crate::data::order::Signal;
+
+/// Account state a sizer may draw on.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SizingContext {
+    pub equity: f64,
+    pub cash: f64,
+    /// Expected fill price.
+    pub price: f64,
+    /// Current signed position in the symbol.
+    pub position: f64,
+    pub atr: Option<f64>,
+}
+
+/// Turns a signal into an order quantity, kept apart from signal generation so the
+/// same strategy can be run under different sizing rules. Quantities are scaled by the
+/// signal's conviction; 0 means don't trade.
+pub trait PositionSizer: Debug + Send + Sync {
+    /// Quantity for a signal that adds to (or opens) the position.
+    fn size(&self, signal: &Signal, ctx: &SizingContext) -> f64;
+
+    /// Quantity for a signal against the position. Callers cap it at the position so
+    /// an exit never reverses it; by default the whole position is closed.
+    fn exit_size(&self, _signal: &Signal, ctx: &SizingContext) -> f64 {
+        ctx.position.abs()
+    }
+}
+
+/// Trades the quantity on the strategy's own order, for entries and exits alike.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct StrategyQuantity;
+
+impl PositionSizer for StrategyQuantity {
+    fn size(&self, signal: &Signal, _ctx: &SizingContext) -> f64 {
+        signal.quantity.unwrap_or(0.0) * conviction(signal)
+    }
+
+    fn exit_size(&self, signal: &Signal, ctx: &SizingContext) -> f64 {
+        signal.quantity.map_or(ctx.position.abs(), |q| q * conviction(signal))
+    }
+}
+
+fn conviction(signal: &Signal) -> f64 {
+    if signal.conviction.is_nan() { 0.0 } else { signal.conviction.clamp(0.0, 1.0) }
+}
+
+/// Quantity for `notional` at the context price, or 0 when there is no usable price.
+fn units(notional: f64, ctx: &SizingContext) -> f64 {
+    if ctx.price.is_nan() || ctx.price <= 0.0 || notional.is_nan() {
+        return 0.0;
+    }
+    (notional / ctx.price).max(0.0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FixedQuantity(pub f64);
+
+impl PositionSizer for FixedQuantity {
+    fn size(&self, signal: &Signal, _ctx: &SizingContext) -> f64 {
+        self.0 * conviction(signal)
+    }
+}
+
+/// The same amount of account currency per trade.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FixedNotional(pub f64);
+
+impl PositionSizer for FixedNotional {
+    fn size(&self, signal: &Signal, ctx: &SizingContext) -> f64 {
+        units(self.0 * conviction(signal), ctx)
+    }
+}
+
+/// A fraction of current equity per trade (1.0 = all in).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PercentEquity(pub f64);
+
+impl PositionSizer for PercentEquity {
+    fn size(&self, signal: &Signal, ctx: &SizingContext) -> f64 {
+        units(ctx.equity * self.0 * conviction(signal), ctx)
+    }
+}
+
+/// Sizes so that being stopped out loses `risk_fraction` of equity. The stop distance is
+/// the signal's own stop, or `atr_multiple` ATRs when the signal has none.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RiskPerTrade {
+    pub risk_fraction: f64,
+    pub atr_multiple: Option<f64>,
+}
+
+impl PositionSizer for RiskPerTrade {
+    fn size(&self, signal: &Signal, ctx: &SizingContext) -> f64 {
+        let distance = match (signal.stop, self.atr_multiple, ctx.atr) {
+            (Some(stop), _, _) => (ctx.price - stop).abs(),
+            (None, Some(k), Some(atr)) => k * atr,
+            _ => return 0.0,
+        };
+        if distance.is_nan() || distance <= 0.0 {
+            return 0.0;
+        }
+        (ctx.equity * self.risk_fraction * conviction(signal) / distance).max(0.0)
+    }
+}
+
+/// Scales exposure inversely to volatility so each position contributes `target`
+/// (per-bar volatility, ATR / price) to the account, capped at `max_leverage` × equity.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VolatilityTarget {
+    pub target: f64,
+    pub max_leverage: f64,
+}
+
+impl PositionSizer for VolatilityTarget {
+    fn size(&self, signal: &Signal, ctx: &SizingContext) -> f64 {
+        let Some(atr) = ctx.atr.filter(|a| *a > 0.0) else {
+            return 0.0;
+        };
+        let vol = atr / ctx.price;
+        let exposure = (self.target / vol).min(self.max_leverage);
+        units(ctx.equity * exposure * conviction(signal), ctx)
+    }
+}
+
+/// Fractional Kelly: stakes `fraction` of the Kelly-optimal share of equity
+/// `p - (1 - p) / b`, where `p` is the win rate and `b` the average win over the
+/// average loss. Negative edges size to 0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Kelly {
+    pub fraction: f64,
+    pub win_rate: f64,
+    pub payoff_ratio: f64,
+}
+
+impl Kelly {
+    /// Estimates win rate and payoff ratio from closed trade PnLs. `None` until there is
+    /// at least one win and one loss.
+    pub fn from_trades(pnls: &[f64], fraction: f64) -> Option<Self> {
+        let (wins, losses): (Vec<f64>, Vec<f64>) = pnls.iter().partition(|p| **p > 0.0);
+        let losses: Vec<f64> = losses.into_iter().filter(|p| *p < 0.0).collect();
+        if wins.is_empty() || losses.is_empty() {
+            return None;
+        }
+        let avg_win = wins.iter().sum::<f64>() / wins.len() as f64;
+        let avg_loss = -losses.iter().sum::<f64>() / losses.len() as f64;
+        Some(Self {
+            fraction,
+            win_rate: wins.len() as f64 / (wins.len() + losses.len()) as f64,
+            payoff_ratio: avg_win / avg_loss,
+        })
+    }
+
+    pub fn kelly_fraction(&self) -> f64 {
+        if self.payoff_ratio <= 0.0 {
+            return 0.0;
+        }
+        (self.win_rate - (1.0 - self.win_rate) / self.payoff_ratio).max(0.0)
+    }
+}
+
+impl PositionSizer for Kelly {
+    fn size(&self, signal: &Signal, ctx: &SizingContext) -> f64 {
+        units(ctx.equity * self.fraction * self.kelly_fraction() * conviction(signal), ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::order::OrderSide;
+
+    fn signal(conviction: f64, stop: Option<f64>) -> Signal {
+        Signal { side: OrderSide::Buy, conviction, price: 100.0, timestamp: "t".into(), stop, quantity: None }
+    }
+
+    #[test]
+    fn test_sizers() {
+        let ctx = SizingContext { equity: 10_000.0, cash: 10_000.0, price: 100.0, position: 0.0, atr: Some(2.0) };
+        let full = signal(1.0, None);
+        assert_eq!(FixedQuantity(3.0).size(&signal(0.5, None), &ctx), 1.5);
+        assert_eq!(FixedNotional(1_000.0).size(&full, &ctx), 10.0);
+        assert_eq!(PercentEquity(0.5).size(&full, &ctx), 50.0);
+        // 1% of equity over a 5-point stop; or 2 ATRs = 4 points without one.
+        let rpt = RiskPerTrade { risk_fraction: 0.01, atr_multiple: Some(2.0) };
+        assert_eq!(rpt.size(&signal(1.0, Some(95.0)), &ctx), 20.0);
+        assert_eq!(rpt.size(&full, &ctx), 25.0);
+        assert_eq!(rpt.size(&full, &SizingContext { atr: None, ..ctx }), 0.0);
+        // ATR/price = 2%; a 1% target means half the equity, a 10% target hits the 2x cap.
+        assert_eq!(VolatilityTarget { target: 0.01, max_leverage: 2.0 }.size(&full, &ctx), 50.0);
+        assert_eq!(VolatilityTarget { target: 0.1, max_leverage: 2.0 }.size(&full, &ctx), 200.0);
+        // Exits close the position unless the strategy's own quantity is being traded.
+        let long = SizingContext { position: 7.0, ..ctx };
+        assert_eq!(PercentEquity(0.5).exit_size(&full, &long), 7.0);
+        let ordered = Signal { quantity: Some(2.0), ..full.clone() };
+        assert_eq!((StrategyQuantity.size(&ordered, &ctx), StrategyQuantity.exit_size(&ordered, &long)), (2.0, 2.0));
+    }
+
+    #[test]
+    fn test_kelly() {
+        let kelly = Kelly::from_trades(&[20.0, 20.0, 20.0, -10.0, -10.0, 0.0], 0.5).unwrap();
+        assert_eq!((kelly.win_rate, kelly.payoff_ratio), (0.6, 2.0));
+        assert!((kelly.kelly_fraction() - 0.4).abs() < 1e-12);
+        let ctx = SizingContext { equity: 10_000.0, price: 100.0, ..Default::default() };
+        assert!((kelly.size(&signal(1.0, None), &ctx) - 20.0).abs() < 1e-9);
+        let losing = Kelly { fraction: 1.0, win_rate: 0.3, payoff_ratio: 1.0 };
+        assert_eq!(losing.size(&signal(1.0, None), &ctx), 0.0);
+        assert!(Kelly::from_trades(&[1.0, 2.0], 0.5).is_none());
+    }
+}
@@ -1,8 +1,12 @@
+pub mod order_book;
+pub mod synthetic;
+
 use std::sync::Arc;
 
 use crate::strategy::Strategy;
 use crate::data::{bar::Bar, order::Order};
 
+/// Running state of a simulated market.
 pub struct Market {
     pub last_close: f64,
 }
@@ -16,14 +20,12 @@ impl Market {
 pub fn run_simulation(strategy: &dyn Strategy, bars: Arc<Vec<Bar>>) -> Vec<Order> {
     let mut orders = Vec::new();
 
-    let mut index =  1;
     let bars = Arc::clone(&bars);
-    for bar in bars.iter() {
+    for (index, bar) in (1..).zip(bars.iter()) {
         if let Some(order) = strategy.generate_signal(bar) {
             orders.push(order);
         }
         println!("{} : {:?}", index, bar);
-        index = index + 1;
     }
 
     orders
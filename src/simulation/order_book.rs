@@ -0,0 +1,452 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::error::Error;
+
+use csv::ReaderBuilder;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::data::order::OrderSide;
+
+pub type OrderId = u64;
+
+const EPS: f64 = 1e-12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeInForce {
+    /// Rest on the book until filled or cancelled.
+    Gtc,
+    /// Fill what crosses immediately, drop the rest.
+    Ioc,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RestingOrder {
+    pub id: OrderId,
+    pub side: OrderSide,
+    pub price: f64,
+    pub quantity: f64,
+    pub timestamp: String,
+}
+
+/// A trade print. `price` is always the resting (maker) order's price.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trade {
+    pub timestamp: String,
+    pub price: f64,
+    pub quantity: f64,
+    pub aggressor: OrderSide,
+    pub maker_id: OrderId,
+    pub taker_id: OrderId,
+}
+
+/// Aggregated price levels, best first: `(price, total quantity, order count)`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BookSnapshot {
+    pub timestamp: String,
+    pub bids: Vec<(f64, f64, usize)>,
+    pub asks: Vec<(f64, f64, usize)>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SubmitResult {
+    pub id: OrderId,
+    pub trades: Vec<Trade>,
+    /// Quantity left resting on the book (0 for filled or IOC orders).
+    pub resting: f64,
+}
+
+/// Price-time priority limit order book. Prices are snapped to `tick_size`.
+#[derive(Debug, Clone)]
+pub struct OrderBook {
+    pub tick_size: f64,
+    bids: BTreeMap<i64, VecDeque<RestingOrder>>,
+    asks: BTreeMap<i64, VecDeque<RestingOrder>>,
+    index: HashMap<OrderId, (OrderSide, i64)>,
+    next_id: OrderId,
+}
+
+impl OrderBook {
+    pub fn new(tick_size: f64) -> Self {
+        Self {
+            tick_size,
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            index: HashMap::new(),
+            next_id: 1,
+        }
+    }
+
+    fn tick(&self, price: f64) -> i64 {
+        (price / self.tick_size).round() as i64
+    }
+
+    fn price(&self, tick: i64) -> f64 {
+        tick as f64 * self.tick_size
+    }
+
+    pub fn best_bid(&self) -> Option<f64> {
+        self.bids.keys().next_back().map(|&t| self.price(t))
+    }
+
+    pub fn best_ask(&self) -> Option<f64> {
+        self.asks.keys().next().map(|&t| self.price(t))
+    }
+
+    pub fn mid(&self) -> Option<f64> {
+        Some((self.best_bid()? + self.best_ask()?) / 2.0)
+    }
+
+    pub fn spread(&self) -> Option<f64> {
+        Some(self.best_ask()? - self.best_bid()?)
+    }
+
+    pub fn order(&self, id: OrderId) -> Option<&RestingOrder> {
+        let (side, tick) = self.index.get(&id)?;
+        self.levels(side).get(tick)?.iter().find(|o| o.id == id)
+    }
+
+    /// Quantity queued ahead of `id` at its price level.
+    pub fn queue_ahead(&self, id: OrderId) -> Option<f64> {
+        let (side, tick) = self.index.get(&id)?;
+        let level = self.levels(side).get(tick)?;
+        let pos = level.iter().position(|o| o.id == id)?;
+        Some(level.iter().take(pos).map(|o| o.quantity).sum())
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Ids of every resting order, bids then asks.
+    pub fn order_ids(&self) -> Vec<OrderId> {
+        self.bids.values().chain(self.asks.values()).flatten().map(|o| o.id).collect()
+    }
+
+    pub fn submit_limit(&mut self, side: OrderSide, price: f64, quantity: f64, tif: TimeInForce, timestamp: &str) -> SubmitResult {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.place(id, side, price, quantity, tif, timestamp)
+    }
+
+    /// Sweeps the opposite side until filled or the book runs dry; nothing rests.
+    pub fn submit_market(&mut self, side: OrderSide, quantity: f64, timestamp: &str) -> SubmitResult {
+        let id = self.next_id;
+        self.next_id += 1;
+        let (trades, _) = self.match_incoming(id, side, None, quantity, timestamp);
+        SubmitResult { id, trades, resting: 0.0 }
+    }
+
+    pub fn cancel(&mut self, id: OrderId) -> Option<RestingOrder> {
+        let (side, tick) = self.index.remove(&id)?;
+        let levels = match side {
+            OrderSide::Buy => &mut self.bids,
+            OrderSide::Sell => &mut self.asks,
+        };
+        let level = levels.get_mut(&tick)?;
+        let pos = level.iter().position(|o| o.id == id)?;
+        let order = level.remove(pos);
+        if level.is_empty() {
+            levels.remove(&tick);
+        }
+        order
+    }
+
+    /// Changes price and/or quantity. Reducing quantity at the same price keeps queue
+    /// position; any price change or size increase re-queues at the back and may trade.
+    pub fn modify(&mut self, id: OrderId, price: f64, quantity: f64, timestamp: &str) -> Result<SubmitResult, Box<dyn Error + Send + Sync>> {
+        let (side, tick) = *self.index.get(&id).ok_or_else(|| format!("Unknown order id {}", id))?;
+        if quantity <= EPS {
+            self.cancel(id);
+            return Ok(SubmitResult { id, ..Default::default() });
+        }
+        if self.tick(price) == tick
+            && let Some(order) = self
+                .levels_mut(&side)
+                .get_mut(&tick)
+                .and_then(|level| level.iter_mut().find(|o| o.id == id))
+            && quantity <= order.quantity
+        {
+            order.quantity = quantity;
+            return Ok(SubmitResult { id, trades: Vec::new(), resting: quantity });
+        }
+        self.cancel(id);
+        Ok(self.place(id, side, price, quantity, TimeInForce::Gtc, timestamp))
+    }
+
+    /// Aggregated top `depth` levels per side.
+    pub fn snapshot(&self, depth: usize, timestamp: &str) -> BookSnapshot {
+        let agg = |(tick, level): (&i64, &VecDeque<RestingOrder>)| {
+            (self.price(*tick), level.iter().map(|o| o.quantity).sum(), level.len())
+        };
+        BookSnapshot {
+            timestamp: timestamp.to_string(),
+            bids: self.bids.iter().rev().take(depth).map(agg).collect(),
+            asks: self.asks.iter().take(depth).map(agg).collect(),
+        }
+    }
+
+    /// Replays a historical print as an IOC order from the aggressor at the print price,
+    /// so resting orders at or through that price fill in priority order.
+    pub fn replay_trade(&mut self, trade: &HistoricalTrade) -> Vec<Trade> {
+        let side = if trade.is_buyer_maker { OrderSide::Sell } else { OrderSide::Buy };
+        self.submit_limit(side, trade.price, trade.quantity, TimeInForce::Ioc, &trade.timestamp).trades
+    }
+
+    fn place(&mut self, id: OrderId, side: OrderSide, price: f64, quantity: f64, tif: TimeInForce, timestamp: &str) -> SubmitResult {
+        let tick = self.tick(price);
+        let (trades, remaining) = self.match_incoming(id, side, Some(tick), quantity, timestamp);
+        let resting = if tif == TimeInForce::Gtc && remaining > EPS {
+            self.index.insert(id, (side, tick));
+            let order = RestingOrder {
+                id,
+                side,
+                price: self.price(tick),
+                quantity: remaining,
+                timestamp: timestamp.to_string(),
+            };
+            self.levels_mut(&side).entry(tick).or_default().push_back(order);
+            remaining
+        } else {
+            0.0
+        };
+        SubmitResult { id, trades, resting }
+    }
+
+    fn match_incoming(&mut self, taker_id: OrderId, side: OrderSide, limit: Option<i64>, mut quantity: f64, timestamp: &str) -> (Vec<Trade>, f64) {
+        let mut trades = Vec::new();
+        while quantity > EPS {
+            let best = match side {
+                OrderSide::Buy => self.asks.keys().next().copied(),
+                OrderSide::Sell => self.bids.keys().next_back().copied(),
+            };
+            let Some(tick) = best else { break };
+            let crosses = match (&side, limit) {
+                (_, None) => true,
+                (OrderSide::Buy, Some(l)) => tick <= l,
+                (OrderSide::Sell, Some(l)) => tick >= l,
+            };
+            if !crosses {
+                break;
+            }
+            let price = self.price(tick);
+            let opposite = match side {
+                OrderSide::Buy => &mut self.asks,
+                OrderSide::Sell => &mut self.bids,
+            };
+            let level = opposite.get_mut(&tick).expect("best level exists");
+            while quantity > EPS
+                && let Some(maker) = level.front_mut()
+            {
+                let fill = quantity.min(maker.quantity);
+                maker.quantity -= fill;
+                quantity -= fill;
+                trades.push(Trade {
+                    timestamp: timestamp.to_string(),
+                    price,
+                    quantity: fill,
+                    aggressor: side,
+                    maker_id: maker.id,
+                    taker_id,
+                });
+                if maker.quantity <= EPS {
+                    let done = level.pop_front().expect("front exists");
+                    self.index.remove(&done.id);
+                }
+            }
+            if level.is_empty() {
+                opposite.remove(&tick);
+            }
+        }
+        (trades, quantity)
+    }
+
+    fn levels(&self, side: &OrderSide) -> &BTreeMap<i64, VecDeque<RestingOrder>> {
+        match side {
+            OrderSide::Buy => &self.bids,
+            OrderSide::Sell => &self.asks,
+        }
+    }
+
+    fn levels_mut(&mut self, side: &OrderSide) -> &mut BTreeMap<i64, VecDeque<RestingOrder>> {
+        match side {
+            OrderSide::Buy => &mut self.bids,
+            OrderSide::Sell => &mut self.asks,
+        }
+    }
+}
+
+/// One historical trade print, as in Binance `aggTrades` / `trades` dumps.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoricalTrade {
+    pub timestamp: String,
+    pub price: f64,
+    pub quantity: f64,
+    /// True when the buyer was the resting side, i.e. the aggressor sold.
+    pub is_buyer_maker: bool,
+}
+
+/// Loads Binance `aggTrades` CSV rows:
+/// `agg_trade_id,price,quantity,first_trade_id,last_trade_id,transact_time,is_buyer_maker[,is_best_match]`.
+/// Header lines are skipped; timestamps stay in epoch milliseconds.
+pub fn load_agg_trades(path: &str) -> Result<Vec<HistoricalTrade>, Box<dyn Error + Send + Sync>> {
+    let mut rdr = ReaderBuilder::new().has_headers(false).flexible(true).from_path(path)?;
+    let mut trades = Vec::new();
+    for record in rdr.records() {
+        let record = record?;
+        if record.len() < 7 {
+            return Err(format!("Expected 7 aggTrades columns, got {}", record.len()).into());
+        }
+        let Ok(price) = record[1].parse::<f64>() else { continue }; // header
+        trades.push(HistoricalTrade {
+            timestamp: record[5].to_string(),
+            price,
+            quantity: record[2].parse()?,
+            is_buyer_maker: record[6].eq_ignore_ascii_case("true"),
+        });
+    }
+    Ok(trades)
+}
+
+/// Background order flow around the mid: Poisson-ish arrivals of limit orders,
+/// market orders and cancels, for testing strategies without historical data.
+#[derive(Debug, Clone)]
+pub struct OrderFlowConfig {
+    pub seed: u64,
+    /// Per-step probabilities of each event type.
+    pub limit_prob: f64,
+    pub market_prob: f64,
+    pub cancel_prob: f64,
+    /// Limit orders are placed up to this many ticks away from the touch.
+    pub depth_ticks: u32,
+    pub mean_size: f64,
+    /// Mid used to seed an empty book.
+    pub initial_mid: f64,
+}
+
+impl Default for OrderFlowConfig {
+    fn default() -> Self {
+        Self {
+            seed: 42,
+            limit_prob: 0.6,
+            market_prob: 0.15,
+            cancel_prob: 0.25,
+            depth_ticks: 10,
+            mean_size: 1.0,
+            initial_mid: 100.0,
+        }
+    }
+}
+
+pub struct OrderFlow {
+    config: OrderFlowConfig,
+    rng: StdRng,
+    /// Ids this generator placed, so it only cancels its own orders.
+    live: Vec<OrderId>,
+}
+
+impl OrderFlow {
+    pub fn new(config: OrderFlowConfig) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(config.seed),
+            config,
+            live: Vec::new(),
+        }
+    }
+
+    /// Draws one event and applies it to `book`, returning any trades it caused.
+    pub fn step(&mut self, book: &mut OrderBook, timestamp: &str) -> Vec<Trade> {
+        self.live.retain(|id| book.order(*id).is_some());
+        let side = if self.rng.gen_bool(0.5) { OrderSide::Buy } else { OrderSide::Sell };
+        let size = self.config.mean_size * -(1.0 - self.rng.r#gen::<f64>()).ln();
+        let u = self.rng.r#gen::<f64>() * (self.config.limit_prob + self.config.market_prob + self.config.cancel_prob);
+
+        if u < self.config.limit_prob || book.is_empty() {
+            let mid = book.mid().unwrap_or(self.config.initial_mid);
+            let offset = (self.rng.gen_range(0..=self.config.depth_ticks) as f64 + 0.5) * book.tick_size;
+            let price = match side {
+                OrderSide::Buy => mid - offset,
+                OrderSide::Sell => mid + offset,
+            };
+            let result = book.submit_limit(side, price, size, TimeInForce::Gtc, timestamp);
+            if result.resting > 0.0 {
+                self.live.push(result.id);
+            }
+            result.trades
+        } else if u < self.config.limit_prob + self.config.market_prob {
+            book.submit_market(side, size, timestamp).trades
+        } else {
+            if !self.live.is_empty() {
+                let id = self.live.swap_remove(self.rng.gen_range(0..self.live.len()));
+                book.cancel(id);
+            }
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_price_time_priority() {
+        let mut book = OrderBook::new(0.5);
+        let a = book.submit_limit(OrderSide::Buy, 100.0, 1.0, TimeInForce::Gtc, "t1").id;
+        let b = book.submit_limit(OrderSide::Buy, 100.0, 2.0, TimeInForce::Gtc, "t2").id;
+        let c = book.submit_limit(OrderSide::Buy, 100.5, 1.0, TimeInForce::Gtc, "t3").id;
+        assert_eq!(book.queue_ahead(b), Some(1.0));
+
+        let sell = book.submit_limit(OrderSide::Sell, 99.0, 3.0, TimeInForce::Gtc, "t4");
+        let makers: Vec<_> = sell.trades.iter().map(|t| (t.maker_id, t.price, t.quantity)).collect();
+        assert_eq!(makers, vec![(c, 100.5, 1.0), (a, 100.0, 1.0), (b, 100.0, 1.0)]);
+        assert_eq!(sell.resting, 0.0);
+        assert_eq!(book.order(b).unwrap().quantity, 1.0);
+        assert_eq!(book.best_ask(), None);
+    }
+
+    #[test]
+    fn test_modify_and_cancel() {
+        let mut book = OrderBook::new(0.01);
+        let a = book.submit_limit(OrderSide::Sell, 10.0, 5.0, TimeInForce::Gtc, "t1").id;
+        let b = book.submit_limit(OrderSide::Sell, 10.0, 5.0, TimeInForce::Gtc, "t2").id;
+        book.modify(a, 10.0, 3.0, "t3").unwrap();
+        assert_eq!(book.queue_ahead(a), Some(0.0));
+        book.modify(a, 10.0, 4.0, "t4").unwrap();
+        assert_eq!(book.queue_ahead(a), Some(5.0));
+        assert_eq!(book.cancel(b).unwrap().quantity, 5.0);
+        assert_eq!(book.queue_ahead(a), Some(0.0));
+        assert!(book.modify(b, 10.0, 1.0, "t5").is_err());
+
+        let snap = book.snapshot(5, "t6");
+        assert_eq!(snap.asks, vec![(10.0, 4.0, 1)]);
+        let fill = book.submit_market(OrderSide::Buy, 10.0, "t7");
+        assert_eq!(fill.trades.len(), 1);
+        assert!(book.is_empty());
+    }
+
+    #[test]
+    fn test_replay_and_synthetic_flow() {
+        let mut book = OrderBook::new(0.1);
+        let mine = book.submit_limit(OrderSide::Buy, 99.9, 2.0, TimeInForce::Gtc, "0").id;
+        let print = HistoricalTrade { timestamp: "1".into(), price: 99.9, quantity: 0.5, is_buyer_maker: true };
+        let fills = book.replay_trade(&print);
+        assert_eq!(fills[0].maker_id, mine);
+        assert_eq!(book.order(mine).unwrap().quantity, 1.5);
+
+        let mut book = OrderBook::new(0.1);
+        let mut flow = OrderFlow::new(OrderFlowConfig::default());
+        let mut printed = 0;
+        for i in 0..2_000 {
+            printed += flow.step(&mut book, &i.to_string()).len();
+            if let (Some(bid), Some(ask)) = (book.best_bid(), book.best_ask()) {
+                assert!(bid < ask, "crossed book at step {}", i);
+            }
+        }
+        assert!(printed > 0);
+        assert!(!book.is_empty());
+    }
+}
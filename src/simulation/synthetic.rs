@@ -0,0 +1,302 @@
+use chrono::{DateTime, Duration, Utc};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::data::bar::Bar;
+use crate::simulation::Market;
+
+const SECONDS_PER_YEAR: f64 = 365.0 * 24.0 * 3600.0;
+
+/// A price process advanced one bar at a time.
+pub trait PriceModel: Send {
+    /// Log return over a step of `dt` years from `price`, and the volatility of that
+    /// step (standard deviation of the log return) used to shape the bar's range.
+    fn step(&mut self, price: f64, dt: f64, rng: &mut StdRng) -> (f64, f64);
+}
+
+/// Geometric Brownian motion; `mu` and `sigma` are annualized.
+#[derive(Debug, Clone)]
+pub struct Gbm {
+    pub mu: f64,
+    pub sigma: f64,
+}
+
+impl PriceModel for Gbm {
+    fn step(&mut self, _price: f64, dt: f64, rng: &mut StdRng) -> (f64, f64) {
+        let vol = self.sigma * dt.sqrt();
+        ((self.mu - 0.5 * self.sigma * self.sigma) * dt + vol * standard_normal(rng), vol)
+    }
+}
+
+/// Merton jump-diffusion: GBM plus Poisson jumps with normal log-sizes.
+#[derive(Debug, Clone)]
+pub struct JumpDiffusion {
+    pub mu: f64,
+    pub sigma: f64,
+    /// Expected jumps per year.
+    pub jump_intensity: f64,
+    pub jump_mean: f64,
+    pub jump_std: f64,
+}
+
+impl PriceModel for JumpDiffusion {
+    fn step(&mut self, _price: f64, dt: f64, rng: &mut StdRng) -> (f64, f64) {
+        let vol = self.sigma * dt.sqrt();
+        let mut log_ret = (self.mu - 0.5 * self.sigma * self.sigma) * dt + vol * standard_normal(rng);
+        for _ in 0..poisson(rng, self.jump_intensity * dt) {
+            log_ret += self.jump_mean + self.jump_std * standard_normal(rng);
+        }
+        (log_ret, vol)
+    }
+}
+
+/// GARCH(1,1) returns. `omega`, `alpha`, `beta` and `mu` are per bar, not annualized.
+#[derive(Debug, Clone)]
+pub struct Garch {
+    pub mu: f64,
+    pub omega: f64,
+    pub alpha: f64,
+    pub beta: f64,
+    variance: f64,
+    last_shock: f64,
+}
+
+impl Garch {
+    /// Starts at the unconditional variance `omega / (1 - alpha - beta)`.
+    pub fn new(mu: f64, omega: f64, alpha: f64, beta: f64) -> Self {
+        let persistence = (alpha + beta).min(0.999);
+        Self {
+            mu,
+            omega,
+            alpha,
+            beta,
+            variance: omega / (1.0 - persistence),
+            last_shock: 0.0,
+        }
+    }
+
+    pub fn variance(&self) -> f64 {
+        self.variance
+    }
+}
+
+impl PriceModel for Garch {
+    fn step(&mut self, _price: f64, _dt: f64, rng: &mut StdRng) -> (f64, f64) {
+        self.variance = self.omega + self.alpha * self.last_shock.powi(2) + self.beta * self.variance;
+        let vol = self.variance.sqrt();
+        self.last_shock = vol * standard_normal(rng);
+        (self.mu + self.last_shock, vol)
+    }
+}
+
+/// GBM whose annualized `(mu, sigma)` follows a Markov chain over regimes.
+#[derive(Debug, Clone)]
+pub struct RegimeSwitching {
+    pub regimes: Vec<(f64, f64)>,
+    /// `transition[i][j]`: chance of moving from regime `i` to `j` on each bar.
+    pub transition: Vec<Vec<f64>>,
+    pub state: usize,
+}
+
+impl PriceModel for RegimeSwitching {
+    fn step(&mut self, price: f64, dt: f64, rng: &mut StdRng) -> (f64, f64) {
+        if let Some(row) = self.transition.get(self.state) {
+            let mut u = rng.r#gen::<f64>();
+            for (j, p) in row.iter().enumerate() {
+                u -= p;
+                if u <= 0.0 {
+                    self.state = j;
+                    break;
+                }
+            }
+        }
+        let (mu, sigma) = self.regimes[self.state.min(self.regimes.len() - 1)];
+        Gbm { mu, sigma }.step(price, dt, rng)
+    }
+}
+
+/// Mean-reverting Ornstein–Uhlenbeck process on log price, pulled toward `mean_price`.
+/// `theta` (reversion speed) and `sigma` are annualized.
+#[derive(Debug, Clone)]
+pub struct OrnsteinUhlenbeck {
+    pub theta: f64,
+    pub mean_price: f64,
+    pub sigma: f64,
+}
+
+impl PriceModel for OrnsteinUhlenbeck {
+    fn step(&mut self, price: f64, dt: f64, rng: &mut StdRng) -> (f64, f64) {
+        // Exact discretization of dx = theta (ln m - x) dt + sigma dW.
+        let x = price.ln();
+        let decay = (-self.theta * dt).exp();
+        let mean = self.mean_price.ln() + (x - self.mean_price.ln()) * decay;
+        let vol = if self.theta > 0.0 {
+            self.sigma * ((1.0 - decay * decay) / (2.0 * self.theta)).sqrt()
+        } else {
+            self.sigma * dt.sqrt()
+        };
+        (mean + vol * standard_normal(rng) - x, vol)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SyntheticConfig {
+    pub start_price: f64,
+    pub start: DateTime<Utc>,
+    pub interval: Duration,
+    pub seed: u64,
+    /// Intrabar points used to draw high and low.
+    pub substeps: usize,
+    pub base_volume: f64,
+}
+
+impl Default for SyntheticConfig {
+    fn default() -> Self {
+        Self {
+            start_price: 100.0,
+            start: DateTime::from_timestamp(1_704_067_200, 0).unwrap(), // 2024-01-01
+            interval: Duration::hours(1),
+            seed: 42,
+            substeps: 16,
+            base_volume: 1_000.0,
+        }
+    }
+}
+
+/// Endless, seeded stream of bars from a price model.
+pub struct BarStream<M: PriceModel> {
+    model: M,
+    market: Market,
+    config: SyntheticConfig,
+    rng: StdRng,
+    next_time: DateTime<Utc>,
+}
+
+impl<M: PriceModel> BarStream<M> {
+    pub fn new(model: M, config: SyntheticConfig) -> Self {
+        Self {
+            model,
+            market: Market::new(config.start_price),
+            rng: StdRng::seed_from_u64(config.seed),
+            next_time: config.start,
+            config,
+        }
+    }
+}
+
+impl<M: PriceModel> Iterator for BarStream<M> {
+    type Item = Bar;
+
+    fn next(&mut self) -> Option<Bar> {
+        let dt = self.config.interval.num_seconds() as f64 / SECONDS_PER_YEAR;
+        let open = self.market.last_close;
+        let (log_ret, vol) = self.model.step(open, dt, &mut self.rng);
+        let close = open * log_ret.exp();
+
+        // Brownian bridge from open to close gives the intrabar extremes.
+        let n = self.config.substeps.max(1);
+        let sub_vol = vol / (n as f64).sqrt();
+        let (mut high, mut low) = (open.max(close), open.min(close));
+        let mut walk = 0.0;
+        let mut path = Vec::with_capacity(n);
+        for _ in 0..n {
+            walk += sub_vol * standard_normal(&mut self.rng);
+            path.push(walk);
+        }
+        for (i, w) in path.iter().enumerate() {
+            let t = (i + 1) as f64 / n as f64;
+            let point = open * (w - t * walk + t * log_ret).exp();
+            high = high.max(point);
+            low = low.min(point);
+        }
+
+        // Volume grows with the size of the move relative to its volatility.
+        let surprise = if vol > 0.0 { log_ret.abs() / vol } else { 0.0 };
+        let volume = self.config.base_volume * (0.5 * standard_normal(&mut self.rng)).exp() * (1.0 + surprise);
+
+        let bar = Bar {
+            timestamp: self.next_time.to_rfc3339(),
+            open,
+            high,
+            low,
+            close,
+            volume,
+        };
+        self.market.last_close = close;
+        self.next_time += self.config.interval;
+        Some(bar)
+    }
+}
+
+/// `count` bars from `model`.
+pub fn generate<M: PriceModel>(model: M, config: SyntheticConfig, count: usize) -> Vec<Bar> {
+    BarStream::new(model, config).take(count).collect()
+}
+
+/// Box–Muller standard normal draw.
+pub fn standard_normal(rng: &mut StdRng) -> f64 {
+    let u1: f64 = rng.r#gen::<f64>().max(f64::MIN_POSITIVE);
+    let u2: f64 = rng.r#gen();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
+/// Knuth's Poisson sampler; fine for the small rates used per bar.
+fn poisson(rng: &mut StdRng, lambda: f64) -> u32 {
+    if lambda <= 0.0 {
+        return 0;
+    }
+    let limit = (-lambda).exp();
+    let mut k = 0;
+    let mut p = rng.r#gen::<f64>();
+    while p > limit {
+        k += 1;
+        p *= rng.r#gen::<f64>();
+    }
+    k
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn valid(bars: &[Bar]) -> bool {
+        bars.iter().all(|b| {
+            b.low <= b.open.min(b.close) && b.high >= b.open.max(b.close) && b.low > 0.0 && b.volume > 0.0
+        })
+    }
+
+    #[test]
+    fn test_gbm_is_seeded_and_well_formed() {
+        let a = generate(Gbm { mu: 0.1, sigma: 0.6 }, SyntheticConfig::default(), 500);
+        let b = generate(Gbm { mu: 0.1, sigma: 0.6 }, SyntheticConfig::default(), 500);
+        assert_eq!(a.len(), 500);
+        assert!(valid(&a));
+        assert!(a.iter().zip(&b).all(|(x, y)| x.close == y.close));
+        assert!(a.windows(2).all(|w| w[1].open == w[0].close));
+        assert_eq!(a[1].timestamp, "2024-01-01T01:00:00+00:00");
+    }
+
+    #[test]
+    fn test_models_produce_valid_bars() {
+        let config = SyntheticConfig::default();
+        let jumps = JumpDiffusion { mu: 0.0, sigma: 0.5, jump_intensity: 50.0, jump_mean: -0.05, jump_std: 0.02 };
+        assert!(valid(&generate(jumps, config.clone(), 300)));
+        let garch = Garch::new(0.0, 1e-6, 0.1, 0.85);
+        assert!(valid(&generate(garch, config.clone(), 300)));
+        let regimes = RegimeSwitching {
+            regimes: vec![(0.5, 0.3), (-0.5, 1.2)],
+            transition: vec![vec![0.98, 0.02], vec![0.05, 0.95]],
+            state: 0,
+        };
+        assert!(valid(&generate(regimes, config, 300)));
+    }
+
+    #[test]
+    fn test_ou_reverts_to_mean() {
+        let config = SyntheticConfig { start_price: 200.0, ..Default::default() };
+        let ou = OrnsteinUhlenbeck { theta: 200.0, mean_price: 100.0, sigma: 0.2 };
+        let bars = generate(ou, config, 2_000);
+        let tail_mean = bars[1_000..].iter().map(|b| b.close).sum::<f64>() / 1_000.0;
+        assert!((tail_mean - 100.0).abs() < 5.0, "tail mean {}", tail_mean);
+    }
+}
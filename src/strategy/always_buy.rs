@@ -60,7 +60,7 @@ mod tests {
             high: 105.0,
             low: 99.0,
             close: 104.0,
-            volume: 8700.0,
+            volume: 870.0,
         };
         let strategy = AlwaysBuy;
         let order = strategy.generate_signal(&bar);
@@ -62,7 +62,7 @@ mod tests {
             high: 102.0,
             low: 95.0,
             close: 97.0,
-            volume: 3000.0,
+            volume: 300.0,
         };
         let strategy = AlwaysSell;
         let order = strategy.generate_signal(&bar);
@@ -1,7 +1,11 @@
+use std::sync::Mutex;
+
 use crate::data::{
     bar::Bar,
     order::{Order, OrderSide},
 };
+use crate::portfolio::rebalance::Target;
+use crate::strategy::Strategy;
 
 pub struct EmaSwitchStrategy {
     ema_short_period: usize,
@@ -36,17 +40,16 @@ impl EmaSwitchStrategy {
         ema
     }
 
-    /// Returns 0..n orders for this bar (0 when no crossover)
-    pub fn generate_signal(&mut self, bar: &Bar) -> Vec<Order> {
-        let mut orders = Vec::new();
-
+    /// Takes in `bar` and returns the trend it implies, or `None` while the long EMA is
+    /// still warming up. Doesn't commit the trend.
+    fn next_trend(&mut self, bar: &Bar) -> Option<Option<OrderSide>> {
         // MUTATE self first (mutable borrow only here)
         self.ema_short.push(bar.close);
         self.ema_long.push(bar.close);
 
         // If we don't have long-period history yet, skip
         if self.ema_long.len() < self.ema_long_period {
-            return orders;
+            return None;
         }
 
         // Prepare slices to pass into pure helper (no borrowing of self required by helper)
@@ -65,12 +68,40 @@ impl EmaSwitchStrategy {
         let long_ema = Self::calc_ema_from_slice(long_slice, self.ema_long_period);
 
         // Determine new trend
-        let new_trend = if short_ema > long_ema {
+        Some(if short_ema > long_ema {
             Some(OrderSide::Buy)
         } else if short_ema < long_ema {
             Some(OrderSide::Sell)
         } else {
-            self.current_trend.clone()
+            self.current_trend
+        })
+    }
+
+    /// The trend as a position when it flips: fully long after the short EMA crosses
+    /// above the long one, fully short after it crosses below. `None` otherwise.
+    pub fn target(&mut self, bar: &Bar) -> Option<Target> {
+        let new_trend = self.next_trend(bar)?;
+        if new_trend == self.current_trend {
+            return None;
+        }
+        self.current_trend = new_trend;
+        match new_trend? {
+            OrderSide::Buy => {
+                self.position = 1;
+                Some(Target::Weight(1.0))
+            }
+            OrderSide::Sell => {
+                self.position = -1;
+                Some(Target::Weight(-1.0))
+            }
+        }
+    }
+
+    /// Returns 0..n orders for this bar (0 when no crossover)
+    pub fn generate_signal(&mut self, bar: &Bar) -> Vec<Order> {
+        let mut orders = Vec::new();
+        let Some(new_trend) = self.next_trend(bar) else {
+            return orders;
         };
 
         // If trend flipped, generate square-off + open orders
@@ -141,3 +172,23 @@ impl EmaSwitchStrategy {
         orders
     }
 }
+
+/// `EmaSwitchStrategy` as a `Strategy`, for the engine and `run_live`: it trades the
+/// trend through `target` and never sends orders of its own.
+pub struct EmaSwitch(Mutex<EmaSwitchStrategy>);
+
+impl EmaSwitch {
+    pub fn new(short: usize, long: usize) -> Self {
+        Self(Mutex::new(EmaSwitchStrategy::new(short, long)))
+    }
+}
+
+impl Strategy for EmaSwitch {
+    fn generate_signal(&self, _bar: &Bar) -> Option<Order> {
+        None
+    }
+
+    fn target(&self, bar: &Bar) -> Option<Target> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).target(bar)
+    }
+}
@@ -0,0 +1,58 @@
+use crate::data::bar::Bar;
+
+/// Average true range with Wilder smoothing, seeded by the simple mean of the first
+/// `period` true ranges.
+#[derive(Debug, Clone)]
+pub struct Atr {
+    period: usize,
+    prev_close: Option<f64>,
+    seed: Vec<f64>,
+    value: Option<f64>,
+}
+
+impl Atr {
+    pub fn new(period: usize) -> Self {
+        Self {
+            period: period.max(1),
+            prev_close: None,
+            seed: Vec::new(),
+            value: None,
+        }
+    }
+
+    /// Adds a bar and returns the ATR once `period` bars have been seen.
+    pub fn update(&mut self, bar: &Bar) -> Option<f64> {
+        let tr = match self.prev_close {
+            Some(pc) => (bar.high - bar.low).max((bar.high - pc).abs()).max((bar.low - pc).abs()),
+            None => bar.high - bar.low,
+        };
+        self.prev_close = Some(bar.close);
+        let n = self.period as f64;
+        self.value = match self.value {
+            Some(atr) => Some((atr * (n - 1.0) + tr) / n),
+            None => {
+                self.seed.push(tr);
+                (self.seed.len() == self.period).then(|| self.seed.iter().sum::<f64>() / n)
+            }
+        };
+        self.value
+    }
+
+    pub fn value(&self) -> Option<f64> {
+        self.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_atr_seeds_then_smooths() {
+        let bar = |h: f64, l: f64, c: f64| Bar { timestamp: String::new(), open: c, high: h, low: l, close: c, volume: 0.0 };
+        let mut atr = Atr::new(2);
+        assert_eq!(atr.update(&bar(11.0, 9.0, 10.0)), None);
+        assert_eq!(atr.update(&bar(14.0, 12.0, 13.0)), Some(3.0)); // TR 2 and 4 (gap from 10)
+        assert_eq!(atr.update(&bar(14.0, 13.0, 13.5)), Some(2.0)); // (3 + 1) / 2
+    }
+}
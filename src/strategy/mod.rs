@@ -1,9 +1,49 @@
 pub mod always_buy;
 pub mod always_sell;
 pub mod ema_switch;
+pub mod indicators;
 
-use crate::data::{bar::Bar, order::Order};
+use crate::broker::bracket::BracketSpec;
+use crate::data::{bar::Bar, depth::BookLevels, order::{Order, Signal}};
+use crate::portfolio::rebalance::Target;
+use crate::risk::RiskEvent;
 
 pub trait Strategy: Send + Sync{
     fn generate_signal(&self, bar: &Bar) -> Option<Order>;
+
+    /// Signal with the order book as of the bar, when L2 data is being replayed.
+    /// Strategies that ignore the book can leave this alone.
+    fn generate_signal_with_book(&self, bar: &Bar, _book: Option<&BookLevels>) -> Option<Order> {
+        self.generate_signal(bar)
+    }
+
+    /// Direction and conviction for position sizing. Defaults to the order from
+    /// `generate_signal` at full conviction.
+    fn signal(&self, bar: &Bar) -> Option<Signal> {
+        self.generate_signal(bar).map(Signal::from)
+    }
+
+    /// `signal`, or the order from `generate_signal_with_book` when a book is available.
+    fn signal_with_book(&self, bar: &Bar, book: Option<&BookLevels>) -> Option<Signal> {
+        match book {
+            Some(_) => self.generate_signal_with_book(bar, book).map(Signal::from),
+            None => self.signal(bar),
+        }
+    }
+
+    /// Desired position in the bar's symbol. Strategies that think in exposure return
+    /// this instead of orders and the engine trades the difference; when it is `Some`,
+    /// `signal` is not consulted.
+    fn target(&self, _bar: &Bar) -> Option<Target> {
+        None
+    }
+
+    /// Stops and targets attached to each entry placed from `signal`, i.e. to orders that
+    /// add to the position. Needs a broker that supports `Broker::place_bracket`.
+    fn bracket(&self) -> Option<BracketSpec> {
+        None
+    }
+
+    /// Told about rejected orders, daily-loss halts and kill-switch trips.
+    fn on_risk_event(&self, _event: &RiskEvent) {}
 }
\ No newline at end of file